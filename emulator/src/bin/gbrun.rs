@@ -0,0 +1,325 @@
+//! 无界面运行ROM, 供脚本和CI使用
+//!
+//! ```text
+//! gbrun <ROM> [--frames N] [--until-serial STR] [--until-pc ADDR]
+//!             [--screenshot PNG] [--serial FILE] [--dump JSON] [--timestamp MS]
+//!             [--trace FILE] [--trace-labels] [--trace-doctor] [--sym FILE] [--gdb PORT|ADDR|-]
+//!             [--profile FOLDED] [--cheat CODE]... [--patch IPS|UPS|BPS]...
+//! ```
+//!
+//! `--patch`在加载前按顺序应用补丁
+//!
+//! `--cheat`添加Game Genie或GameShark金手指, 可以重复
+//!
+//! `--profile`把调用链写入folded stack文件(供flamegraph使用), 并输出热点报告
+//!
+//! `--gdb`在TCP端口(只有端口时监听127.0.0.1)或标准输入输出(`-`)上等待GDB连接, 此时忽略运行和停止条件,
+//! 客户端分离后再输出截图等结果
+//!
+//! `--trace`按gameboy-doctor格式记录执行的每条指令, `--trace-labels`在有符号的地址前插入符号行,
+//! `--trace-doctor`让LY总是读出0x90, 使记录可以与gameboy-doctor的参考记录逐行比较
+//!
+//! 符号文件默认为ROM同名的`.sym`文件, 此时`--until-pc`也可以是符号名. 出错时打印调用栈
+//!
+//! 退出码: 0 正常结束或满足停止条件; 1 模拟器出错; 2 参数错误; 3 未在限定帧数内满足停止条件
+
+use std::{
+    env,
+    fs::{self, File},
+    io::BufWriter,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use emulator::{
+    anyerror,
+    debug::gdb::{GdbServer, Stdio},
+    dev::ppu::graphic::{ScreenBitmap, SCREEN_HEIGHT, SCREEN_WIDTH},
+    emulator::{HeadlessEmulator, CYCLES_PER_FRAME},
+    error::EmuResult,
+    types::Addr,
+};
+
+const USAGE: &str = "usage: gbrun <ROM> [--frames N] [--until-serial STR] [--until-pc ADDR] \
+[--screenshot PNG] [--serial FILE] [--dump JSON] [--timestamp MS] [--trace FILE] [--trace-labels] \
+[--trace-doctor] [--sym FILE] [--gdb PORT|ADDR|-] [--profile FOLDED] [--cheat CODE]... [--patch IPS|UPS|BPS]...";
+
+/// 热点报告的行数
+const PROFILE_TOP: usize = 20;
+
+#[derive(Default)]
+struct Args {
+    rom: String,
+    frames: u32,
+    until_serial: Option<String>,
+    /// 地址或符号名, 加载符号后解析
+    until_pc: Option<String>,
+    screenshot: Option<String>,
+    serial: Option<String>,
+    dump: Option<String>,
+    timestamp: i64,
+    trace: Option<String>,
+    trace_labels: bool,
+    trace_doctor: bool,
+    sym: Option<String>,
+    gdb: Option<String>,
+    profile: Option<String>,
+    cheats: Vec<String>,
+    patches: Vec<String>,
+}
+
+fn parse_addr(s: &str) -> Option<Addr> {
+    let s = s
+        .trim_start_matches("0x")
+        .trim_start_matches("0X")
+        .trim_start_matches('$');
+    Addr::from_str_radix(s, 16).ok()
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        frames: 600,
+        ..Default::default()
+    };
+    let mut rom = None;
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--frames" => {
+                let v = value()?;
+                args.frames = v.parse().map_err(|_| format!("invalid frame count: {v}"))?;
+            }
+            "--until-serial" => args.until_serial = Some(value()?),
+            "--until-pc" => args.until_pc = Some(value()?),
+            "--screenshot" => args.screenshot = Some(value()?),
+            "--serial" => args.serial = Some(value()?),
+            "--dump" => args.dump = Some(value()?),
+            "--trace" => args.trace = Some(value()?),
+            "--trace-labels" => args.trace_labels = true,
+            "--trace-doctor" => args.trace_doctor = true,
+            "--sym" => args.sym = Some(value()?),
+            "--gdb" => args.gdb = Some(value()?),
+            "--profile" => args.profile = Some(value()?),
+            "--cheat" => args.cheats.push(value()?),
+            "--patch" => args.patches.push(value()?),
+            "--timestamp" => {
+                let v = value()?;
+                args.timestamp = v.parse().map_err(|_| format!("invalid timestamp: {v}"))?;
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {arg}")),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(format!("unexpected argument: {arg}")),
+        }
+    }
+    args.rom = rom.ok_or_else(|| USAGE.to_string())?;
+    Ok(args)
+}
+
+fn write_png(path: &str, bitmap: &ScreenBitmap) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("{path}: {err}"))?;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        SCREEN_WIDTH as u32,
+        SCREEN_HEIGHT as u32,
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    // 像素按 r | g << 8 | b << 16 | a << 24 存放, 小端字节序即为RGBA
+    let data: Vec<u8> = bitmap
+        .iter()
+        .flatten()
+        .flat_map(|pixel| pixel.to_le_bytes())
+        .collect();
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|err| format!("{path}: {err}"))
+}
+
+/// 在不断增长的串口输出中查找`needle`, 每次只检查新增的字节和与之前重叠的`needle.len() - 1`个字节
+struct SerialMatcher<'a> {
+    needle: &'a [u8],
+    scanned: usize,
+}
+
+impl SerialMatcher<'_> {
+    fn found(&mut self, output: &[u8]) -> bool {
+        if self.needle.is_empty() {
+            return true;
+        }
+        let start = self
+            .scanned
+            .min(output.len())
+            .saturating_sub(self.needle.len() - 1);
+        self.scanned = output.len();
+        output[start..]
+            .windows(self.needle.len())
+            .any(|w| w == self.needle)
+    }
+}
+
+fn serve_gdb(emulator: &mut HeadlessEmulator, addr: &str) -> EmuResult {
+    let res = if addr == "-" {
+        GdbServer::new(Stdio::new()).serve(emulator)
+    } else {
+        let addr = if addr.bytes().all(|b| b.is_ascii_digit()) {
+            format!("127.0.0.1:{addr}")
+        } else {
+            addr.to_string()
+        };
+        TcpListener::bind(&addr)
+            .and_then(|listener| {
+                eprintln!("waiting for gdb on {addr}");
+                listener.accept()
+            })
+            .and_then(|(stream, peer)| {
+                eprintln!("gdb connected from {peer}");
+                GdbServer::new(stream).serve(emulator)
+            })
+    };
+    res.or_else(|err| anyerror!("gdb: {err}"))
+}
+
+fn run(args: Args) -> Result<ExitCode, String> {
+    let rom = fs::read(&args.rom).map_err(|err| format!("{}: {err}", args.rom))?;
+    let patches = args
+        .patches
+        .iter()
+        .map(|path| fs::read(path).map_err(|err| format!("{path}: {err}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    let patches: Vec<_> = patches.iter().map(Vec::as_slice).collect();
+    let mut emulator = HeadlessEmulator::default();
+    let info = emulator
+        .load_cart_patched(rom.into_boxed_slice(), &patches, args.timestamp)
+        .map_err(|err| format!("{}: {err}", args.rom))?;
+    eprintln!("loaded {:?} ({})", info.title, info.cart_type);
+
+    let default_sym = Path::new(&args.rom).with_extension("sym");
+    let sym = match &args.sym {
+        Some(path) => Some(PathBuf::from(path)),
+        None => default_sym.exists().then_some(default_sym),
+    };
+    if let Some(path) = sym {
+        let text = fs::read_to_string(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let count = emulator
+            .load_symbols(&text)
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        eprintln!("loaded {count} symbols from {}", path.display());
+    }
+    let until_pc = match &args.until_pc {
+        Some(v) => Some(
+            parse_addr(v)
+                .or_else(|| emulator.debugger().symbols.lookup(v).map(|(_, addr)| addr))
+                .ok_or_else(|| format!("invalid address or unknown symbol: {v}"))?,
+        ),
+        None => None,
+    };
+
+    for code in &args.cheats {
+        emulator
+            .cheats_mut()
+            .add(code)
+            .map_err(|err| err.to_string())?;
+    }
+
+    if let Some(path) = &args.trace {
+        let file = File::create(path).map_err(|err| format!("{path}: {err}"))?;
+        let tracer = &mut emulator.debugger_mut().tracer;
+        tracer.start_writer(file);
+        tracer.set_labels(args.trace_labels);
+        tracer.set_doctor(args.trace_doctor);
+    }
+    if args.profile.is_some() {
+        emulator.debugger_mut().profiler.start();
+    }
+    // 出错时打印调用栈
+    emulator.debugger_mut().set_track_calls(true);
+
+    let mut serial = args.until_serial.as_deref().map(|s| SerialMatcher {
+        needle: s.as_bytes(),
+        scanned: 0,
+    });
+    let has_condition = args.gdb.is_none() && (serial.is_some() || until_pc.is_some());
+    let mut stop = |emu: &HeadlessEmulator| {
+        serial
+            .as_mut()
+            .is_some_and(|m| m.found(emu.serial_output().bytes()))
+            || until_pc.is_some_and(|pc| emu.core().cpu.pc() == pc)
+    };
+    // 逐帧运行, 避免帧数乘以每帧周期数溢出
+    let mut frames = 0;
+    let res = match &args.gdb {
+        Some(addr) => serve_gdb(&mut emulator, addr).map(|_| false),
+        None => loop {
+            if frames == args.frames {
+                break Ok(false);
+            }
+            frames += 1;
+            match emulator.run_until(CYCLES_PER_FRAME, &mut stop) {
+                Ok(false) => {}
+                res => break res,
+            }
+        },
+    };
+    emulator.present();
+    emulator.debugger_mut().tracer.stop();
+    if let (Some(path), Some(profile)) = (&args.profile, emulator.debugger_mut().profiler.stop()) {
+        let symbols = &emulator.debugger().symbols;
+        fs::write(path, profile.folded(symbols)).map_err(|err| format!("{path}: {err}"))?;
+        eprint!("{}", profile.report(symbols, PROFILE_TOP));
+    }
+
+    if args.gdb.is_some() {
+        frames = emulator.cycles() / CYCLES_PER_FRAME;
+    }
+    let code = match &res {
+        Ok(true) => {
+            eprintln!("stop condition reached after {frames} frames");
+            ExitCode::SUCCESS
+        }
+        Ok(false) if has_condition => {
+            eprintln!("stop condition not reached within {} frames", args.frames);
+            ExitCode::from(3)
+        }
+        Ok(false) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("emulator error after {frames} frames: {err}");
+            for line in emulator.backtrace() {
+                eprintln!("    at {line}");
+            }
+            ExitCode::FAILURE
+        }
+    };
+
+    if let Some(path) = &args.screenshot {
+        write_png(path, emulator.screen_output().frame())?;
+    }
+    if let Some(path) = &args.serial {
+        fs::write(path, emulator.serial_output().bytes())
+            .map_err(|err| format!("{path}: {err}"))?;
+    }
+    if let Some(path) = &args.dump {
+        let json = serde_json::to_string_pretty(&emulator.dump()).map_err(|err| err.to_string())?;
+        fs::write(path, json).map_err(|err| format!("{path}: {err}"))?;
+    }
+    Ok(code)
+}
+
+fn main() -> ExitCode {
+    match parse_args() {
+        Ok(args) => run(args).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }),
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! 反汇编器, 按操作码的`xx yyy zzz`位段解码, 包括CB前缀指令
+//!
+//! ref https://gbdev.io/gb-opcodes/optables/
+
+use serde::Serialize;
+
+use crate::{
+    dev::Bus,
+    types::{Addr, ClockCycle, Word},
+};
+
+const R: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+const RP: [&str; 4] = ["BC", "DE", "HL", "SP"];
+const RP2: [&str; 4] = ["BC", "DE", "HL", "AF"];
+const CC: [&str; 4] = ["NZ", "Z", "NC", "C"];
+const ALU: [&str; 8] = [
+    "ADD A,", "ADC A,", "SUB ", "SBC A,", "AND ", "XOR ", "OR ", "CP ",
+];
+const ROT: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+const MISC: [&str; 8] = ["RLCA", "RRCA", "RLA", "RRA", "DAA", "CPL", "SCF", "CCF"];
+const OPERAND_MHL: usize = 6;
+
+#[allow(non_snake_case)]
+mod tsify_derive {
+    use tsify::Tsify;
+
+    use super::*;
+
+    #[derive(Serialize, Tsify, Debug, Clone, PartialEq, Eq)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Instruction {
+        pub addr: Addr,
+        /// 1-3字节
+        pub bytes: Vec<Word>,
+        /// 时钟周期, 条件跳转为不跳转时的周期
+        pub cycles: ClockCycle,
+        /// 条件跳转跳转时的周期
+        pub cycles_taken: Option<ClockCycle>,
+        /// 如`JR NZ,$0150`, `LD A,($FF44)`
+        pub text: String,
+        /// 跳转或调用的目标地址
+        pub target: Option<Addr>,
+        /// 指令地址处的符号
+        pub label: Option<String>,
+    }
+}
+
+pub use tsify_derive::Instruction;
+
+impl Instruction {
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// 下一条指令的地址
+    pub fn next(&self) -> Addr {
+        self.addr.wrapping_add(self.len() as Addr)
+    }
+}
+
+/// 解码过程中的中间结果
+struct Decoded {
+    len: u8,
+    cycles: ClockCycle,
+    cycles_taken: Option<ClockCycle>,
+    text: String,
+    target: Option<Addr>,
+}
+
+impl Decoded {
+    fn new(len: u8, cycles: ClockCycle, text: impl Into<String>) -> Self {
+        Self {
+            len,
+            cycles,
+            cycles_taken: None,
+            text: text.into(),
+            target: None,
+        }
+    }
+
+    /// 条件跳转, `taken`为跳转时的周期
+    fn branch(mut self, taken: ClockCycle) -> Self {
+        self.cycles_taken = Some(taken);
+        self
+    }
+
+    fn jump(mut self, target: Addr) -> Self {
+        self.target = Some(target);
+        self
+    }
+}
+
+/// 解码`addr`处的指令, `fetch`读取一个字节
+pub fn decode(addr: Addr, fetch: impl Fn(Addr) -> Word) -> Instruction {
+    let opcode = fetch(addr);
+    let d8 = fetch(addr.wrapping_add(1));
+    let d16 = Addr::from_le_bytes([d8, fetch(addr.wrapping_add(2))]);
+    // 相对跳转的目标
+    let rel = addr.wrapping_add(2).wrapping_add(d8 as i8 as Addr);
+    let e8 = {
+        let e = d8 as i8;
+        if e < 0 {
+            format!("-${:02X}", e.unsigned_abs())
+        } else {
+            format!("+${e:02X}")
+        }
+    };
+
+    let x = opcode >> 6;
+    let y = ((opcode >> 3) & 0b111) as usize;
+    let z = (opcode & 0b111) as usize;
+    let p = y >> 1;
+    let q = y & 1;
+    // 操作数为(HL)时多一次访存
+    let mhl = |r: usize, cycles: ClockCycle, extra: ClockCycle| {
+        if r == OPERAND_MHL {
+            cycles + extra
+        } else {
+            cycles
+        }
+    };
+
+    let decoded = match (x, z) {
+        (0, 0) => match y {
+            0 => Decoded::new(1, 4, "NOP"),
+            1 => Decoded::new(3, 20, format!("LD (${d16:04X}),SP")),
+            2 => Decoded::new(2, 4, "STOP"),
+            3 => Decoded::new(2, 12, format!("JR ${rel:04X}")).jump(rel),
+            _ => Decoded::new(2, 8, format!("JR {},${rel:04X}", CC[y - 4]))
+                .branch(12)
+                .jump(rel),
+        },
+        (0, 1) if q == 0 => Decoded::new(3, 12, format!("LD {},${d16:04X}", RP[p])),
+        (0, 1) => Decoded::new(1, 8, format!("ADD HL,{}", RP[p])),
+        (0, 2) => {
+            let mem = ["(BC)", "(DE)", "(HL+)", "(HL-)"][p];
+            if q == 0 {
+                Decoded::new(1, 8, format!("LD {mem},A"))
+            } else {
+                Decoded::new(1, 8, format!("LD A,{mem}"))
+            }
+        }
+        (0, 3) => {
+            let op = if q == 0 { "INC" } else { "DEC" };
+            Decoded::new(1, 8, format!("{op} {}", RP[p]))
+        }
+        (0, 4) => Decoded::new(1, mhl(y, 4, 8), format!("INC {}", R[y])),
+        (0, 5) => Decoded::new(1, mhl(y, 4, 8), format!("DEC {}", R[y])),
+        (0, 6) => Decoded::new(2, mhl(y, 8, 4), format!("LD {},${d8:02X}", R[y])),
+        (0, 7) => Decoded::new(1, 4, MISC[y]),
+        (1, _) if y == OPERAND_MHL && z == OPERAND_MHL => Decoded::new(1, 4, "HALT"),
+        (1, _) => Decoded::new(1, mhl(y, mhl(z, 4, 4), 4), format!("LD {},{}", R[y], R[z])),
+        (2, _) => Decoded::new(1, mhl(z, 4, 4), format!("{}{}", ALU[y], R[z])),
+        (3, 0) => match y {
+            // 返回地址在栈上, 无法静态确定
+            0..=3 => Decoded::new(1, 8, format!("RET {}", CC[y])).branch(20),
+            4 => Decoded::new(2, 12, format!("LDH ($FF{d8:02X}),A")),
+            5 => Decoded::new(2, 16, format!("ADD SP,{e8}")),
+            6 => Decoded::new(2, 12, format!("LDH A,($FF{d8:02X})")),
+            _ => Decoded::new(2, 12, format!("LD HL,SP{e8}")),
+        },
+        (3, 1) if q == 0 => Decoded::new(1, 12, format!("POP {}", RP2[p])),
+        (3, 1) => match p {
+            0 => Decoded::new(1, 16, "RET"),
+            1 => Decoded::new(1, 16, "RETI"),
+            2 => Decoded::new(1, 4, "JP HL"),
+            _ => Decoded::new(1, 8, "LD SP,HL"),
+        },
+        (3, 2) => match y {
+            0..=3 => Decoded::new(3, 12, format!("JP {},${d16:04X}", CC[y]))
+                .branch(16)
+                .jump(d16),
+            4 => Decoded::new(1, 8, "LD ($FF00+C),A"),
+            5 => Decoded::new(3, 16, format!("LD (${d16:04X}),A")),
+            6 => Decoded::new(1, 8, "LD A,($FF00+C)"),
+            _ => Decoded::new(3, 16, format!("LD A,(${d16:04X})")),
+        },
+        (3, 3) => match y {
+            0 => Decoded::new(3, 16, format!("JP ${d16:04X}")).jump(d16),
+            1 => decode_cb(d8),
+            6 => Decoded::new(1, 4, "DI"),
+            7 => Decoded::new(1, 4, "EI"),
+            _ => illegal(opcode),
+        },
+        (3, 4) if y < 4 => Decoded::new(3, 12, format!("CALL {},${d16:04X}", CC[y]))
+            .branch(24)
+            .jump(d16),
+        (3, 5) if q == 0 => Decoded::new(1, 16, format!("PUSH {}", RP2[p])),
+        (3, 5) if p == 0 => Decoded::new(3, 24, format!("CALL ${d16:04X}")).jump(d16),
+        (3, 6) => Decoded::new(2, 8, format!("{}${d8:02X}", ALU[y])),
+        (3, 7) => {
+            let vec = (y * 8) as Addr;
+            Decoded::new(1, 16, format!("RST ${vec:02X}")).jump(vec)
+        }
+        _ => illegal(opcode),
+    };
+
+    Instruction {
+        addr,
+        bytes: (0..decoded.len as Addr)
+            .map(|i| fetch(addr.wrapping_add(i)))
+            .collect(),
+        cycles: decoded.cycles,
+        cycles_taken: decoded.cycles_taken,
+        text: decoded.text,
+        target: decoded.target,
+        label: None,
+    }
+}
+
+fn decode_cb(opcode: Word) -> Decoded {
+    let x = opcode >> 6;
+    let y = ((opcode >> 3) & 0b111) as usize;
+    let z = (opcode & 0b111) as usize;
+    let (text, cycles) = match x {
+        0 => (format!("{} {}", ROT[y], R[z]), 16),
+        1 => (format!("BIT {y},{}", R[z]), 12),
+        2 => (format!("RES {y},{}", R[z]), 16),
+        _ => (format!("SET {y},{}", R[z]), 16),
+    };
+    let cycles = if z == OPERAND_MHL { cycles } else { 8 };
+    Decoded::new(2, cycles, text)
+}
+
+fn illegal(opcode: Word) -> Decoded {
+    Decoded::new(1, 4, format!("DB ${opcode:02X}"))
+}
+
+/// 反汇编`addr`处的指令, 不触发观察点. 加载了符号时标注指令地址, 并把操作数中的地址替换为符号
+pub fn disassemble(bus: &Bus, addr: Addr) -> Instruction {
+    let mut inst = decode(addr, |addr| bus.peek(addr));
+    let symbols = &bus.debugger.symbols;
+    if !symbols.is_empty() {
+        inst.label = symbols.name(bus.rom_bank(addr), addr).map(str::to_string);
+        if let Some(text) = symbolize(bus, &inst.text) {
+            inst.text = text;
+        }
+    }
+    inst
+}
+
+/// 替换操作数中的16位地址, 如`CALL $4000`变为`CALL VBlankHandler`
+fn symbolize(bus: &Bus, text: &str) -> Option<String> {
+    let start = text.find('$')?;
+    let hex = text.get(start + 1..start + 5)?;
+    let rest = &text[start + 5..];
+    // `($FF00+C)`不是地址
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) || rest.starts_with('+') {
+        return None;
+    }
+    let addr = Addr::from_str_radix(hex, 16).ok()?;
+    let name = bus.debugger.symbols.name(bus.rom_bank(addr), addr)?;
+    Some(format!("{}{name}{rest}", &text[..start]))
+}
+
+/// 反汇编`pc`前`before`条和`pc`起`after`条指令, 供调试器显示.
+///
+/// 变长指令无法向前解码, 从`pc`之前尽量远的位置开始解码, 取恰好落在`pc`上的一种
+pub fn disassemble_around(bus: &Bus, pc: Addr, before: usize, after: usize) -> Vec<Instruction> {
+    let mut lines = Vec::new();
+    for back in (1..=before * 3).rev() {
+        let mut addr = pc.wrapping_sub(back as Addr);
+        let mut candidate = Vec::new();
+        while addr != pc && (pc.wrapping_sub(addr) as usize) <= back {
+            let inst = disassemble(bus, addr);
+            addr = inst.next();
+            candidate.push(inst);
+        }
+        if addr == pc && candidate.len() >= before {
+            lines = candidate.split_off(candidate.len() - before);
+            break;
+        }
+    }
+    let mut addr = pc;
+    for _ in 0..after {
+        let inst = disassemble(bus, addr);
+        addr = inst.next();
+        lines.push(inst);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::decode;
+    use crate::emulator::{HeadlessEmulator, CYCLES_PER_FRAME};
+
+    fn text(bytes: &[u8]) -> (String, usize, u32) {
+        let inst = decode(0x0100, |addr| {
+            bytes.get((addr - 0x0100) as usize).copied().unwrap_or(0)
+        });
+        (inst.text.clone(), inst.len(), inst.cycles)
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(text(&[0x00]), ("NOP".into(), 1, 4));
+        assert_eq!(text(&[0xC3, 0x50, 0x01]), ("JP $0150".into(), 3, 16));
+        assert_eq!(text(&[0x20, 0xFE]), ("JR NZ,$0100".into(), 2, 8));
+        assert_eq!(text(&[0x3E, 0x91]), ("LD A,$91".into(), 2, 8));
+        assert_eq!(text(&[0xE0, 0x40]), ("LDH ($FF40),A".into(), 2, 12));
+        assert_eq!(text(&[0xF8, 0xF0]), ("LD HL,SP-$10".into(), 2, 12));
+        assert_eq!(text(&[0x36, 0x00]), ("LD (HL),$00".into(), 2, 12));
+        assert_eq!(text(&[0x7E]), ("LD A,(HL)".into(), 1, 8));
+        assert_eq!(text(&[0x76]), ("HALT".into(), 1, 4));
+        assert_eq!(text(&[0x86]), ("ADD A,(HL)".into(), 1, 8));
+        assert_eq!(text(&[0xFE, 0x90]), ("CP $90".into(), 2, 8));
+        assert_eq!(text(&[0xCB, 0x7C]), ("BIT 7,H".into(), 2, 8));
+        assert_eq!(text(&[0xCB, 0x46]), ("BIT 0,(HL)".into(), 2, 12));
+        assert_eq!(text(&[0xCB, 0x37]), ("SWAP A".into(), 2, 8));
+        assert_eq!(text(&[0xCB, 0xFE]), ("SET 7,(HL)".into(), 2, 16));
+        assert_eq!(text(&[0xFF]), ("RST $38".into(), 1, 16));
+        assert_eq!(text(&[0xD3]), ("DB $D3".into(), 1, 4));
+
+        let call = decode(0x0100, |addr| [0xC4, 0x34, 0x12][(addr - 0x0100) as usize]);
+        assert_eq!(call.cycles_taken, Some(24));
+        assert_eq!(call.target, Some(0x1234));
+    }
+
+    #[test]
+    fn test_disassemble_around() {
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        emulator.run(CYCLES_PER_FRAME * 30).unwrap();
+        let pc = emulator.dump().pc;
+        let lines = emulator.disassemble(5, 5);
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[5].addr, pc);
+        for pair in lines.windows(2) {
+            assert_eq!(pair[0].next(), pair[1].addr);
+        }
+    }
+
+    #[test]
+    fn test_symbols() {
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        // 0x0100: NOP; JP $0150
+        emulator
+            .load_symbols("00:0100 Boot\n00:0150 Start\n")
+            .unwrap();
+        let lines = emulator.disassemble(0, 2);
+        assert_eq!(lines[0].label.as_deref(), Some("Boot"));
+        assert_eq!(lines[1].label, None);
+        assert_eq!(lines[1].text, "JP Start");
+        assert_eq!(lines[1].target, Some(0x0150));
+    }
+}
@@ -0,0 +1,596 @@
+//! GDB远程串行协议服务端, 供原生构建用GDB等前端调试
+//!
+//! 寄存器依次为`af, bc, de, hl, sp, pc`, 各16位小端, 目标描述见[`TARGET_XML`].
+//! 支持读写寄存器和内存, 断点(`Z0`/`Z1`), 观察点(`Z2`-`Z4`), 单步和继续运行.
+//! 通过TCP连接时可以用Ctrl-C中断运行
+//!
+//! ref https://sourceware.org/gdb/current/onlinedocs/gdb.html/Remote-Protocol.html
+
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    net::TcpStream,
+};
+
+use log::warn;
+
+use super::{Access, Breakpoint, StopReason, Watchpoint};
+use crate::{
+    emulator::Emulator,
+    error::IllegalInstruction,
+    output::{audio::AudioOutput, screen::ScreenOutput, screen::TileOutput, serial::SerialOutput},
+    types::{Addr, DWord},
+};
+
+pub const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.sm83.core">
+    <reg name="af" bitsize="16" type="int"/>
+    <reg name="bc" bitsize="16" type="int"/>
+    <reg name="de" bitsize="16" type="int"/>
+    <reg name="hl" bitsize="16" type="data_ptr"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+const REG_COUNT: usize = 6;
+/// Ctrl-C
+const INTERRUPT: u8 = 0x03;
+
+/// 与客户端的连接
+pub trait Connection: Read + Write {
+    /// 运行期间检查客户端是否请求中断, 不支持时总是返回`false`
+    fn interrupted(&mut self) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+impl Connection for TcpStream {
+    fn interrupted(&mut self) -> io::Result<bool> {
+        let mut byte = [0];
+        self.set_nonblocking(true)?;
+        let res = self.peek(&mut byte);
+        self.set_nonblocking(false)?;
+        match res {
+            Ok(1) if byte[0] == INTERRUPT => self.read_exact(&mut byte).map(|_| true),
+            Ok(_) => Ok(false),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// 标准输入输出, 用于`target remote | gbrun ROM --gdb -`, 不支持中断
+pub struct Stdio {
+    stdin: io::Stdin,
+    stdout: io::Stdout,
+}
+
+impl Default for Stdio {
+    fn default() -> Self {
+        Self {
+            stdin: io::stdin(),
+            stdout: io::stdout(),
+        }
+    }
+}
+
+impl Stdio {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Read for Stdio {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdin.read(buf)
+    }
+}
+
+impl Write for Stdio {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdout.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+impl Connection for Stdio {}
+
+/// 处理完一个请求后的动作
+enum Handled {
+    Reply(String),
+    /// 客户端分离, 结束会话
+    Detach,
+    /// 客户端结束调试, 不需要应答
+    Kill,
+}
+
+pub struct GdbServer<C> {
+    conn: C,
+    /// GDB的(类型, 地址, 长度)到调试器断点编号
+    points: HashMap<(u8, Addr, Addr), u32>,
+    /// 最近一次停止的应答, 用于`?`
+    last_stop: String,
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, b| sum.wrapping_add(*b))
+}
+
+fn hex(s: &[u8]) -> Option<DWord> {
+    DWord::from_str_radix(std::str::from_utf8(s).ok()?, 16).ok()
+}
+
+fn hex_bytes(s: &[u8]) -> Option<Vec<u8>> {
+    s.chunks(2)
+        .map(|pair| hex(pair).filter(|_| pair.len() == 2).map(|b| b as u8))
+        .collect()
+}
+
+/// 解析`addr,len`
+fn addr_len(s: &[u8]) -> Option<(Addr, Addr)> {
+    let mut parts = s.splitn(2, |b| *b == b',');
+    Some((hex(parts.next()?)?, hex(parts.next()?)?))
+}
+
+impl<C: Connection> GdbServer<C> {
+    pub fn new(conn: C) -> Self {
+        Self {
+            conn,
+            points: HashMap::new(),
+            last_stop: "S05".to_string(),
+        }
+    }
+
+    /// 处理请求直到客户端分离或断开连接
+    pub fn serve<S, T, A, O>(&mut self, emulator: &mut Emulator<S, T, A, O>) -> io::Result<()>
+    where
+        S: ScreenOutput,
+        T: TileOutput,
+        A: AudioOutput,
+        O: SerialOutput,
+    {
+        while let Some(packet) = self.recv()? {
+            match self.handle(emulator, &packet)? {
+                Handled::Reply(reply) => self.send(&reply)?,
+                Handled::Detach => {
+                    self.send("OK")?;
+                    break;
+                }
+                Handled::Kill => break,
+            }
+        }
+        for (_, id) in self.points.drain() {
+            emulator.debugger_mut().remove(id);
+        }
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.conn.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// 读取一个数据包, 连接关闭时返回`None`. 包之间的应答和停止时收到的中断被忽略
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'$') => break,
+                    Some(_) => {}
+                }
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(b) => data.push(b),
+                }
+            }
+            let mut sum = [0; 2];
+            self.conn.read_exact(&mut sum)?;
+            if hex(&sum) == Some(checksum(&data) as DWord) {
+                self.conn.write_all(b"+")?;
+                return Ok(Some(data));
+            }
+            // 校验失败, 请求重发
+            self.conn.write_all(b"-")?;
+            self.conn.flush()?;
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${data}#{:02x}", checksum(data.as_bytes()));
+        self.conn.write_all(packet.as_bytes())?;
+        self.conn.flush()
+    }
+
+    fn handle<S, T, A, O>(
+        &mut self,
+        emulator: &mut Emulator<S, T, A, O>,
+        packet: &[u8],
+    ) -> io::Result<Handled>
+    where
+        S: ScreenOutput,
+        T: TileOutput,
+        A: AudioOutput,
+        O: SerialOutput,
+    {
+        let Some((&cmd, args)) = packet.split_first() else {
+            return Ok(Handled::Reply(String::new()));
+        };
+        let reply = match cmd {
+            b'?' => self.last_stop.clone(),
+            b'g' => read_regs(emulator),
+            b'G' => match hex_bytes(args) {
+                Some(bytes) if bytes.len() == REG_COUNT * 2 => {
+                    for (n, pair) in bytes.chunks(2).enumerate() {
+                        write_reg(emulator, n, DWord::from_le_bytes([pair[0], pair[1]]));
+                    }
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            b'p' => match hex(args).map(usize::from).and_then(|n| reg(emulator, n)) {
+                Some(value) => le_hex(value),
+                None => "E01".to_string(),
+            },
+            b'P' => {
+                let parsed = args
+                    .iter()
+                    .position(|b| *b == b'=')
+                    .and_then(|eq| Some((hex(&args[..eq])?, hex_bytes(&args[eq + 1..])?)));
+                match parsed {
+                    Some((n, value)) if (n as usize) < REG_COUNT && value.len() == 2 => {
+                        write_reg(
+                            emulator,
+                            n as usize,
+                            DWord::from_le_bytes([value[0], value[1]]),
+                        );
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            b'm' => match addr_len(args) {
+                Some((addr, len)) => (0..len)
+                    .map(|i| format!("{:02x}", emulator.peek(addr.wrapping_add(i))))
+                    .collect(),
+                None => "E01".to_string(),
+            },
+            b'M' => {
+                let parsed = args.iter().position(|b| *b == b':').and_then(|colon| {
+                    Some((addr_len(&args[..colon])?, hex_bytes(&args[colon + 1..])?))
+                });
+                match parsed {
+                    Some(((addr, len), bytes)) if bytes.len() == len as usize => {
+                        for (i, byte) in bytes.into_iter().enumerate() {
+                            emulator.poke(addr.wrapping_add(i as Addr), byte);
+                        }
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            b'c' | b's' => {
+                if let Some(addr) = hex(args) {
+                    *emulator.core_mut().cpu.pc_mut() = addr;
+                }
+                self.resume(emulator, cmd == b's')?
+            }
+            b'v' if args == b"Cont?" => "vCont;c;s".to_string(),
+            b'v' if args.starts_with(b"Cont;") => match args.get(5) {
+                Some(b'c') => self.resume(emulator, false)?,
+                Some(b's') => self.resume(emulator, true)?,
+                _ => String::new(),
+            },
+            b'Z' | b'z' => self.toggle_point(emulator, cmd == b'Z', args),
+            b'q' if args.starts_with(b"Supported") => {
+                "PacketSize=1000;qXfer:features:read+".to_string()
+            }
+            b'q' if args.starts_with(b"Xfer:features:read:target.xml:") => {
+                match addr_len(&args[b"Xfer:features:read:target.xml:".len()..]) {
+                    Some((offset, len)) => {
+                        let xml = TARGET_XML.as_bytes();
+                        let start = (offset as usize).min(xml.len());
+                        let end = (start + len as usize).min(xml.len());
+                        let more = if end < xml.len() { "m" } else { "l" };
+                        format!("{more}{}", String::from_utf8_lossy(&xml[start..end]))
+                    }
+                    None => "E01".to_string(),
+                }
+            }
+            b'q' if args == b"Attached" => "1".to_string(),
+            b'q' if args == b"fThreadInfo" => "m1".to_string(),
+            b'q' if args == b"sThreadInfo" => "l".to_string(),
+            b'q' if args == b"C" => "QC1".to_string(),
+            b'H' | b'T' => "OK".to_string(),
+            b'D' => return Ok(Handled::Detach),
+            b'k' => return Ok(Handled::Kill),
+            _ => String::new(),
+        };
+        Ok(Handled::Reply(reply))
+    }
+
+    /// 单步或继续运行直到断点, 观察点, 出错或客户端中断, 返回停止应答
+    fn resume<S, T, A, O>(
+        &mut self,
+        emulator: &mut Emulator<S, T, A, O>,
+        step: bool,
+    ) -> io::Result<String>
+    where
+        S: ScreenOutput,
+        T: TileOutput,
+        A: AudioOutput,
+        O: SerialOutput,
+    {
+        let stop = loop {
+            let res = if step {
+                emulator.run_cycles(1)
+            } else {
+                emulator.run_frame()
+            };
+            match res {
+                Ok(res) => match res.stop {
+                    Some(reason) => break self.stop_reply(emulator, reason),
+                    None if step => break "S05".to_string(),
+                    None if self.conn.interrupted()? => break "S02".to_string(),
+                    None => {}
+                },
+                Err(err) => {
+                    warn!("{err}");
+                    break match *err {
+                        IllegalInstruction { .. } => "S04".to_string(),
+                        _ => "S06".to_string(),
+                    };
+                }
+            }
+        };
+        self.last_stop = stop.clone();
+        Ok(stop)
+    }
+
+    fn stop_reply<S, T, A, O>(&self, emulator: &Emulator<S, T, A, O>, reason: StopReason) -> String
+    where
+        S: ScreenOutput,
+        T: TileOutput,
+        A: AudioOutput,
+        O: SerialOutput,
+    {
+        match reason {
+            StopReason::Breakpoint { .. } => "S05".to_string(),
+            StopReason::Watchpoint { id, addr, .. } => {
+                let kind = emulator
+                    .debugger()
+                    .watchpoints()
+                    .find(|(i, _)| *i == id)
+                    .map(|(_, w)| w.access);
+                let kind = match kind {
+                    Some(Access::Read) => "rwatch",
+                    Some(Access::ReadWrite) => "awatch",
+                    _ => "watch",
+                };
+                format!("T05{kind}:{addr:04x};")
+            }
+        }
+    }
+
+    /// `Z`/`z`: 类型,地址,长度
+    fn toggle_point<S, T, A, O>(
+        &mut self,
+        emulator: &mut Emulator<S, T, A, O>,
+        insert: bool,
+        args: &[u8],
+    ) -> String
+    where
+        S: ScreenOutput,
+        T: TileOutput,
+        A: AudioOutput,
+        O: SerialOutput,
+    {
+        let parsed = args.split_first().and_then(|(kind, rest)| {
+            let (addr, len) = addr_len(rest.strip_prefix(b",")?)?;
+            Some((*kind, addr, len))
+        });
+        let Some(key @ (kind, addr, len)) = parsed else {
+            return "E01".to_string();
+        };
+        let debugger = emulator.debugger_mut();
+        if !insert {
+            return match self.points.remove(&key) {
+                Some(id) => {
+                    debugger.remove(id);
+                    "OK".to_string()
+                }
+                None => "E01".to_string(),
+            };
+        }
+        let access = match kind {
+            b'0' | b'1' => None,
+            b'2' => Some(Access::Write),
+            b'3' => Some(Access::Read),
+            b'4' => Some(Access::ReadWrite),
+            _ => return String::new(),
+        };
+        let id = match access {
+            None => debugger.add_breakpoint(Breakpoint::at(addr)),
+            Some(access) => debugger.add_watchpoint(Watchpoint {
+                range: addr..=addr.saturating_add(len.max(1) - 1),
+                access,
+            }),
+        };
+        if let Some(old) = self.points.insert(key, id) {
+            debugger.remove(old);
+        }
+        "OK".to_string()
+    }
+}
+
+fn reg<S, T, A, O>(emulator: &Emulator<S, T, A, O>, n: usize) -> Option<DWord>
+where
+    S: ScreenOutput,
+    T: TileOutput,
+    A: AudioOutput,
+    O: SerialOutput,
+{
+    let cpu = &emulator.core().cpu;
+    let value = match n {
+        0 => cpu.af(),
+        1 => cpu.bc(),
+        2 => cpu.de(),
+        3 => cpu.hl(),
+        4 => cpu.sp(),
+        5 => cpu.pc(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn write_reg<S, T, A, O>(emulator: &mut Emulator<S, T, A, O>, n: usize, value: DWord)
+where
+    S: ScreenOutput,
+    T: TileOutput,
+    A: AudioOutput,
+    O: SerialOutput,
+{
+    let cpu = &mut emulator.core_mut().cpu;
+    match n {
+        // F的低4位恒为0
+        0 => *cpu.af_mut() = value & 0xFFF0,
+        1 => *cpu.bc_mut() = value,
+        2 => *cpu.de_mut() = value,
+        3 => *cpu.hl_mut() = value,
+        4 => *cpu.sp_mut() = value,
+        _ => *cpu.pc_mut() = value,
+    }
+}
+
+fn le_hex(value: DWord) -> String {
+    let [lo, hi] = value.to_le_bytes();
+    format!("{lo:02x}{hi:02x}")
+}
+
+fn read_regs<S, T, A, O>(emulator: &Emulator<S, T, A, O>) -> String
+where
+    S: ScreenOutput,
+    T: TileOutput,
+    A: AudioOutput,
+    O: SerialOutput,
+{
+    (0..REG_COUNT)
+        .filter_map(|n| reg(emulator, n))
+        .map(le_hex)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        io::{self, Cursor, Read, Write},
+    };
+
+    use super::{checksum, Connection, GdbServer};
+    use crate::emulator::HeadlessEmulator;
+
+    /// 预先写好全部请求的连接
+    struct Script {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Script {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Script {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Connection for Script {}
+
+    fn session(requests: &[&str]) -> Vec<String> {
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        let input = requests
+            .iter()
+            .map(|req| format!("+${req}#{:02x}", checksum(req.as_bytes())))
+            .collect::<String>();
+        let mut server = GdbServer::new(Script {
+            input: Cursor::new(input.into_bytes()),
+            output: Vec::new(),
+        });
+        server.serve(&mut emulator).unwrap();
+        assert_eq!(emulator.debugger().breakpoints().count(), 0);
+        let output = String::from_utf8(server.conn.output).unwrap();
+        output
+            .split('$')
+            .skip(1)
+            .map(|packet| {
+                let (data, sum) = packet.split_once('#').unwrap();
+                assert_eq!(&sum[..2], format!("{:02x}", checksum(data.as_bytes())));
+                data.to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_gdb() {
+        let replies = session(&[
+            "qSupported:multiprocess+;xmlRegisters=i386",
+            "qXfer:features:read:target.xml:0,10",
+            "g",
+            "m100,4",
+            "Z0,150,1",
+            "c",
+            "p5",
+            "z0,150,1",
+            "s",
+            "P1=3412",
+            "p1",
+            "Mc000,2:abcd",
+            "mc000,2",
+            "Z2,c000,2000",
+            "vCont;c",
+            "D",
+        ]);
+        assert!(replies[0].contains("qXfer:features:read+"));
+        assert_eq!(replies[1], "m<?xml version=\"1");
+        // af=01B0 bc=0013 de=00D8 hl=014D sp=FFFE pc=0100
+        assert_eq!(replies[2], "b0011300d8004d01feff0001");
+        assert_eq!(replies[3], "00c35001");
+        assert_eq!(replies[4], "OK");
+        assert_eq!(replies[5], "S05");
+        assert_eq!(replies[6], "5001");
+        assert_eq!(replies[7], "OK");
+        assert_eq!(replies[8], "S05");
+        assert_eq!(replies[9], "OK");
+        assert_eq!(replies[10], "3412");
+        assert_eq!(replies[11], "OK");
+        assert_eq!(replies[12], "abcd");
+        assert_eq!(replies[13], "OK");
+        assert!(replies[14].starts_with("T05watch:c") || replies[14].starts_with("T05watch:d"));
+        assert_eq!(replies[15], "OK");
+        assert_eq!(replies.len(), 16);
+    }
+}
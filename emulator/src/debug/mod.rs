@@ -0,0 +1,565 @@
+//! 调试器: PC断点(可指定ROM bank和寄存器条件)和内存读写观察点
+//!
+//! 断点在`CPU::tick`执行指令前检查, 命中时不执行该指令; 观察点在`Bus::read`/`Bus::write`中检查,
+//! 命中时当前指令照常执行完毕. 命中后由`Emulator`的运行函数停止并返回[`StopReason`].
+//! 没有断点, 跟踪, 性能分析且未开启调用栈记录时, CPU每条指令只检查一次[`Debugger::active`].
+//! 指令跟踪见[`trace`], 符号文件见[`symbols`], 性能分析见[`profile`], GDB远程调试见`gdb`
+
+pub mod disasm;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gdb;
+pub mod profile;
+pub mod symbols;
+pub mod trace;
+
+use std::{
+    cell::{Cell, LazyCell},
+    fmt,
+    ops::RangeInclusive,
+};
+
+use profile::Profiler;
+use symbols::Symbols;
+use trace::Tracer;
+
+use crate::{
+    dev::CPU,
+    types::{Addr, ClockCycle, DWord, Word},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reg {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
+impl Reg {
+    pub fn get(self, cpu: &CPU) -> DWord {
+        match self {
+            Reg::A => cpu.a() as DWord,
+            Reg::F => cpu.f() as DWord,
+            Reg::B => cpu.b() as DWord,
+            Reg::C => cpu.c() as DWord,
+            Reg::D => cpu.d() as DWord,
+            Reg::E => cpu.e() as DWord,
+            Reg::H => cpu.h() as DWord,
+            Reg::L => cpu.l() as DWord,
+            Reg::AF => cpu.af(),
+            Reg::BC => cpu.bc(),
+            Reg::DE => cpu.de(),
+            Reg::HL => cpu.hl(),
+            Reg::SP => cpu.sp(),
+            Reg::PC => cpu.pc(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// 寄存器条件, 如`A == 0x10`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub reg: Reg,
+    pub op: CmpOp,
+    pub value: DWord,
+}
+
+impl Condition {
+    pub fn eval(&self, cpu: &CPU) -> bool {
+        let reg = self.reg.get(cpu);
+        match self.op {
+            CmpOp::Eq => reg == self.value,
+            CmpOp::Ne => reg != self.value,
+            CmpOp::Lt => reg < self.value,
+            CmpOp::Le => reg <= self.value,
+            CmpOp::Gt => reg > self.value,
+            CmpOp::Ge => reg >= self.value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// 为`None`时每条指令都检查条件
+    pub addr: Option<Addr>,
+    /// 地址位于ROM时, 只在映射了该bank时命中
+    pub bank: Option<usize>,
+    /// 全部满足时命中
+    pub conds: Vec<Condition>,
+}
+
+impl Breakpoint {
+    pub fn at(addr: Addr) -> Self {
+        Self {
+            addr: Some(addr),
+            ..Default::default()
+        }
+    }
+
+    pub fn at_bank(addr: Addr, bank: usize) -> Self {
+        Self {
+            addr: Some(addr),
+            bank: Some(bank),
+            ..Default::default()
+        }
+    }
+
+    pub fn when(mut self, reg: Reg, op: CmpOp, value: DWord) -> Self {
+        self.conds.push(Condition { reg, op, value });
+        self
+    }
+
+    /// `bank`只在需要比较bank时求值
+    fn hit(&self, cpu: &CPU, bank: impl Fn() -> Option<usize>) -> bool {
+        self.addr.is_none_or(|addr| addr == cpu.pc())
+            && self.bank.is_none_or(|b| bank() == Some(b))
+            && self.conds.iter().all(|cond| cond.eval(cpu))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    fn contains(self, other: Access) -> bool {
+        self == Access::ReadWrite || self == other
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: RangeInclusive<Addr>,
+    pub access: Access,
+}
+
+/// 运行停止的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// 停在断点处, 该指令尚未执行
+    Breakpoint {
+        id: u32,
+        pc: Addr,
+        bank: Option<usize>,
+    },
+    /// 观察的地址被访问, 访问所在的指令已执行完毕
+    Watchpoint {
+        id: u32,
+        addr: Addr,
+        value: Word,
+        access: Access,
+    },
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            StopReason::Breakpoint { id, pc, bank } => match bank {
+                Some(bank) => write!(f, "breakpoint #{id} at {bank:02X}:{pc:04X}"),
+                None => write!(f, "breakpoint #{id} at {pc:04X}"),
+            },
+            StopReason::Watchpoint {
+                id,
+                addr,
+                value,
+                access,
+            } => {
+                let access = match access {
+                    Access::Write => "write",
+                    _ => "read",
+                };
+                write!(f, "watchpoint #{id}: {access} 0x{value:02X} at {addr:04X}")
+            }
+        }
+    }
+}
+
+/// 调用栈的一帧
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// CALL/RST指令的地址, 中断时为被打断的PC
+    pub call_site: Addr,
+    /// `call_site`所在的ROM bank
+    pub bank: Option<usize>,
+    pub target: Addr,
+    pub target_bank: Option<usize>,
+    /// 压入返回地址后的SP, 返回时据此出栈
+    pub sp: Addr,
+    pub interrupt: bool,
+}
+
+/// 不返回而是直接丢弃返回地址的代码会让调用栈不断增长, 超出时丢弃最早的帧
+const MAX_CALL_DEPTH: usize = 256;
+
+#[derive(Default)]
+pub struct Debugger {
+    next_id: u32,
+    breakpoints: Vec<(u32, Breakpoint)>,
+    watchpoints: Vec<(u32, Watchpoint)>,
+    stop: Cell<Option<StopReason>>,
+    /// 从断点处继续运行时跳过该断点一次
+    resume_pc: Cell<Option<Addr>>,
+    track_calls: bool,
+    call_stack: Vec<Frame>,
+    pub tracer: Tracer,
+    pub profiler: Profiler,
+    pub symbols: Symbols,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn next_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> u32 {
+        let id = self.next_id();
+        self.breakpoints.push((id, breakpoint));
+        id
+    }
+
+    /// 在符号处设置断点, 如`VBlankHandler`, 符号不存在时返回`None`
+    pub fn add_breakpoint_at(&mut self, symbol: &str) -> Option<u32> {
+        let (bank, addr) = self.symbols.lookup(symbol)?;
+        let breakpoint = if symbols::ROMX.contains(&addr) {
+            Breakpoint::at_bank(addr, bank)
+        } else {
+            Breakpoint::at(addr)
+        };
+        Some(self.add_breakpoint(breakpoint))
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> u32 {
+        let id = self.next_id();
+        self.watchpoints.push((id, watchpoint));
+        id
+    }
+
+    /// 删除断点或观察点, 返回是否存在
+    pub fn remove(&mut self, id: u32) -> bool {
+        let len = self.breakpoints.len() + self.watchpoints.len();
+        self.breakpoints.retain(|(i, _)| *i != id);
+        self.watchpoints.retain(|(i, _)| *i != id);
+        len != self.breakpoints.len() + self.watchpoints.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+        self.resume_pc.set(None);
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (u32, &Breakpoint)> {
+        self.breakpoints.iter().map(|(id, b)| (*id, b))
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = (u32, &Watchpoint)> {
+        self.watchpoints.iter().map(|(id, w)| (*id, w))
+    }
+
+    /// 没有断点, 跟踪和性能分析时也记录调用栈, 用于出错时的回溯
+    pub fn set_track_calls(&mut self, track_calls: bool) {
+        self.track_calls = track_calls;
+    }
+
+    /// 是否需要在执行指令前后介入, 为`false`时调用栈不再更新
+    #[inline]
+    pub fn active(&self) -> bool {
+        self.track_calls
+            || !self.breakpoints.is_empty()
+            || self.tracer.enabled()
+            || self.profiler.enabled()
+    }
+
+    /// 是否需要当前指令所在的ROM bank: 性能分析, 或跟踪时输出符号行
+    #[inline]
+    pub fn needs_bank(&self) -> bool {
+        self.profiler.enabled() || self.tracer.enabled() && self.tracer.labels()
+    }
+
+    /// 调用栈, 最近的调用在最后
+    pub fn call_stack(&self) -> &[Frame] {
+        &self.call_stack
+    }
+
+    pub fn clear_call_stack(&mut self) {
+        self.call_stack.clear()
+    }
+
+    pub fn push_frame(&mut self, frame: Frame) {
+        if self.call_stack.len() == MAX_CALL_DEPTH {
+            self.call_stack.remove(0);
+        }
+        self.call_stack.push(frame)
+    }
+
+    /// 返回后SP为`sp`, 弹出所有已返回的帧
+    pub fn pop_frames(&mut self, sp: Addr) {
+        while self.call_stack.last().is_some_and(|frame| frame.sp < sp) {
+            self.call_stack.pop();
+        }
+    }
+
+    /// 性能分析启用时, 把`at`处的`cycles`个周期计入当前调用链
+    #[inline]
+    pub fn profile(&mut self, at: Option<usize>, pc: Addr, cycles: ClockCycle) {
+        if self.profiler.enabled() {
+            let chain = self.call_stack.iter().map(|f| (f.target_bank, f.target));
+            self.profiler.record(chain, (at, pc), cycles)
+        }
+    }
+
+    /// 用符号显示地址, 见[`Symbols::format`]
+    pub fn format_addr(&self, bank: Option<usize>, addr: Addr) -> String {
+        self.symbols.format(bank, addr)
+    }
+
+    /// 取出命中的断点或观察点
+    pub fn take_stop(&self) -> Option<StopReason> {
+        self.stop.take()
+    }
+
+    /// 执行指令前调用, 命中时返回`true`, 此时不应执行指令.
+    /// `bank`返回PC所在的ROM bank, 只在比较限定bank的断点或命中时求值一次
+    #[inline]
+    pub fn check_breakpoint(&self, cpu: &CPU, bank: impl FnOnce() -> Option<usize>) -> bool {
+        if self.breakpoints.is_empty() {
+            return false;
+        }
+        let pc = cpu.pc();
+        if self.resume_pc.take() == Some(pc) {
+            return false;
+        }
+        let bank = LazyCell::new(bank);
+        let Some(&(id, _)) = self.breakpoints.iter().find(|(_, b)| b.hit(cpu, || *bank)) else {
+            return false;
+        };
+        self.resume_pc.set(Some(pc));
+        self.stop.set(Some(StopReason::Breakpoint {
+            id,
+            pc,
+            bank: *bank,
+        }));
+        true
+    }
+
+    #[inline]
+    pub fn check_read(&self, addr: Addr, value: Word) {
+        if !self.watchpoints.is_empty() {
+            self.check_access(addr, value, Access::Read)
+        }
+    }
+
+    #[inline]
+    pub fn check_write(&self, addr: Addr, value: Word) {
+        if !self.watchpoints.is_empty() {
+            self.check_access(addr, value, Access::Write)
+        }
+    }
+
+    fn check_access(&self, addr: Addr, value: Word, access: Access) {
+        let hit = self
+            .watchpoints
+            .iter()
+            .find(|(_, w)| w.access.contains(access) && w.range.contains(&addr));
+        // 一条指令多次命中时保留第一次
+        if let (Some(&(id, _)), None) = (hit, self.stop.get()) {
+            self.stop.set(Some(StopReason::Watchpoint {
+                id,
+                addr,
+                value,
+                access,
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{Access, Breakpoint, CmpOp, Reg, StopReason, Watchpoint};
+    use crate::emulator::{HeadlessEmulator, CYCLES_PER_FRAME};
+
+    fn emulator() -> HeadlessEmulator {
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        emulator
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut emulator = emulator();
+        let id = emulator
+            .debugger_mut()
+            .add_breakpoint(Breakpoint::at(0x0150));
+        let res = emulator.run_frame().unwrap();
+        assert_eq!(
+            res.stop,
+            Some(StopReason::Breakpoint {
+                id,
+                pc: 0x0150,
+                bank: Some(0)
+            })
+        );
+        assert_eq!(emulator.dump().pc, 0x0150);
+        // 从断点处继续运行不会立即再次命中
+        let res = emulator.run_cycles(16).unwrap();
+        assert_eq!(res.stop, None);
+        assert!(res.cycles >= 16);
+        assert!(emulator.debugger_mut().remove(id));
+        assert!(!emulator.debugger_mut().remove(id));
+
+        // 找到第一条在ROMX中执行的指令
+        emulator
+            .debugger_mut()
+            .add_breakpoint(Breakpoint::default().when(Reg::PC, CmpOp::Ge, 0x4000));
+        let res = emulator.run_cycles(CYCLES_PER_FRAME * 60);
+        let Some(StopReason::Breakpoint {
+            pc,
+            bank: Some(bank),
+            ..
+        }) = res.unwrap().stop
+        else {
+            panic!("never executed in switchable ROM");
+        };
+        assert!(bank > 0);
+        emulator.debugger_mut().clear();
+
+        // 只在映射了指定bank时命中
+        let save = emulator.save(0).unwrap();
+        emulator
+            .debugger_mut()
+            .add_breakpoint(Breakpoint::at_bank(pc, bank + 1));
+        assert_eq!(emulator.run_cycles(1).unwrap().stop, None);
+        emulator.load(&save).unwrap();
+        emulator.debugger_mut().clear();
+        let id = emulator
+            .debugger_mut()
+            .add_breakpoint(Breakpoint::at_bank(pc, bank));
+        let res = emulator.run_cycles(1).unwrap();
+        assert_eq!(
+            res.stop,
+            Some(StopReason::Breakpoint {
+                id,
+                pc,
+                bank: Some(bank)
+            })
+        );
+        assert_eq!(res.cycles, 0);
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut emulator = emulator();
+        let id = emulator.debugger_mut().add_watchpoint(Watchpoint {
+            range: 0xC000..=0xDFFF,
+            access: Access::Write,
+        });
+        let res = emulator.run_frame().unwrap();
+        let Some(StopReason::Watchpoint {
+            id: hit,
+            addr,
+            access,
+            ..
+        }) = res.stop
+        else {
+            panic!("no write to WRAM");
+        };
+        assert_eq!(hit, id);
+        assert_eq!(access, Access::Write);
+        assert!((0xC000..=0xDFFF).contains(&addr));
+
+        // 宿主读取内存不会触发观察点
+        emulator.debugger_mut().clear();
+        emulator.debugger_mut().add_watchpoint(Watchpoint {
+            range: 0x0000..=0xFFFF,
+            access: Access::Read,
+        });
+        emulator.dump();
+        assert_eq!(emulator.debugger().take_stop(), None);
+
+        // 取指令和立即数不会触发观察点, 只有数据读取会
+        emulator.debugger_mut().clear();
+        emulator.debugger_mut().add_watchpoint(Watchpoint {
+            range: 0x0000..=0x7FFF,
+            access: Access::Read,
+        });
+        assert_eq!(emulator.run_frame().unwrap().stop, None);
+        emulator.debugger_mut().add_watchpoint(Watchpoint {
+            range: 0x8000..=0xFFFF,
+            access: Access::Read,
+        });
+        let res = emulator.run_frame().unwrap();
+        assert!(matches!(
+            res.stop,
+            Some(StopReason::Watchpoint {
+                access: Access::Read,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_call_stack() {
+        let mut emulator = emulator();
+        // 调试器未启用时不记录调用栈
+        emulator.run_cycles(CYCLES_PER_FRAME * 60).unwrap();
+        assert!(emulator.debugger().call_stack().is_empty());
+
+        emulator.load_symbols("00:0040 VBlankInterrupt\n").unwrap();
+        assert_eq!(emulator.debugger_mut().add_breakpoint_at("Missing"), None);
+        let id = emulator
+            .debugger_mut()
+            .add_breakpoint_at("VBlankInterrupt")
+            .unwrap();
+        let res = emulator.run_cycles(CYCLES_PER_FRAME * 60).unwrap();
+        assert!(
+            matches!(res.stop, Some(StopReason::Breakpoint { id: hit, pc: 0x0040, .. }) if hit == id)
+        );
+
+        let frame = *emulator.debugger().call_stack().last().unwrap();
+        assert!(frame.interrupt);
+        assert_eq!(frame.target, 0x0040);
+        assert_eq!(frame.sp, emulator.dump().sp);
+        let backtrace = emulator.backtrace();
+        assert_eq!(backtrace[0], "VBlankInterrupt");
+        assert!(backtrace[1].ends_with("(interrupt)"));
+
+        // 调用和返回成对出现, 调用栈不会无限增长
+        emulator.debugger_mut().clear();
+        emulator.debugger_mut().set_track_calls(true);
+        emulator.run_cycles(CYCLES_PER_FRAME * 60).unwrap();
+        assert!(emulator.debugger().call_stack().len() < 16);
+        emulator.reset().unwrap();
+        assert!(emulator.debugger().call_stack().is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+//! 性能分析, 按(ROM bank, PC)和调用链累计执行的时钟周期
+//!
+//! 调用链来自调试器的调用栈, 每层以调用目标(函数入口)表示. 输出热点报告, 或供flamegraph.pl/inferno
+//! 使用的folded stack格式:
+//!
+//! ```text
+//! (root);VBlankHandler;UpdateSprites 123456
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+use super::symbols::Symbols;
+use crate::types::{Addr, ClockCycle};
+
+/// (ROM bank, 地址), 不在ROM中时bank为`None`
+pub type Location = (Option<usize>, Addr);
+
+/// 调用栈为空时的根
+const ROOT: &str = "(root)";
+
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    /// 每条指令的(周期, 执行次数)
+    pub hotspots: HashMap<Location, (u64, u64)>,
+    /// 每个调用链的周期, 不含根
+    pub stacks: HashMap<Box<[Location]>, u64>,
+    pub total: u64,
+}
+
+/// 未启用时只有一次判空的开销
+#[derive(Default)]
+pub struct Profiler {
+    profile: Option<Profile>,
+    /// 复用的调用链缓冲, 避免每条指令分配
+    chain: Vec<Location>,
+}
+
+impl Profiler {
+    pub fn enabled(&self) -> bool {
+        self.profile.is_some()
+    }
+
+    /// 开始分析, 丢弃之前的结果
+    pub fn start(&mut self) {
+        self.profile = Some(Profile::default());
+    }
+
+    /// 结束分析并取出结果
+    pub fn stop(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    /// 当前累计的结果
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// 记录`at`处花费的`cycles`个周期, `chain`为调用链(由外到内的调用目标)
+    pub fn record(
+        &mut self,
+        chain: impl Iterator<Item = Location>,
+        at: Location,
+        cycles: ClockCycle,
+    ) {
+        let Some(profile) = &mut self.profile else {
+            return;
+        };
+        let cycles = cycles as u64;
+        profile.total += cycles;
+        let hotspot = profile.hotspots.entry(at).or_default();
+        hotspot.0 += cycles;
+        hotspot.1 += 1;
+
+        self.chain.clear();
+        self.chain.extend(chain);
+        match profile.stacks.get_mut(self.chain.as_slice()) {
+            Some(total) => *total += cycles,
+            None => {
+                profile.stacks.insert(self.chain.as_slice().into(), cycles);
+            }
+        }
+    }
+}
+
+impl Profile {
+    /// 每个函数的(包含调用的周期, 自身的周期), 递归调用只计一次
+    pub fn functions(&self) -> HashMap<Option<Location>, (u64, u64)> {
+        let mut functions: HashMap<Option<Location>, (u64, u64)> = HashMap::new();
+        let mut seen = HashSet::new();
+        for (chain, &cycles) in &self.stacks {
+            seen.clear();
+            functions.entry(None).or_default().0 += cycles;
+            for &loc in chain.iter() {
+                if seen.insert(loc) {
+                    functions.entry(Some(loc)).or_default().0 += cycles;
+                }
+            }
+            functions.entry(chain.last().copied()).or_default().1 += cycles;
+        }
+        functions
+    }
+
+    /// 热点报告: 周期最多的`top`条指令和`top`个函数
+    pub fn report(&self, symbols: &Symbols, top: usize) -> String {
+        let percent = |cycles: u64| cycles as f64 * 100.0 / self.total.max(1) as f64;
+        let mut out = String::new();
+        let _ = writeln!(out, "total {} cycles", self.total);
+
+        let mut hotspots: Vec<_> = self.hotspots.iter().collect();
+        hotspots.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(b.0)));
+        let _ = writeln!(
+            out,
+            "\n{:>12} {:>7} {:>10}  instruction",
+            "cycles", "%", "count"
+        );
+        for (&(bank, addr), &(cycles, count)) in hotspots.into_iter().take(top) {
+            let _ = writeln!(
+                out,
+                "{cycles:>12} {:>6.2}% {count:>10}  {}",
+                percent(cycles),
+                location(symbols, bank, addr)
+            );
+        }
+
+        let mut functions: Vec<_> = self.functions().into_iter().collect();
+        functions.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(&b.0)));
+        let _ = writeln!(
+            out,
+            "\n{:>12} {:>7} {:>12} {:>7}  function",
+            "total", "%", "self", "%"
+        );
+        for (func, (total, own)) in functions.into_iter().take(top) {
+            let name = match func {
+                Some((bank, addr)) => location(symbols, bank, addr),
+                None => ROOT.to_string(),
+            };
+            let _ = writeln!(
+                out,
+                "{total:>12} {:>6.2}% {own:>12} {:>6.2}%  {name}",
+                percent(total),
+                percent(own)
+            );
+        }
+        out
+    }
+
+    /// folded stack格式, 每行一个调用链, 按调用链排序
+    pub fn folded(&self, symbols: &Symbols) -> String {
+        let mut lines: Vec<_> = self
+            .stacks
+            .iter()
+            .map(|(chain, cycles)| {
+                let mut line = ROOT.to_string();
+                for &(bank, addr) in chain.iter() {
+                    line.push(';');
+                    line.push_str(&symbols.format(bank, addr));
+                }
+                format!("{line} {cycles}")
+            })
+            .collect();
+        lines.sort();
+        lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+}
+
+/// 有符号时为`符号+偏移 ($01:4123)`
+fn location(symbols: &Symbols, bank: Option<usize>, addr: Addr) -> String {
+    let raw = match bank {
+        Some(bank) => format!("${bank:02X}:{addr:04X}"),
+        None => format!("${addr:04X}"),
+    };
+    let name = symbols.format(bank, addr);
+    if name == raw {
+        raw
+    } else {
+        format!("{name} ({raw})")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::emulator::{HeadlessEmulator, CYCLES_PER_FRAME};
+
+    #[test]
+    fn test_profile() {
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        emulator.run_cycles(CYCLES_PER_FRAME * 30).unwrap();
+
+        emulator.debugger_mut().profiler.start();
+        let start = emulator.cycles();
+        emulator.run_cycles(CYCLES_PER_FRAME * 30).unwrap();
+        let profile = emulator.debugger_mut().profiler.stop().unwrap();
+        assert!(!emulator.debugger().profiler.enabled());
+
+        // 所有周期都被计入
+        assert_eq!(profile.total, (emulator.cycles() - start) as u64);
+        let hotspots: u64 = profile.hotspots.values().map(|(cycles, _)| cycles).sum();
+        assert_eq!(hotspots, profile.total);
+        let functions = profile.functions();
+        assert_eq!(functions[&None].0, profile.total);
+
+        emulator.load_symbols("00:0040 VBlankInterrupt\n").unwrap();
+        let folded = profile.folded(&emulator.debugger().symbols);
+        let sum: u64 = folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().1.parse::<u64>().unwrap())
+            .sum();
+        assert_eq!(sum, profile.total);
+        assert!(folded.contains("(root);VBlankInterrupt"));
+
+        let report = profile.report(&emulator.debugger().symbols, 10);
+        assert!(report.starts_with(&format!("total {} cycles", profile.total)));
+        assert!(report.contains("VBlankInterrupt ($00:0040)"));
+    }
+}
@@ -0,0 +1,152 @@
+//! RGBDS/no$gmb格式的符号文件(`.sym`), 每行一个`bank:地址 符号`, `;`之后为注释:
+//!
+//! ```text
+//! ; File generated by rgblink
+//! 00:0150 Start
+//! 01:4000 VBlankHandler
+//! 01:4012 VBlankHandler.loop
+//! ```
+//!
+//! ref https://rgbds.gbdev.io/docs/rgblink.1#S
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{anyerror, error::EmuResult, types::Addr};
+
+/// 可切换ROM bank映射的区域, 只有这里的符号需要区分bank
+pub(super) const ROMX: std::ops::RangeInclusive<Addr> = 0x4000..=0x7FFF;
+
+#[derive(Default, Debug, Clone)]
+pub struct Symbols {
+    /// 按(地址, bank)排序, 便于查找不超过某地址的最近符号
+    by_addr: BTreeMap<(Addr, usize), String>,
+    by_name: HashMap<String, (usize, Addr)>,
+}
+
+/// 符号的`nearest`查找不跨越的内存区域
+fn area(addr: Addr) -> Addr {
+    match addr {
+        0x0000..=0x3FFF => 0,
+        0x4000..=0x7FFF => 1,
+        0x8000..=0x9FFF => 2,
+        0xA000..=0xBFFF => 3,
+        0xC000..=0xDFFF => 4,
+        0xE000..=0xFF7F => 5,
+        _ => 6,
+    }
+}
+
+impl Symbols {
+    pub fn parse(text: &str) -> EmuResult<Self> {
+        let mut symbols = Self::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let parsed = line
+                .split_once(char::is_whitespace)
+                .and_then(|(loc, name)| {
+                    let (bank, addr) = loc.split_once(':')?;
+                    let bank = usize::from_str_radix(bank, 16).ok()?;
+                    let addr = Addr::from_str_radix(addr, 16).ok()?;
+                    Some((bank, addr, name.trim()))
+                });
+            let Some((bank, addr, name)) = parsed else {
+                return anyerror!("invalid symbol at line {}: {line}", n + 1);
+            };
+            symbols.insert(bank, addr, name);
+        }
+        Ok(symbols)
+    }
+
+    /// 同一地址有多个符号时保留第一个作为显示名, 但都可以按名字查找
+    pub fn insert(&mut self, bank: usize, addr: Addr, name: &str) {
+        self.by_addr
+            .entry((addr, bank))
+            .or_insert_with(|| name.to_string());
+        self.by_name.insert(name.to_string(), (bank, addr));
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    /// 按名字查找, 返回(bank, 地址)
+    pub fn lookup(&self, name: &str) -> Option<(usize, Addr)> {
+        self.by_name.get(name).copied()
+    }
+
+    /// `addr`处的符号. 位于0x4000-0x7FFF时只匹配`bank`, 其余地址不区分bank
+    pub fn name(&self, bank: Option<usize>, addr: Addr) -> Option<&str> {
+        match bank {
+            Some(bank) if ROMX.contains(&addr) => self.by_addr.get(&(addr, bank)),
+            _ => self
+                .by_addr
+                .range((addr, 0)..=(addr, usize::MAX))
+                .next()
+                .map(|(_, name)| name),
+        }
+        .map(String::as_str)
+    }
+
+    /// 不超过`addr`的最近符号和偏移, 不跨越内存区域
+    pub fn nearest(&self, bank: Option<usize>, addr: Addr) -> Option<(&str, Addr)> {
+        self.by_addr
+            .range(..=(addr, usize::MAX))
+            .rev()
+            .take_while(|((a, _), _)| area(*a) == area(addr))
+            .find(|((a, b), _)| !ROMX.contains(a) || bank.is_none_or(|bank| bank == *b))
+            .map(|((a, _), name)| (name.as_str(), addr - a))
+    }
+
+    /// 显示地址, 如`VBlankHandler+$12`, 没有符号时为`$01:4123`或`$0150`
+    pub fn format(&self, bank: Option<usize>, addr: Addr) -> String {
+        match (self.nearest(bank, addr), bank) {
+            (Some((name, 0)), _) => name.to_string(),
+            (Some((name, offset)), _) => format!("{name}+${offset:X}"),
+            (None, Some(bank)) => format!("${bank:02X}:{addr:04X}"),
+            (None, None) => format!("${addr:04X}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Symbols;
+
+    const SYM: &str = "\
+; File generated by rgblink
+00:0150 Start
+00:0150 EntryPoint
+01:4000 VBlankHandler
+01:4012 VBlankHandler.loop
+02:4000 LoadTiles ; bank 2
+00:c000 wFrameCounter
+";
+
+    #[test]
+    fn test_symbols() {
+        let symbols = Symbols::parse(SYM).unwrap();
+        assert_eq!(symbols.len(), 6);
+        assert_eq!(symbols.lookup("LoadTiles"), Some((2, 0x4000)));
+        assert_eq!(symbols.lookup("EntryPoint"), Some((0, 0x0150)));
+        assert_eq!(symbols.name(Some(0), 0x0150), Some("Start"));
+        assert_eq!(symbols.name(Some(2), 0x4000), Some("LoadTiles"));
+        assert_eq!(symbols.name(Some(3), 0x4000), None);
+        assert_eq!(symbols.name(None, 0xC000), Some("wFrameCounter"));
+
+        assert_eq!(symbols.format(Some(1), 0x4015), "VBlankHandler.loop+$3");
+        assert_eq!(symbols.format(Some(2), 0x4015), "LoadTiles+$15");
+        assert_eq!(symbols.format(Some(3), 0x4015), "$03:4015");
+        assert_eq!(symbols.format(Some(0), 0x0200), "Start+$B0");
+        // 不跨越内存区域
+        assert_eq!(symbols.format(None, 0xFF80), "$FF80");
+
+        assert!(Symbols::parse("0150 Start").is_err());
+    }
+}
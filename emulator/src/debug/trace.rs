@@ -0,0 +1,245 @@
+//! 指令跟踪, 每条指令执行前输出一行gameboy-doctor格式的记录:
+//!
+//! ```text
+//! A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+//! ```
+//!
+//! ref https://github.com/robert/gameboy-doctor
+//!
+//! gameboy-doctor的参考记录假定LY始终读出0x90, 需要与参考记录逐行比较时用[`Tracer::set_doctor`]开启doctor模式,
+//! 否则记录会在首次读LY后出现差异
+//!
+//! 开启[`Tracer::set_labels`]后, 执行到有符号的地址时先输出一行`符号:`, 此时不再与gameboy-doctor兼容
+
+use std::io::{self, Write};
+
+use log::warn;
+
+use crate::{
+    dev::{ppu::LY_REG_ADDR, CPU},
+    types::{Addr, Word},
+};
+
+/// doctor模式下CPU读LY得到的值, 即第一条VBlank扫描线
+const DOCTOR_LY: Word = 0x90;
+
+/// 跟踪记录的去向
+enum Sink {
+    /// 缓存在内存中, 由宿主分块取走
+    Buffer(Vec<u8>),
+    Writer(io::BufWriter<Box<dyn Write>>),
+}
+
+/// 未启用时只有一次判空的开销
+#[derive(Default)]
+pub struct Tracer {
+    sink: Option<Sink>,
+    labels: bool,
+    doctor: bool,
+}
+
+impl Tracer {
+    pub fn enabled(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    /// 开始跟踪, 记录缓存在内存中, 需要宿主定期调用[`Self::take`]取走
+    pub fn start_buffered(&mut self) {
+        self.stop();
+        self.sink = Some(Sink::Buffer(Vec::new()));
+    }
+
+    /// 开始跟踪, 记录写入`writer`(如文件)
+    pub fn start_writer(&mut self, writer: impl Write + 'static) {
+        self.stop();
+        self.sink = Some(Sink::Writer(io::BufWriter::new(Box::new(writer))));
+    }
+
+    pub fn labels(&self) -> bool {
+        self.labels
+    }
+
+    /// 是否输出符号行
+    pub fn set_labels(&mut self, labels: bool) {
+        self.labels = labels;
+    }
+
+    pub fn doctor(&self) -> bool {
+        self.doctor
+    }
+
+    /// 开启doctor模式: CPU读LY时总是得到0x90, 与gameboy-doctor的参考记录一致. 会改变程序行为, 仅用于比较记录
+    pub fn set_doctor(&mut self, doctor: bool) {
+        self.doctor = doctor;
+    }
+
+    /// doctor模式下替换CPU读到的LY
+    pub fn patch_read(&self, addr: Addr, word: Word) -> Word {
+        if self.doctor && addr == LY_REG_ADDR {
+            DOCTOR_LY
+        } else {
+            word
+        }
+    }
+
+    /// 结束跟踪, 返回缓存中尚未取走的记录
+    pub fn stop(&mut self) -> Vec<u8> {
+        match self.sink.take() {
+            Some(Sink::Buffer(buf)) => buf,
+            Some(Sink::Writer(mut writer)) => {
+                if let Err(err) = writer.flush() {
+                    warn!("failed to flush trace: {err}");
+                }
+                Vec::new()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// 取走缓存中的记录
+    pub fn take(&mut self) -> Vec<u8> {
+        match &mut self.sink {
+            Some(Sink::Buffer(buf)) => std::mem::take(buf),
+            _ => Vec::new(),
+        }
+    }
+
+    /// `pcmem`为PC起的4个字节, `label`为PC处的符号
+    pub fn trace(&mut self, cpu: &CPU, pcmem: [Word; 4], label: Option<&str>) {
+        let label = label.filter(|_| self.labels);
+        let res = match &mut self.sink {
+            Some(Sink::Buffer(buf)) => write_line(buf, cpu, pcmem, label),
+            Some(Sink::Writer(writer)) => write_line(writer, cpu, pcmem, label),
+            None => return,
+        };
+        if let Err(err) = res {
+            warn!("failed to write trace, tracing stopped: {err}");
+            self.sink = None;
+        }
+    }
+}
+
+fn write_line(
+    w: &mut impl Write,
+    cpu: &CPU,
+    pcmem: [Word; 4],
+    label: Option<&str>,
+) -> io::Result<()> {
+    if let Some(label) = label {
+        writeln!(w, "{label}:")?;
+    }
+    writeln!(
+        w,
+        "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+        cpu.a(),
+        cpu.f(),
+        cpu.b(),
+        cpu.c(),
+        cpu.d(),
+        cpu.e(),
+        cpu.h(),
+        cpu.l(),
+        cpu.sp(),
+        cpu.pc(),
+        pcmem[0],
+        pcmem[1],
+        pcmem[2],
+        pcmem[3]
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::emulator::{HeadlessEmulator, CYCLES_PER_FRAME};
+
+    /// 在`lines`中找到`LDH A,(0x44)`后的一行, 返回其中的A
+    fn ly_reads<'a>(lines: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+        let mut prev_reads_ly = false;
+        let mut reads = Vec::new();
+        for line in lines {
+            if prev_reads_ly {
+                reads.push(&line[2..4]);
+            }
+            prev_reads_ly = line.contains("PCMEM:F0,44,");
+        }
+        reads
+    }
+
+    #[test]
+    fn test_trace() {
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        emulator.debugger_mut().tracer.start_buffered();
+        emulator.run_cycles(8).unwrap();
+        let chunk = String::from_utf8(emulator.debugger_mut().tracer.take()).unwrap();
+        let mut lines = chunk.lines();
+        assert_eq!(
+            lines.next(),
+            Some("A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01")
+        );
+        assert!(lines.next().unwrap().ends_with("PC:0101 PCMEM:C3,50,01,CE"));
+        assert_eq!(lines.next(), None);
+
+        // 取走的记录不再出现
+        emulator.run_cycles(CYCLES_PER_FRAME).unwrap();
+        let rest = emulator.debugger_mut().tracer.stop();
+        assert!(rest.starts_with(b"A:"));
+        assert!(!emulator.debugger().tracer.enabled());
+        emulator.run_cycles(8).unwrap();
+        assert!(emulator.debugger_mut().tracer.take().is_empty());
+    }
+
+    #[test]
+    fn test_trace_labels() {
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        emulator.load_symbols("00:0101 Jump\n").unwrap();
+        let tracer = &mut emulator.debugger_mut().tracer;
+        tracer.start_buffered();
+        tracer.set_labels(true);
+        emulator.run_cycles(8).unwrap();
+        let chunk = String::from_utf8(emulator.debugger_mut().tracer.stop()).unwrap();
+        let lines: Vec<_> = chunk.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "Jump:");
+        assert!(lines[2].ends_with("PC:0101 PCMEM:C3,50,01,CE"));
+    }
+
+    #[test]
+    fn test_trace_doctor() {
+        let rom = fs::read("../public/roms/instrs/06-ld r,r.gb").unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator
+            .load_cart(rom.clone().into_boxed_slice(), 0)
+            .unwrap();
+        let tracer = &mut emulator.debugger_mut().tracer;
+        tracer.start_buffered();
+        tracer.set_doctor(true);
+        let passed = emulator
+            .run_until(600 * CYCLES_PER_FRAME, |emu| {
+                emu.serial_output().bytes().ends_with(b"Passed\n")
+            })
+            .unwrap();
+        assert!(passed);
+        let trace = String::from_utf8(emulator.debugger_mut().tracer.stop()).unwrap();
+        assert!(trace.starts_with(
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02\n"
+        ));
+        let reads = ly_reads(trace.lines());
+        assert!(!reads.is_empty());
+        assert!(reads.iter().all(|&a| a == "90"));
+
+        // 关闭后读到真实的LY
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        let tracer = &mut emulator.debugger_mut().tracer;
+        tracer.start_buffered();
+        tracer.set_doctor(false);
+        emulator.run_cycles(60 * CYCLES_PER_FRAME).unwrap();
+        let trace = String::from_utf8(emulator.debugger_mut().tracer.stop()).unwrap();
+        assert!(ly_reads(trace.lines()).iter().any(|&a| a != "90"));
+    }
+}
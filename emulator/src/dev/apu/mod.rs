@@ -115,6 +115,7 @@ pub struct APU {
 }
 
 impl APU {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             reg_nr50: DEFAULT_REG_DMG_NR50,
@@ -145,7 +146,7 @@ impl APU {
     }
 
     fn handle_fs_step(&mut self) {
-        let is_length_period = (self.fs_step % 2) == 0;
+        let is_length_period = self.fs_step.is_multiple_of(2);
         self.chan1.set_half_length_period(is_length_period);
         self.chan2.set_half_length_period(is_length_period);
         self.chan3.set_half_length_period(is_length_period);
@@ -225,13 +226,13 @@ impl APU {
         self.chan4.tick();
 
         // Every 8192 T-cycles, the frame sequencer is stepped
-        if self.ticks % FRAME_SEQUENCER_PERIOD == 0 {
+        if self.ticks.is_multiple_of(FRAME_SEQUENCER_PERIOD) {
             self.handle_fs_step();
         }
 
         // Every sample period, we can send the current sample to the speaker
         // It's up to the speaker to store an audio buffer and play it a regular interval
-        if self.ticks % SAMPLE_PERIOD == 0 {
+        if self.ticks.is_multiple_of(SAMPLE_PERIOD) {
             let left_volume = self.volume_left();
             let right_volume = self.volume_right();
 
@@ -1,6 +1,7 @@
 use super::{
     apu::{APU, APU_ADDR_HIGH_BOUND_INCLUDED, APU_ADDR_LOW_BOUND},
-    cart::{Cart, CartInfo},
+    cart::{Cart, CartInfo, RAM_BANK_SIZE, ROM_BANK_SIZE},
+    cheat::Cheats,
     gamepad::{Buttons, BUTTON_ADDR},
     int_regs::{
         InterruptFlagRegister, InterruptMaskRegsiter, INTERRUPT_FLAG_REGISTER_ADDR,
@@ -15,12 +16,26 @@ use super::{
     MemoryRegion, Reset,
 };
 use crate::{
+    debug::Debugger,
     error::{EmuErr, EmuResult, NoCartridge},
     types::{Addr, Word},
 };
 use log::warn;
 use serde::{Deserialize, Serialize};
 
+/// 可整块读取的内存区域
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// 完整的卡带ROM
+    Rom,
+    Vram,
+    /// 外部RAM的指定bank
+    CartRam(usize),
+    Wram,
+    Oam,
+    Hram,
+}
+
 /// ref https://gbdev.io/pandocs/Memory_Map.html
 /// 0x0000 - 0x7FFF: 32KB CART ROM
 /// 0x8000 - 0x9FFF: 8KB VRAM
@@ -46,6 +61,12 @@ pub struct Bus {
     pub hram: HighRam,
     pub int_flag_reg: InterruptFlagRegister,
     pub int_mask_reg: InterruptMaskRegsiter,
+    /// 不写入存档, 恢复状态时保留
+    #[serde(skip)]
+    pub debugger: Debugger,
+    /// 不写入存档, 恢复状态时保留
+    #[serde(skip)]
+    pub cheats: Cheats,
 }
 
 impl Reset for Bus {
@@ -64,6 +85,7 @@ impl Reset for Bus {
 }
 
 impl Bus {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Bus {
             cart: None,
@@ -76,20 +98,52 @@ impl Bus {
             hram: HighRam::new(),
             int_mask_reg: InterruptMaskRegsiter::new(),
             btns: Default::default(),
+            debugger: Debugger::new(),
+            cheats: Cheats::default(),
         }
     }
 
+    /// CPU访问, 触发观察点
     pub fn read(&self, addr: Addr) -> EmuResult<Word> {
-        let word = match addr {
-            CART_ROM_LOW_BOUND..=CART_ROM_HIGH_BOUND_INCLUDED
-            | CART_RAM_LOW_BOUND..=CART_RAM_HIGH_BOUND_INCLUDED => {
+        let word = self.debugger.tracer.patch_read(addr, self.load(addr)?);
+        self.debugger.check_read(addr, word);
+        Ok(word)
+    }
+
+    /// CPU取指令和立即数, 不触发观察点
+    pub fn fetch(&self, addr: Addr) -> EmuResult<Word> {
+        Ok(self.debugger.tracer.patch_read(addr, self.load(addr)?))
+    }
+
+    /// 不触发观察点的总线读取, 供DMA使用
+    fn load(&self, addr: Addr) -> EmuResult<Word> {
+        match addr {
+            CART_ROM_LOW_BOUND..=CART_ROM_HIGH_BOUND_INCLUDED => {
                 if let Some(ref c) = self.cart {
-                    c.read(addr)
+                    Ok(self.cheats.patch_rom(addr, c.read(addr)))
                 } else {
                     warn!("no cartridge is plugged in! illegal read at address: 0x:{addr:04X}");
-                    return EmuErr(NoCartridge);
+                    EmuErr(NoCartridge)
                 }
             }
+            CART_RAM_LOW_BOUND..=CART_RAM_HIGH_BOUND_INCLUDED => {
+                if let Some(ref c) = self.cart {
+                    Ok(c.read(addr))
+                } else {
+                    warn!("no cartridge is plugged in! illegal read at address: 0x:{addr:04X}");
+                    EmuErr(NoCartridge)
+                }
+            }
+            _ => Ok(self.read_device(addr).unwrap_or_else(|| {
+                warn!("illegal read at address: 0x{addr:04X}");
+                0xFF
+            })),
+        }
+    }
+
+    /// 卡带以外的设备, 未映射的地址返回`None`
+    fn read_device(&self, addr: Addr) -> Option<Word> {
+        let word = match addr {
             VRAM_LOW_BOUND..=VRAM_HIGH_BOUND_INCLUDED => self.ppu.vram.read(addr),
             WRAM_LOW_BOUND..=WRAM_HIGH_BOUND_INCLUDED => self.wram.read(addr),
             OAM_LOW_BOUND..=OAM_HIGH_BOUND_INCLUDED => self.ppu.oam.read(addr),
@@ -101,15 +155,75 @@ impl Bus {
             INTERRUPT_FLAG_REGISTER_ADDR => self.int_flag_reg.read(),
             HRAM_LOW_BOUND..=HRAM_HIGH_BOUND_INCLUDED => self.hram.read(addr),
             INTERRUPT_MASK_REGISTER_ADDR => self.int_mask_reg.read(),
-            _ => {
-                warn!("illegal read at address: 0x{addr:04X}");
-                0xFF
+            _ => return None,
+        };
+        Some(word)
+    }
+
+    /// 无副作用地读取, 供调试器和内存查看器使用: 不触发观察点, 不输出日志,
+    /// 忽略外部RAM的启用状态, 非法地址和未插入卡带时返回0xFF
+    pub fn peek(&self, addr: Addr) -> Word {
+        match addr {
+            CART_ROM_LOW_BOUND..=CART_ROM_HIGH_BOUND_INCLUDED => self
+                .cart
+                .as_ref()
+                .map_or(0xFF, |c| self.cheats.patch_rom(addr, c.peek(addr))),
+            CART_RAM_LOW_BOUND..=CART_RAM_HIGH_BOUND_INCLUDED => {
+                self.cart.as_ref().map_or(0xFF, |c| c.peek(addr))
             }
+            _ => self.read_device(addr).unwrap_or(0xFF),
+        }
+    }
+
+    /// 读取指定bank的内容: ROM区域按ROM bank, 外部RAM区域按RAM bank, 其余地址同[`Self::peek`].
+    /// bank不存在时返回0xFF
+    pub fn peek_bank(&self, bank: usize, addr: Addr) -> Word {
+        let (mem, offset) = match (addr, &self.cart) {
+            (CART_ROM_LOW_BOUND..=CART_ROM_HIGH_BOUND_INCLUDED, Some(c)) => (
+                c.rom(),
+                bank * ROM_BANK_SIZE + addr as usize % ROM_BANK_SIZE,
+            ),
+            (CART_RAM_LOW_BOUND..=CART_RAM_HIGH_BOUND_INCLUDED, Some(c)) => (
+                c.sram(),
+                bank * RAM_BANK_SIZE + (addr - CART_RAM_LOW_BOUND) as usize,
+            ),
+            _ => return self.peek(addr),
         };
-        Ok(word)
+        mem.get(offset).copied().unwrap_or(0xFF)
+    }
+
+    /// 直接修改内存, 不触发观察点. ROM和外部RAM绕过MBC写入当前映射的bank;
+    /// I/O寄存器按普通写入处理, 可能产生副作用
+    pub fn poke(&mut self, addr: Addr, data: Word) {
+        match addr {
+            CART_ROM_LOW_BOUND..=CART_ROM_HIGH_BOUND_INCLUDED
+            | CART_RAM_LOW_BOUND..=CART_RAM_HIGH_BOUND_INCLUDED => {
+                if let Some(ref mut c) = self.cart {
+                    c.poke(addr, data)
+                }
+            }
+            _ => self.write_device(addr, data),
+        }
+    }
+
+    /// 整块内存区域, 外部RAM不存在的bank为空
+    pub fn region(&self, region: Region) -> &[Word] {
+        match region {
+            Region::Rom => self.cart.as_ref().map_or(&[], |c| c.rom()),
+            Region::Vram => self.ppu.vram.as_slice(),
+            Region::CartRam(bank) => self
+                .cart
+                .as_ref()
+                .and_then(|c| c.sram().chunks(RAM_BANK_SIZE).nth(bank))
+                .unwrap_or(&[]),
+            Region::Wram => self.wram.as_slice(),
+            Region::Oam => self.ppu.oam.as_slice(),
+            Region::Hram => self.hram.as_slice(),
+        }
     }
 
     pub fn write(&mut self, addr: Addr, data: Word) -> EmuResult<()> {
+        self.debugger.check_write(addr, data);
         match addr {
             CART_ROM_LOW_BOUND..=CART_ROM_HIGH_BOUND_INCLUDED
             | CART_RAM_LOW_BOUND..=CART_RAM_HIGH_BOUND_INCLUDED => {
@@ -120,6 +234,13 @@ impl Bus {
                     return EmuErr(NoCartridge);
                 }
             }
+            _ => self.write_device(addr, data),
+        };
+        Ok(())
+    }
+
+    fn write_device(&mut self, addr: Addr, data: Word) {
+        match addr {
             VRAM_LOW_BOUND..=VRAM_HIGH_BOUND_INCLUDED => self.ppu.vram.write(addr, data),
             WRAM_LOW_BOUND..=WRAM_HIGH_BOUND_INCLUDED => self.wram.write(addr, data),
             OAM_LOW_BOUND..=OAM_HIGH_BOUND_INCLUDED => self.ppu.oam.write(addr, data),
@@ -134,19 +255,47 @@ impl Bus {
             HRAM_LOW_BOUND..=HRAM_HIGH_BOUND_INCLUDED => self.hram.write(addr, data),
             INTERRUPT_MASK_REGISTER_ADDR => self.int_mask_reg.write(data),
             _ => warn!("illegal write at address: 0x{addr:04X}"),
-        };
-        Ok(())
+        }
     }
 
     pub fn tick_dma(&mut self) -> EmuResult {
         if let Some((hi, lo)) = self.ppu.dma.tick() {
             let addr = (hi as Addr) << 8 | (lo as Addr);
-            let data = self.read(addr)?;
+            let data = self.load(addr)?;
             unsafe { *self.ppu.oam.get_unchecked_mut(lo as usize) = data }
         }
         Ok(())
     }
 
+    /// 写入GameShark金手指, 每帧调用一次. 外部RAM按代码中的bank写入, bank超出时回绕
+    pub fn apply_cheats(&mut self) {
+        for (bank, addr, data) in self.cheats.ram_writes() {
+            match addr {
+                CART_RAM_LOW_BOUND..=CART_RAM_HIGH_BOUND_INCLUDED => {
+                    let Some(sram) = self.cart.as_mut().map(Cart::sram_mut) else {
+                        continue;
+                    };
+                    if !sram.is_empty() {
+                        let offset =
+                            bank as usize * RAM_BANK_SIZE + (addr - CART_RAM_LOW_BOUND) as usize;
+                        sram[offset % sram.len()] = data;
+                    }
+                }
+                _ => self.wram.write(addr, data),
+            }
+        }
+    }
+
+    /// `addr`处映射的ROM bank, 不在ROM区域或没有卡带时返回`None`
+    pub fn rom_bank(&self, addr: Addr) -> Option<usize> {
+        match (addr, &self.cart) {
+            (CART_ROM_LOW_BOUND..=CART_ROM_HIGH_BOUND_INCLUDED, Some(cart)) => {
+                Some(cart.rom_bank(addr))
+            }
+            _ => None,
+        }
+    }
+
     /// 是否有中断事件等待处理
     pub fn has_int(&self) -> bool {
         self.int_flag_reg.val() & self.int_mask_reg.val() != 0
@@ -216,3 +365,56 @@ pub const WRAM_HIGH_BOUND_INCLUDED: Addr = WRAM_HIGH_BOUND - 1;
 pub const OAM_HIGH_BOUND_INCLUDED: Addr = OAM_HIGH_BOUND - 1;
 pub const IO_HIGH_BOUND_INCLUDED: Addr = IO_HIGH_BOUND - 1;
 pub const HRAM_HIGH_BOUND_INCLUDED: Addr = HRAM_HIGH_BOUND - 1;
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{Bus, Region};
+    use crate::{
+        debug::{Access, Watchpoint},
+        dev::cart::RAM_BANK_SIZE,
+    };
+
+    #[test]
+    fn test_peek_poke() {
+        let mut bus = Bus::new();
+        assert_eq!(bus.peek(0x0100), 0xFF);
+        assert!(bus.region(Region::Rom).is_empty());
+
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        bus.load_cart(rom.clone().into_boxed_slice(), 0).unwrap();
+        bus.debugger.add_watchpoint(Watchpoint {
+            range: 0x0000..=0xFFFF,
+            access: Access::ReadWrite,
+        });
+        assert_eq!(bus.peek(0x0101), rom[0x0101]);
+        assert_eq!(bus.peek_bank(2, 0x4123), rom[0x8123]);
+        assert_eq!(bus.peek_bank(2, 0x0123), rom[0x8123]);
+        assert_eq!(bus.peek_bank(1000, 0x4000), 0xFF);
+        assert_eq!(bus.region(Region::Rom), rom.as_slice());
+
+        // 外部RAM未启用时照样可以查看和修改
+        bus.poke(0xA001, 0x42);
+        bus.cart.as_mut().unwrap().sram_mut()[3 * RAM_BANK_SIZE] = 0x24;
+        assert_eq!(bus.load(0xA001).unwrap(), 0xFF);
+        assert_eq!(bus.peek(0xA001), 0x42);
+        assert_eq!(bus.peek_bank(3, 0xA000), 0x24);
+        assert_eq!(bus.region(Region::CartRam(0))[1], 0x42);
+        assert_eq!(bus.region(Region::CartRam(3))[0], 0x24);
+        assert!(bus.region(Region::CartRam(4)).is_empty());
+
+        // 修改ROM不会被当作MBC寄存器写入
+        bus.poke(0x2000, 0x00);
+        assert_eq!(bus.peek(0x2000), 0x00);
+        assert_eq!(bus.cart.as_ref().unwrap().rom_bank(0x4000), 1);
+
+        bus.poke(0xC010, 0x99);
+        bus.poke(0xFF90, 0x77);
+        assert_eq!(bus.peek(0xC010), 0x99);
+        assert_eq!(bus.region(Region::Wram)[0x10], 0x99);
+        assert_eq!(bus.region(Region::Hram)[0x10], 0x77);
+        assert_eq!(bus.peek(0xE000), 0xFF);
+        assert_eq!(bus.debugger.take_stop(), None);
+    }
+}
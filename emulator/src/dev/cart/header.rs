@@ -3,6 +3,7 @@ use crate::error::{
     EmuErr, EmuResult, EmulatorError, InvalidChecksum, InvalidLogo, InvalidRomSize,
 };
 use core::mem::offset_of;
+use std::borrow::Cow;
 use std::mem::size_of;
 
 const KB: usize = 1024;
@@ -10,6 +11,15 @@ const ENTRY_SIZE: usize = 0x04;
 const LOGO_SIZE: usize = 0x30;
 const TITLE_SIZE: usize = 0x10;
 const ROM_OFFSET: usize = 0x0100;
+/// MMM01菜单的大小
+pub(super) const MMM01_MENU_SIZE: usize = 32 * KB;
+/// 标志在卡带头中的偏移
+pub(super) const LOGO_OFFSET: usize = ROM_OFFSET + ENTRY_SIZE;
+pub(super) const NINTENDO_LOGO: &[u8; LOGO_SIZE] = &[
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
 
 /// https://gbdev.io/pandocs/The_Cartridge_Header.html
 /// mapped to 0x0100-0x014F in ROM
@@ -44,10 +54,15 @@ pub enum MBCType {
     MBC1,
     MBC2,
     MBC3,
+    MBC5,
+    MMM01,
+    HuC1,
+    HuC3,
+    MBC7,
 }
 
 impl Header {
-    pub unsafe fn from_rom_unchecked<'a>(rom: &'a Rom) -> &'a Self {
+    pub unsafe fn from_rom_unchecked(rom: &Rom) -> &Self {
         unsafe {
             let base = rom.as_ptr().add(ROM_OFFSET) as *const Self;
             &*base
@@ -68,32 +83,39 @@ impl Header {
         }
     }
 
+    /// MMM01合集的菜单和卡带头位于ROM末尾的32KB, ROM开头通常是第一个游戏的卡带头.
+    /// 末尾是有效的MMM01卡带头时返回其偏移
+    pub fn mmm01_offset(rom: &Rom) -> Option<usize> {
+        let offset = rom.len().checked_sub(MMM01_MENU_SIZE)?;
+        let header = Self::from_rom(&rom[offset..]).ok()?;
+        matches!(header.mbc_type(), Some(MBCType::MMM01)).then_some(offset)
+    }
+
     //TODO The CGB and later models only check the top half of the logo (the first $18 bytes).
     pub fn check_logo(&self) -> Option<EmulatorError> {
-        const CORRECT_LOGO: &[u8; LOGO_SIZE] = &[
-            0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C,
-            0x00, 0x0D, 0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6,
-            0xDD, 0xDD, 0xD9, 0x99, 0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC,
-            0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
-        ];
-        if &self.nintendo_logo == CORRECT_LOGO {
+        if &self.nintendo_logo == NINTENDO_LOGO {
             None
         } else {
             Some(InvalidLogo {
-                expected: CORRECT_LOGO,
+                expected: NINTENDO_LOGO,
                 actual: self.nintendo_logo,
             })
         }
     }
 
-    pub fn title<'a>(&'a self) -> &'a str {
+    /// 标题末尾可能是厂商代码和CGB标志, 不一定是合法的ASCII
+    pub fn title(&self) -> Cow<'_, str> {
         let end = self
             .title
             .iter()
             .enumerate()
             .find_map(|(i, &c)| if c == 0 { Some(i) } else { None })
             .unwrap_or(TITLE_SIZE);
-        unsafe { core::str::from_utf8_unchecked(&self.title[..end]) }
+        String::from_utf8_lossy(&self.title[..end])
+    }
+
+    pub fn header_checksum(&self) -> u8 {
+        self.checksum
     }
 
     pub fn rom_size(&self) -> usize {
@@ -180,25 +202,31 @@ impl Header {
         use MBCType::*;
         match self.cart_type {
             0x00 | 0x08 | 0x09 => Some(NoMBC),
-            0x01 | 0x02 | 0x03 => Some(MBC1),
+            0x01..=0x03 => Some(MBC1),
             0x05 | 0x06 => Some(MBC2),
-            0x0F | 0x10 | 0x11 | 0x12 | 0x13 => Some(MBC3),
+            0x0F..=0x13 => Some(MBC3),
+            0x19..=0x1E => Some(MBC5),
+            0x0B..=0x0D => Some(MMM01),
+            0xFE => Some(HuC3),
+            0xFF => Some(HuC1),
+            0x22 => Some(MBC7),
             _ => None,
         }
     }
 
     pub fn has_rtc(&self) -> bool {
-        match self.cart_type {
-            0x0F | 0x10 => true,
-            _ => false,
-        }
+        matches!(self.cart_type, 0x0F | 0x10 | 0xFE)
+    }
+
+    pub fn has_rumble(&self) -> bool {
+        matches!(self.cart_type, 0x1C..=0x1E)
     }
 
     pub fn has_battery(&self) -> bool {
-        match self.cart_type {
-            3 | 6 | 9 | 13 | 15 | 16 | 19 | 27 | 30 | 34 => true,
-            _ => false,
-        }
+        matches!(
+            self.cart_type,
+            3 | 6 | 9 | 13 | 15 | 16 | 19 | 27 | 30 | 34 | 0xFE | 0xFF
+        )
     }
 
     pub fn publisher(&self) -> &'static str {
@@ -461,11 +489,13 @@ impl Header {
 mod test {
     use std::fs;
 
-    const ROMS_PATH: &'static str = "../public/roms";
+    const ROMS_PATH: &str = "../public/roms";
     fn read_roms() -> Vec<Vec<u8>> {
         fs::read_dir(ROMS_PATH)
             .unwrap()
-            .map(|entry| fs::read(entry.unwrap().path()).unwrap())
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_file())
+            .map(|path| fs::read(path).unwrap())
             .collect()
     }
 
@@ -477,7 +507,7 @@ mod test {
         let roms = read_roms();
         for (i, rom) in roms.iter().enumerate() {
             println!("testing rom {}", i + 1);
-            let header = unsafe { Header::from_rom_unchecked(&rom) };
+            let header = unsafe { Header::from_rom_unchecked(rom) };
             let title = header.title();
             let ty = header.cart_typename();
             let rom_size = header.rom_size();
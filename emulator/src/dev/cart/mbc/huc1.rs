@@ -0,0 +1,132 @@
+use super::{RamBank, RomBank, MBC, RAM_BANK_SIZE};
+use crate::{
+    dev::cart::{
+        Rom, RAM_ADDR_HIGH_BOUND, RAM_ADDR_LOW_BOUND, ROM0_ADDR_HIGH_BOUND, ROM0_ADDR_LOW_BOUND,
+        ROM1_ADDR_HIGH_BOUND, ROM1_ADDR_LOW_BOUND,
+    },
+    error::EmuResult,
+    types::{Addr, Word},
+    utils::bytes::{bytes_to_slice, slice_as_bytes},
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+/// 红外模式下没有收到光时读到的值
+pub(super) const IR_NO_LIGHT: Word = 0xC0;
+
+/// ref https://gbdev.io/pandocs/HuC1.html
+/// 类似MBC1, 6位ROM bank, 2位RAM bank. 0x0000-0x1FFF写入0x0E时0xA000-0xBFFF映射红外端口, 否则映射RAM.
+/// 不模拟红外通信, 始终读到没有光
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+pub struct HuC1 {
+    /// 不写入存档, 恢复状态时从已加载的卡带取回
+    #[serde(skip)]
+    pub rom_banks: Box<[RomBank]>,
+    #[serde_as(as = "Box<[[_; RAM_BANK_SIZE]]>")]
+    pub ram_banks: Box<[RamBank]>,
+    pub rom_bank_sel: u8,
+    pub ram_bank_sel: u8,
+    pub ir_mode: bool,
+}
+
+impl HuC1 {
+    fn rom1_bank(&self) -> usize {
+        self.rom_bank_sel.max(1) as usize % self.rom_banks.len()
+    }
+
+    fn ram(&self) -> Option<&RamBank> {
+        self.ram_bank().map(|bank| &self.ram_banks[bank])
+    }
+
+    fn ram_mut(&mut self) -> Option<&mut RamBank> {
+        let bank = self.ram_bank()?;
+        Some(&mut self.ram_banks[bank])
+    }
+}
+
+impl MBC for HuC1 {
+    fn read(&self, addr: Addr) -> Word {
+        match addr {
+            ROM0_ADDR_LOW_BOUND..=ROM0_ADDR_HIGH_BOUND => {
+                self.rom_banks[0][(addr - ROM0_ADDR_LOW_BOUND) as usize]
+            }
+            ROM1_ADDR_LOW_BOUND..=ROM1_ADDR_HIGH_BOUND => {
+                self.rom_banks[self.rom1_bank()][(addr - ROM1_ADDR_LOW_BOUND) as usize]
+            }
+            RAM_ADDR_LOW_BOUND..=RAM_ADDR_HIGH_BOUND => match (self.ir_mode, self.ram()) {
+                (true, _) => IR_NO_LIGHT,
+                (false, Some(ram)) => ram[(addr - RAM_ADDR_LOW_BOUND) as usize],
+                _ => 0xFF,
+            },
+            _ => {
+                warn!("illegal read cart at address: 0x{addr:04X}");
+                0xFF
+            }
+        }
+    }
+
+    fn write(&mut self, addr: Addr, data: Word) {
+        match addr {
+            0x0000..=0x1FFF => self.ir_mode = data == 0x0E,
+            0x2000..=0x3FFF => self.rom_bank_sel = data & 0x3F,
+            0x4000..=0x5FFF => self.ram_bank_sel = data & 0x03,
+            0x6000..=0x7FFF => {}
+            RAM_ADDR_LOW_BOUND..=RAM_ADDR_HIGH_BOUND => {
+                // 红外模式下写入控制LED, 忽略
+                if !self.ir_mode {
+                    if let Some(ram) = self.ram_mut() {
+                        ram[(addr - RAM_ADDR_LOW_BOUND) as usize] = data;
+                    }
+                }
+            }
+            _ => warn!("illegal write cart at address: 0x{addr:04X}"),
+        }
+    }
+
+    fn rom_banks_mut(&mut self) -> &mut Box<[RomBank]> {
+        &mut self.rom_banks
+    }
+
+    fn rom_bank(&self, addr: Addr) -> usize {
+        if addr <= ROM0_ADDR_HIGH_BOUND {
+            0
+        } else {
+            self.rom1_bank()
+        }
+    }
+
+    fn cart_rom(&self) -> &Rom {
+        let rom = self.rom_banks.as_ref();
+        slice_as_bytes(rom)
+    }
+
+    fn sram(&self) -> &[u8] {
+        self.ram_banks.as_flattened()
+    }
+
+    fn sram_mut(&mut self) -> &mut [u8] {
+        self.ram_banks.as_flattened_mut()
+    }
+
+    fn ram_bank(&self) -> Option<usize> {
+        if self.ir_mode || self.ram_banks.is_empty() {
+            return None;
+        }
+        Some(self.ram_bank_sel as usize % self.ram_banks.len())
+    }
+
+    fn new(rom: Box<[u8]>, ram_size: usize, _: bool, _: i64) -> EmuResult<Self> {
+        let rom_banks: Box<[RomBank]> = unsafe { bytes_to_slice(rom) };
+        let ram_banks_num = ram_size / RAM_BANK_SIZE;
+        let ram_banks = vec![[0; RAM_BANK_SIZE]; ram_banks_num].into_boxed_slice();
+        Ok(Self {
+            rom_banks,
+            ram_banks,
+            rom_bank_sel: 1,
+            ram_bank_sel: 0,
+            ir_mode: false,
+        })
+    }
+}
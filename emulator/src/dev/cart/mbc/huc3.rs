@@ -0,0 +1,210 @@
+use super::{huc1::IR_NO_LIGHT, rtc::RTC, RamBank, RomBank, MBC, RAM_BANK_SIZE};
+use crate::{
+    dev::cart::{
+        Rom, RAM_ADDR_HIGH_BOUND, RAM_ADDR_LOW_BOUND, ROM0_ADDR_HIGH_BOUND, ROM0_ADDR_LOW_BOUND,
+        ROM1_ADDR_HIGH_BOUND, ROM1_ADDR_LOW_BOUND,
+    },
+    error::EmuResult,
+    types::{Addr, Word},
+    utils::bytes::{bytes_to_slice, slice_as_bytes},
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+/// 0x0000-0x1FFF选择0xA000-0xBFFF映射的内容
+const MODE_RAM_READ: Word = 0x00;
+const MODE_RAM: Word = 0x0A;
+const MODE_COMMAND: Word = 0x0B;
+const MODE_RESPONSE: Word = 0x0C;
+const MODE_SEMAPHORE: Word = 0x0D;
+const MODE_IR: Word = 0x0E;
+
+/// RTC内存中当前时间的位置: 0x00-0x02为当天的分钟数, 0x03-0x06为天数, 每个地址存一个半字节
+const CLOCK_MINUTES: usize = 0x00;
+const CLOCK_DAYS: usize = 0x03;
+const RTC_MEMORY_SIZE: usize = 0x100;
+
+/// ref https://gbdev.io/pandocs/HuC3.html
+/// 7位ROM bank, 最多4个RAM bank. RTC通过命令访问半字节寻址的RTC内存: 在命令模式写入命令,
+/// 在信号量模式写入bit 0为0的值执行, 在响应模式读取结果. 时钟由`RTC`计时, 不模拟闹钟和蜂鸣器
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+pub struct HuC3 {
+    /// 不写入存档, 恢复状态时从已加载的卡带取回
+    #[serde(skip)]
+    pub rom_banks: Box<[RomBank]>,
+    #[serde_as(as = "Box<[[_; RAM_BANK_SIZE]]>")]
+    pub ram_banks: Box<[RamBank]>,
+    pub rom_bank_sel: u8,
+    pub ram_bank_sel: u8,
+    pub mode: Word,
+    pub rtc: RTC,
+    pub rtc_memory: Box<[Word]>,
+    /// 命令访问的RTC内存地址
+    pub rtc_addr: u8,
+    /// 等待执行的命令
+    pub command: Word,
+    pub response: Word,
+}
+
+impl HuC3 {
+    fn rom1_bank(&self) -> usize {
+        self.rom_bank_sel.max(1) as usize % self.rom_banks.len()
+    }
+
+    fn ram(&self) -> Option<&RamBank> {
+        self.ram_bank().map(|bank| &self.ram_banks[bank])
+    }
+
+    fn ram_mut(&mut self) -> Option<&mut RamBank> {
+        let bank = self.ram_bank()?;
+        Some(&mut self.ram_banks[bank])
+    }
+
+    /// 把`value`的低`n`个半字节写入RTC内存的`at`处
+    fn store_nibbles(&mut self, at: usize, n: usize, value: u16) {
+        for i in 0..n {
+            self.rtc_memory[at + i] = (value >> (i * 4) & 0x0F) as Word;
+        }
+    }
+
+    fn load_nibbles(&self, at: usize, n: usize) -> u16 {
+        (0..n).fold(0, |acc, i| {
+            acc | (self.rtc_memory[at + i] as u16) << (i * 4)
+        })
+    }
+
+    fn execute(&mut self) {
+        let arg = self.command & 0x0F;
+        let addr = self.rtc_addr as usize;
+        match self.command >> 4 {
+            // 读取并递增地址
+            0x1 => {
+                self.response = self.command & 0xF0 | self.rtc_memory[addr];
+                self.rtc_addr = self.rtc_addr.wrapping_add(1);
+            }
+            // 写入并递增地址
+            0x3 => {
+                self.rtc_memory[addr] = arg;
+                self.rtc_addr = self.rtc_addr.wrapping_add(1);
+            }
+            0x4 => self.rtc_addr = self.rtc_addr & 0xF0 | arg,
+            0x5 => self.rtc_addr = self.rtc_addr & 0x0F | arg << 4,
+            0x6 => match arg {
+                // 当前时间复制到RTC内存
+                0x0 => {
+                    let (minutes, days) = self.rtc.clock();
+                    self.store_nibbles(CLOCK_MINUTES, 3, minutes);
+                    self.store_nibbles(CLOCK_DAYS, 4, days);
+                }
+                // RTC内存中的时间写入时钟
+                0x1 => {
+                    let minutes = self.load_nibbles(CLOCK_MINUTES, 3);
+                    let days = self.load_nibbles(CLOCK_DAYS, 4);
+                    self.rtc.set_clock(minutes, days);
+                }
+                // 状态, 总是就绪
+                0x2 => self.response = self.command & 0xF0 | 0x01,
+                _ => {}
+            },
+            _ => warn!("unsupported HuC3 command: 0x{:02X}", self.command),
+        }
+    }
+}
+
+impl MBC for HuC3 {
+    fn read(&self, addr: Addr) -> Word {
+        match addr {
+            ROM0_ADDR_LOW_BOUND..=ROM0_ADDR_HIGH_BOUND => {
+                self.rom_banks[0][(addr - ROM0_ADDR_LOW_BOUND) as usize]
+            }
+            ROM1_ADDR_LOW_BOUND..=ROM1_ADDR_HIGH_BOUND => {
+                self.rom_banks[self.rom1_bank()][(addr - ROM1_ADDR_LOW_BOUND) as usize]
+            }
+            RAM_ADDR_LOW_BOUND..=RAM_ADDR_HIGH_BOUND => match self.mode {
+                MODE_RESPONSE => self.response,
+                // 命令立即执行完毕
+                MODE_SEMAPHORE => 0x01,
+                MODE_IR => IR_NO_LIGHT,
+                _ => self
+                    .ram()
+                    .map_or(0xFF, |ram| ram[(addr - RAM_ADDR_LOW_BOUND) as usize]),
+            },
+            _ => {
+                warn!("illegal read cart at address: 0x{addr:04X}");
+                0xFF
+            }
+        }
+    }
+
+    fn write(&mut self, addr: Addr, data: Word) {
+        match addr {
+            0x0000..=0x1FFF => self.mode = data & 0x0F,
+            0x2000..=0x3FFF => self.rom_bank_sel = data & 0x7F,
+            0x4000..=0x5FFF => self.ram_bank_sel = data & 0x03,
+            0x6000..=0x7FFF => {}
+            RAM_ADDR_LOW_BOUND..=RAM_ADDR_HIGH_BOUND => match self.mode {
+                MODE_RAM => {
+                    if let Some(ram) = self.ram_mut() {
+                        ram[(addr - RAM_ADDR_LOW_BOUND) as usize] = data;
+                    }
+                }
+                MODE_COMMAND => self.command = data,
+                MODE_SEMAPHORE if data & 0x01 == 0 => self.execute(),
+                _ => {}
+            },
+            _ => warn!("illegal write cart at address: 0x{addr:04X}"),
+        }
+    }
+
+    fn rom_banks_mut(&mut self) -> &mut Box<[RomBank]> {
+        &mut self.rom_banks
+    }
+
+    fn rom_bank(&self, addr: Addr) -> usize {
+        if addr <= ROM0_ADDR_HIGH_BOUND {
+            0
+        } else {
+            self.rom1_bank()
+        }
+    }
+
+    fn cart_rom(&self) -> &Rom {
+        let rom = self.rom_banks.as_ref();
+        slice_as_bytes(rom)
+    }
+
+    fn sram(&self) -> &[u8] {
+        self.ram_banks.as_flattened()
+    }
+
+    fn sram_mut(&mut self) -> &mut [u8] {
+        self.ram_banks.as_flattened_mut()
+    }
+
+    fn ram_bank(&self) -> Option<usize> {
+        if !matches!(self.mode, MODE_RAM_READ | MODE_RAM) || self.ram_banks.is_empty() {
+            return None;
+        }
+        Some(self.ram_bank_sel as usize % self.ram_banks.len())
+    }
+
+    fn new(rom: Box<[u8]>, ram_size: usize, _: bool, timestamp: i64) -> EmuResult<Self> {
+        let rom_banks: Box<[RomBank]> = unsafe { bytes_to_slice(rom) };
+        let ram_banks_num = ram_size / RAM_BANK_SIZE;
+        let ram_banks = vec![[0; RAM_BANK_SIZE]; ram_banks_num].into_boxed_slice();
+        Ok(Self {
+            rom_banks,
+            ram_banks,
+            rom_bank_sel: 1,
+            ram_bank_sel: 0,
+            mode: MODE_RAM_READ,
+            rtc: RTC::new(timestamp),
+            rtc_memory: vec![0; RTC_MEMORY_SIZE].into_boxed_slice(),
+            rtc_addr: 0,
+            command: 0,
+            response: 0,
+        })
+    }
+}
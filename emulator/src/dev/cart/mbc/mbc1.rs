@@ -1,5 +1,6 @@
-use super::{RamBank, RomBank, MBC, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use super::{RamBank, RomBank, MBC, RAM_BANK_SIZE};
 use crate::dev::cart::{
+    header::{LOGO_OFFSET, NINTENDO_LOGO},
     Rom, RAM_ADDR_HIGH_BOUND, RAM_ADDR_LOW_BOUND, ROM0_ADDR_HIGH_BOUND, ROM0_ADDR_LOW_BOUND,
     ROM1_ADDR_HIGH_BOUND, ROM1_ADDR_LOW_BOUND,
 };
@@ -7,7 +8,7 @@ use crate::error::EmuResult;
 use crate::types::{Addr, Word};
 use crate::utils::bits::BitMap;
 use crate::utils::bytes::{bytes_to_slice, slice_as_bytes};
-use log::{debug, error, warn};
+use log::warn;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
@@ -20,7 +21,8 @@ pub enum WorkingMode {
 #[serde_as]
 #[derive(Serialize, Deserialize)]
 pub struct MBC1 {
-    #[serde_as(as = "Box<[[_; ROM_BANK_SIZE]]>")]
+    /// 不写入存档, 恢复状态时从已加载的卡带取回
+    #[serde(skip)]
     pub rom_banks: Box<[RomBank]>,
     #[serde_as(as = "Box<[[_; RAM_BANK_SIZE]]>")]
     pub ram_banks: Box<[RamBank]>,
@@ -28,65 +30,71 @@ pub struct MBC1 {
     pub ram_bank_sel: u8,
     pub ram_enable: bool,
     pub mode: WorkingMode,
+    /// MBC1M: 1MB的合集卡带, 第二个寄存器接到ROM bank的bit 4-5, 第一个寄存器只用低4位
+    pub multicart: bool,
 }
 
-impl MBC1 {
-    fn no_ram(&self) -> bool {
-        self.rom_banks.is_empty()
-    }
+/// MBC1M的每个游戏占256KB(16个bank), bank 0x10开头也有卡带头
+const MULTICART_GAME_BANKS: usize = 0x10;
+
+/// 1MB且bank 0x10处有任天堂标志时视为MBC1M
+fn is_multicart(rom_banks: &[RomBank]) -> bool {
+    rom_banks.len() == 4 * MULTICART_GAME_BANKS
+        && rom_banks[MULTICART_GAME_BANKS][LOGO_OFFSET..LOGO_OFFSET + NINTENDO_LOGO.len()]
+            == NINTENDO_LOGO[..]
+}
 
+impl MBC1 {
     fn has_ram(&self) -> bool {
-        !self.rom_banks.is_empty()
+        !self.ram_banks.is_empty()
     }
 
-    fn rom0(&self) -> &RomBank {
-        if self.mode == WorkingMode::Advanced && self.rom_banks.len() > 32 {
-            let bank_idx = self.ram_bank_sel << 5;
-            &self.rom_banks[bank_idx as usize]
+    /// 第二个寄存器在ROM bank号中的位置
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart {
+            4
         } else {
-            &self.rom_banks[0]
+            5
         }
     }
 
-    fn rom1(&self) -> &RomBank {
+    fn rom0_bank(&self) -> usize {
         if self.mode == WorkingMode::Advanced && self.rom_banks.len() > 32 {
-            let bank_idx = self.ram_bank_sel << 5 | self.rom_bank_sel;
-            &self.rom_banks[bank_idx as usize]
+            (self.ram_bank_sel << self.bank2_shift()) as usize
         } else {
-            &self.rom_banks[self.rom_bank_sel as usize]
+            0
         }
     }
 
-    fn ram(&self) -> Option<&RamBank> {
-        if self.no_ram() {
-            return None;
-        }
-        let bank = if self.rom_banks.len() <= 32 && self.mode == WorkingMode::Advanced {
-            &self.ram_banks[self.ram_bank_sel as usize]
+    /// 第二个寄存器在两种模式下都作用于0x4000-0x7FFF
+    fn rom1_bank(&self) -> usize {
+        let low = if self.multicart {
+            self.rom_bank_sel & 0x0F
         } else {
-            &self.ram_banks[0]
+            self.rom_bank_sel
         };
-        // match self.mode {
-        //     WorkingMode::Simple => &self.ram_banks[self.ram_bank_sel as usize],
-        //     WorkingMode::Advanced => &self.ram_banks[0],
-        // };
-        Some(bank)
+        if self.rom_banks.len() > 32 {
+            (self.ram_bank_sel << self.bank2_shift() | low) as usize
+        } else {
+            low as usize
+        }
+    }
+
+    fn rom0(&self) -> &RomBank {
+        &self.rom_banks[self.rom0_bank()]
+    }
+
+    fn rom1(&self) -> &RomBank {
+        &self.rom_banks[self.rom1_bank()]
+    }
+
+    fn ram(&self) -> Option<&RamBank> {
+        self.ram_bank().map(|bank| &self.ram_banks[bank])
     }
 
     fn ram_mut(&mut self) -> Option<&mut RamBank> {
-        if self.no_ram() {
-            return None;
-        }
-        // let bank = match self.mode {
-        //     WorkingMode::Simple => &mut self.ram_banks[self.ram_bank_sel as usize],
-        //     WorkingMode::Advanced => &mut self.ram_banks[0],
-        // };
-        let bank = if self.rom_banks.len() <= 32 && self.mode == WorkingMode::Advanced {
-            &mut self.ram_banks[self.ram_bank_sel as usize]
-        } else {
-            &mut self.ram_banks[0]
-        };
-        Some(bank)
+        let bank = self.ram_bank()?;
+        Some(&mut self.ram_banks[bank])
     }
 
     fn set_ram_enable(&mut self, data: Word) {
@@ -118,7 +126,9 @@ impl MBC1 {
         // This second 2-bit register can be used to select a RAM Bank in range from $00–$03 (32 KiB ram carts only)
         // or to specify the upper two bits (bits 5-6) of the ROM Bank number (1 MiB ROM or larger carts only).
         // If neither ROM nor RAM is large enough, setting this register does nothing.
-        let data = if self.rom_banks.len() > 32 {
+        let data = if self.multicart {
+            data
+        } else if self.rom_banks.len() > 32 {
             if self.rom_banks.len() <= 64 {
                 data & 0x01
             } else {
@@ -187,15 +197,47 @@ impl MBC for MBC1 {
         }
     }
 
+    fn rom_banks_mut(&mut self) -> &mut Box<[RomBank]> {
+        &mut self.rom_banks
+    }
+
+    fn rom_bank(&self, addr: Addr) -> usize {
+        if addr <= ROM0_ADDR_HIGH_BOUND {
+            self.rom0_bank()
+        } else {
+            self.rom1_bank()
+        }
+    }
+
+    fn ram_bank(&self) -> Option<usize> {
+        if !self.has_ram() {
+            return None;
+        }
+        if self.rom_banks.len() <= 32 && self.mode == WorkingMode::Advanced {
+            Some(self.ram_bank_sel as usize)
+        } else {
+            Some(0)
+        }
+    }
+
     fn cart_rom(&self) -> &Rom {
         let rom = self.rom_banks.as_ref();
         slice_as_bytes(rom)
     }
 
+    fn sram(&self) -> &[u8] {
+        self.ram_banks.as_flattened()
+    }
+
+    fn sram_mut(&mut self) -> &mut [u8] {
+        self.ram_banks.as_flattened_mut()
+    }
+
     fn new(rom: Box<[u8]>, ram_size: usize, _: bool, _: i64) -> EmuResult<Self> {
         let rom_banks: Box<[RomBank]> = unsafe { bytes_to_slice(rom) };
         let ram_banks_num = ram_size / RAM_BANK_SIZE;
         let ram_banks = vec![[0; RAM_BANK_SIZE]; ram_banks_num].into_boxed_slice();
+        let multicart = is_multicart(&rom_banks);
         Ok(Self {
             rom_banks,
             ram_banks,
@@ -203,6 +245,7 @@ impl MBC for MBC1 {
             ram_bank_sel: 0,
             ram_enable: false,
             mode: WorkingMode::Simple,
+            multicart,
         })
     }
 }
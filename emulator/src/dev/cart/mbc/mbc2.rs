@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
-use super::{RomBank, MBC, ROM_BANK_SIZE};
+use super::{RomBank, MBC};
 use crate::{
     dev::cart::{
         Rom, RAM_ADDR_HIGH_BOUND, RAM_ADDR_LOW_BOUND, ROM0_ADDR_HIGH_BOUND, ROM0_ADDR_LOW_BOUND,
@@ -15,7 +15,8 @@ use crate::{
 #[serde_as]
 #[derive(Serialize, Deserialize)]
 pub struct MBC2 {
-    #[serde_as(as = "Box<[[_; ROM_BANK_SIZE]]>")]
+    /// 不写入存档, 恢复状态时从已加载的卡带取回
+    #[serde(skip)]
     pub rom_banks: Box<[RomBank]>,
     /// header 中的ram-size字段为0
     #[serde_as(as = "Box<[_; 512]>")]
@@ -100,11 +101,40 @@ impl MBC for MBC2 {
         }
     }
 
+    fn rom_banks_mut(&mut self) -> &mut Box<[RomBank]> {
+        &mut self.rom_banks
+    }
+
+    fn rom_bank(&self, addr: Addr) -> usize {
+        if addr <= ROM0_ADDR_HIGH_BOUND {
+            0
+        } else {
+            self.rom_bank_sel as usize
+        }
+    }
+
     fn cart_rom(&self) -> &Rom {
         let rom = self.rom_banks.as_ref();
         slice_as_bytes(rom)
     }
 
+    fn sram(&self) -> &[u8] {
+        self.ram.as_slice()
+    }
+
+    fn sram_mut(&mut self) -> &mut [u8] {
+        self.ram.as_mut_slice()
+    }
+
+    /// 512个半字节, 在整个外部RAM区域内重复映射
+    fn ram_offset(&self, addr: Addr) -> Option<usize> {
+        Some((addr - RAM_ADDR_LOW_BOUND) as usize % 512)
+    }
+
+    fn peek_ram(&self, addr: Addr) -> Word {
+        (self.ram[(addr - RAM_ADDR_LOW_BOUND) as usize % 512] & 0x0F) | 0xF0
+    }
+
     fn new(rom: Box<[u8]>, _: usize, _: bool, _: i64) -> EmuResult<Self> {
         let rom_banks: Box<[RomBank]> = unsafe { bytes_to_slice(rom) };
         let ram = Box::new([0; _]);
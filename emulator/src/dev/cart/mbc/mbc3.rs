@@ -1,4 +1,4 @@
-use super::{rtc::RTC, RamBank, RomBank, MBC, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use super::{rtc::RTC, RamBank, RomBank, MBC, RAM_BANK_SIZE};
 use crate::{
     dev::cart::{
         Rom, RAM_ADDR_HIGH_BOUND, RAM_ADDR_LOW_BOUND, ROM0_ADDR_HIGH_BOUND, ROM0_ADDR_LOW_BOUND,
@@ -15,7 +15,8 @@ use serde_with::serde_as;
 #[serde_as]
 #[derive(Serialize, Deserialize)]
 pub struct MBC3 {
-    #[serde_as(as = "Box<[[_; ROM_BANK_SIZE]]>")]
+    /// 不写入存档, 恢复状态时从已加载的卡带取回
+    #[serde(skip)]
     pub rom_banks: Box<[RomBank]>,
     #[serde_as(as = "Box<[[_; RAM_BANK_SIZE]]>")]
     pub ram_banks: Box<[RamBank]>,
@@ -73,17 +74,12 @@ impl MBC for MBC3 {
                 self.rom1()[(addr - ROM1_ADDR_LOW_BOUND) as usize]
             }
             RAM_ADDR_LOW_BOUND..=RAM_ADDR_HIGH_BOUND => match self.ram_bank_sel {
-                0x00..=0x03 => {
-                    if self.ram_enable
-                        && let Some(ram) = self.ram()
-                    {
-                        ram[(addr - RAM_ADDR_LOW_BOUND) as usize]
-                    } else {
-                        0xFF
-                    }
-                }
+                0x00..=0x03 => match (self.ram_enable, self.ram()) {
+                    (true, Some(ram)) => ram[(addr - RAM_ADDR_LOW_BOUND) as usize],
+                    _ => 0xFF,
+                },
                 0x08..=0x0C => match &self.rtc {
-                    Some(rtc) => rtc.read(addr),
+                    Some(rtc) => rtc.read(self.ram_bank_sel as Addr),
                     None => 0xFF,
                 },
                 _ => {
@@ -121,7 +117,7 @@ impl MBC for MBC3 {
                 }
                 0x08..=0x0C => {
                     if let Some(rtc) = &mut self.rtc {
-                        rtc.write(addr, data)
+                        rtc.write(self.ram_bank_sel as Addr, data)
                     }
                 }
                 _ => warn!(
@@ -133,11 +129,47 @@ impl MBC for MBC3 {
         }
     }
 
+    fn rom_banks_mut(&mut self) -> &mut Box<[RomBank]> {
+        &mut self.rom_banks
+    }
+
+    fn rom_bank(&self, addr: Addr) -> usize {
+        if addr <= ROM0_ADDR_HIGH_BOUND {
+            0
+        } else {
+            self.rom_bank_sel as usize
+        }
+    }
+
     fn cart_rom(&self) -> &Rom {
         let rom = self.rom_banks.as_ref();
         slice_as_bytes(rom)
     }
 
+    fn sram(&self) -> &[u8] {
+        self.ram_banks.as_flattened()
+    }
+
+    fn sram_mut(&mut self) -> &mut [u8] {
+        self.ram_banks.as_flattened_mut()
+    }
+
+    fn ram_bank(&self) -> Option<usize> {
+        match self.ram_bank_sel {
+            0x00..=0x03 if !self.ram_banks.is_empty() => Some(self.ram_bank_sel as usize),
+            _ => None,
+        }
+    }
+
+    fn peek_ram(&self, addr: Addr) -> Word {
+        match (self.ram_bank_sel, &self.rtc) {
+            (0x08..=0x0C, Some(rtc)) => rtc.read(self.ram_bank_sel as Addr),
+            _ => self
+                .ram_offset(addr)
+                .map_or(0xFF, |offset| self.sram()[offset]),
+        }
+    }
+
     fn new(rom: Box<[u8]>, ram_size: usize, has_rtc: bool, timestamp: i64) -> EmuResult<Self> {
         let rom_banks: Box<[RomBank]> = unsafe { bytes_to_slice(rom) };
         let ram_banks_num = ram_size / RAM_BANK_SIZE;
@@ -0,0 +1,147 @@
+use super::{RamBank, RomBank, MBC, RAM_BANK_SIZE};
+use crate::{
+    dev::cart::{
+        Rom, RAM_ADDR_HIGH_BOUND, RAM_ADDR_LOW_BOUND, ROM0_ADDR_HIGH_BOUND, ROM0_ADDR_LOW_BOUND,
+        ROM1_ADDR_HIGH_BOUND, ROM1_ADDR_LOW_BOUND,
+    },
+    error::EmuResult,
+    types::{Addr, Word},
+    utils::bytes::{bytes_to_slice, slice_as_bytes},
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+/// ref https://gbdev.io/pandocs/MBC5.html
+/// 9位ROM bank(最大8MB, 0x4000-0x7FFF也可以映射bank 0), 4位RAM bank(最大128KB)
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+pub struct MBC5 {
+    /// 不写入存档, 恢复状态时从已加载的卡带取回
+    #[serde(skip)]
+    pub rom_banks: Box<[RomBank]>,
+    #[serde_as(as = "Box<[[_; RAM_BANK_SIZE]]>")]
+    pub ram_banks: Box<[RamBank]>,
+    pub rom_bank_sel: u16,
+    pub ram_bank_sel: u8,
+    pub ram_enable: bool,
+    /// 带震动马达的卡带, RAM bank寄存器的bit 3控制马达
+    pub has_rumble: bool,
+    pub rumble: bool,
+}
+
+impl MBC5 {
+    fn rom1_bank(&self) -> usize {
+        self.rom_bank_sel as usize % self.rom_banks.len()
+    }
+
+    fn ram(&self) -> Option<&RamBank> {
+        self.ram_bank().map(|bank| &self.ram_banks[bank])
+    }
+
+    fn ram_mut(&mut self) -> Option<&mut RamBank> {
+        let bank = self.ram_bank()?;
+        Some(&mut self.ram_banks[bank])
+    }
+
+    fn set_ram_enable(&mut self, data: Word) {
+        self.ram_enable = data == 0x0A;
+    }
+
+    fn set_ram_bank_sel(&mut self, data: Word) {
+        if self.has_rumble {
+            self.rumble = data & 0x08 != 0;
+            self.ram_bank_sel = data & 0x07;
+        } else {
+            self.ram_bank_sel = data & 0x0F;
+        }
+    }
+}
+
+impl MBC for MBC5 {
+    fn read(&self, addr: Addr) -> Word {
+        match addr {
+            ROM0_ADDR_LOW_BOUND..=ROM0_ADDR_HIGH_BOUND => {
+                self.rom_banks[0][(addr - ROM0_ADDR_LOW_BOUND) as usize]
+            }
+            ROM1_ADDR_LOW_BOUND..=ROM1_ADDR_HIGH_BOUND => {
+                self.rom_banks[self.rom1_bank()][(addr - ROM1_ADDR_LOW_BOUND) as usize]
+            }
+            RAM_ADDR_LOW_BOUND..=RAM_ADDR_HIGH_BOUND => match (self.ram_enable, self.ram()) {
+                (true, Some(ram)) => ram[(addr - RAM_ADDR_LOW_BOUND) as usize],
+                _ => 0xFF,
+            },
+            _ => {
+                warn!("illegal read cart at address: 0x{addr:04X}");
+                0xFF
+            }
+        }
+    }
+
+    fn write(&mut self, addr: Addr, data: Word) {
+        match addr {
+            0x0000..=0x1FFF => self.set_ram_enable(data),
+            0x2000..=0x2FFF => self.rom_bank_sel = self.rom_bank_sel & 0x100 | data as u16,
+            0x3000..=0x3FFF => {
+                self.rom_bank_sel = self.rom_bank_sel & 0xFF | ((data & 0x01) as u16) << 8
+            }
+            0x4000..=0x5FFF => self.set_ram_bank_sel(data),
+            0x6000..=0x7FFF => {}
+            RAM_ADDR_LOW_BOUND..=RAM_ADDR_HIGH_BOUND => {
+                if self.ram_enable {
+                    if let Some(ram) = self.ram_mut() {
+                        ram[(addr - RAM_ADDR_LOW_BOUND) as usize] = data;
+                    }
+                }
+            }
+            _ => warn!("illegal write cart at address: 0x{addr:04X}"),
+        }
+    }
+
+    fn rom_banks_mut(&mut self) -> &mut Box<[RomBank]> {
+        &mut self.rom_banks
+    }
+
+    fn rom_bank(&self, addr: Addr) -> usize {
+        if addr <= ROM0_ADDR_HIGH_BOUND {
+            0
+        } else {
+            self.rom1_bank()
+        }
+    }
+
+    fn cart_rom(&self) -> &Rom {
+        let rom = self.rom_banks.as_ref();
+        slice_as_bytes(rom)
+    }
+
+    fn sram(&self) -> &[u8] {
+        self.ram_banks.as_flattened()
+    }
+
+    fn sram_mut(&mut self) -> &mut [u8] {
+        self.ram_banks.as_flattened_mut()
+    }
+
+    fn ram_bank(&self) -> Option<usize> {
+        if self.ram_banks.is_empty() {
+            return None;
+        }
+        Some(self.ram_bank_sel as usize % self.ram_banks.len())
+    }
+
+    fn new(rom: Box<[u8]>, ram_size: usize, _: bool, _: i64) -> EmuResult<Self> {
+        let rom_banks: Box<[RomBank]> = unsafe { bytes_to_slice(rom) };
+        let ram_banks_num = ram_size / RAM_BANK_SIZE;
+        let ram_banks = vec![[0; RAM_BANK_SIZE]; ram_banks_num].into_boxed_slice();
+        Ok(Self {
+            rom_banks,
+            ram_banks,
+            rom_bank_sel: 1,
+            ram_bank_sel: 0,
+            ram_enable: false,
+            has_rumble: false,
+            rumble: false,
+        })
+    }
+}
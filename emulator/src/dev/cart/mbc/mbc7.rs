@@ -0,0 +1,324 @@
+use super::{RomBank, MBC};
+use crate::{
+    dev::cart::{
+        Rom, RAM_ADDR_HIGH_BOUND, RAM_ADDR_LOW_BOUND, ROM0_ADDR_HIGH_BOUND, ROM0_ADDR_LOW_BOUND,
+        ROM1_ADDR_HIGH_BOUND, ROM1_ADDR_LOW_BOUND,
+    },
+    error::EmuResult,
+    types::{Addr, Word},
+    utils::bytes::{bytes_to_slice, slice_as_bytes},
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+/// 93LC56: 128个16位字
+const EEPROM_WORDS: usize = 128;
+/// 水平放置时加速度计的读数
+const ACCEL_CENTER: u16 = 0x81D0;
+/// 1g对应的读数变化
+const ACCEL_PER_G: f32 = 0x70 as f32;
+/// 擦除后等待锁存的加速度计读数
+const ACCEL_ERASED: u16 = 0x8000;
+
+/// 串行EEPROM的状态, 片选有效时在时钟上升沿移入DI
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum EepromState {
+    /// 等待起始位
+    Idle,
+    /// 接收2位操作码和8位地址
+    Command { bits: u16, count: u8 },
+    /// 从DO移出16位数据
+    Read { data: u16, count: u8 },
+    /// 接收16位数据, `addr`为`None`时写入所有字
+    Write {
+        addr: Option<u8>,
+        data: u16,
+        count: u8,
+    },
+    /// 命令执行完毕, 等待片选无效
+    Done,
+}
+
+/// 93LC56串行EEPROM, 16位组织
+#[derive(Serialize, Deserialize)]
+pub struct Eeprom {
+    /// 按小端字节序保存的字, 即`.sav`文件的内容
+    pub data: Box<[u8]>,
+    pub state: EepromState,
+    pub write_enable: bool,
+    pub cs: bool,
+    pub clk: bool,
+    pub data_in: bool,
+    pub data_out: bool,
+}
+
+impl Eeprom {
+    fn new() -> Self {
+        Self {
+            data: vec![0xFF; EEPROM_WORDS * 2].into_boxed_slice(),
+            state: EepromState::Idle,
+            write_enable: false,
+            cs: false,
+            clk: false,
+            data_in: false,
+            data_out: true,
+        }
+    }
+
+    fn word(&self, addr: u8) -> u16 {
+        let at = (addr as usize % EEPROM_WORDS) * 2;
+        u16::from_le_bytes([self.data[at], self.data[at + 1]])
+    }
+
+    fn set_word(&mut self, addr: u8, word: u16) {
+        if self.write_enable {
+            let at = (addr as usize % EEPROM_WORDS) * 2;
+            self.data[at..at + 2].copy_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    /// bit 7: CS, bit 6: CLK, bit 1: DI, bit 0: DO
+    fn read(&self) -> Word {
+        (self.cs as Word) << 7
+            | (self.clk as Word) << 6
+            | (self.data_in as Word) << 1
+            | self.data_out as Word
+    }
+
+    fn write(&mut self, data: Word) {
+        let (cs, clk, di) = (data & 0x80 != 0, data & 0x40 != 0, data & 0x02 != 0);
+        let rising = !self.clk && clk;
+        (self.cs, self.clk, self.data_in) = (cs, clk, di);
+        if !cs {
+            // 片选无效时复位, DO输出就绪
+            self.state = EepromState::Idle;
+            self.data_out = true;
+        } else if rising {
+            self.clock(di);
+        }
+    }
+
+    fn clock(&mut self, di: bool) {
+        self.state = match self.state {
+            EepromState::Idle if di => EepromState::Command { bits: 0, count: 0 },
+            EepromState::Idle => EepromState::Idle,
+            EepromState::Command { bits, count } => {
+                let bits = bits << 1 | di as u16;
+                if count + 1 == 10 {
+                    self.execute(bits)
+                } else {
+                    EepromState::Command {
+                        bits,
+                        count: count + 1,
+                    }
+                }
+            }
+            EepromState::Read { data, count } => {
+                self.data_out = data & 0x8000 != 0;
+                match count + 1 {
+                    16 => EepromState::Done,
+                    count => EepromState::Read {
+                        data: data << 1,
+                        count,
+                    },
+                }
+            }
+            EepromState::Write { addr, data, count } if count + 1 < 16 => EepromState::Write {
+                addr,
+                data: data << 1 | di as u16,
+                count: count + 1,
+            },
+            EepromState::Write { addr, data, .. } => {
+                let data = data << 1 | di as u16;
+                match addr {
+                    Some(addr) => self.set_word(addr, data),
+                    None => (0..EEPROM_WORDS as u8).for_each(|addr| self.set_word(addr, data)),
+                }
+                self.data_out = true;
+                EepromState::Done
+            }
+            EepromState::Done => EepromState::Done,
+        }
+    }
+
+    /// `bits`: 2位操作码, 8位地址(最高位无效)
+    fn execute(&mut self, bits: u16) -> EepromState {
+        let addr = (bits & 0x7F) as u8;
+        match bits >> 8 {
+            // READ, 先输出一个0
+            0b10 => {
+                self.data_out = false;
+                EepromState::Read {
+                    data: self.word(addr),
+                    count: 0,
+                }
+            }
+            // WRITE
+            0b01 => EepromState::Write {
+                addr: Some(addr),
+                data: 0,
+                count: 0,
+            },
+            // ERASE
+            0b11 => {
+                self.set_word(addr, 0xFFFF);
+                EepromState::Done
+            }
+            _ => match bits >> 6 & 0b11 {
+                // EWDS
+                0b00 => {
+                    self.write_enable = false;
+                    EepromState::Done
+                }
+                // WRAL
+                0b01 => EepromState::Write {
+                    addr: None,
+                    data: 0,
+                    count: 0,
+                },
+                // ERAL
+                0b10 => {
+                    (0..EEPROM_WORDS as u8).for_each(|addr| self.set_word(addr, 0xFFFF));
+                    EepromState::Done
+                }
+                // EWEN
+                _ => {
+                    self.write_enable = true;
+                    EepromState::Done
+                }
+            },
+        }
+    }
+}
+
+/// ref https://gbdev.io/pandocs/MBC7.html
+/// 7位ROM bank, 0xA000-0xAFFF为寄存器: 两轴加速度计和串行EEPROM, 需要两个RAM启用寄存器都打开
+#[derive(Serialize, Deserialize)]
+pub struct MBC7 {
+    /// 不写入存档, 恢复状态时从已加载的卡带取回
+    #[serde(skip)]
+    pub rom_banks: Box<[RomBank]>,
+    pub rom_bank_sel: u8,
+    pub ram_enable1: bool,
+    pub ram_enable2: bool,
+    pub eeprom: Eeprom,
+    /// 宿主输入的倾斜(x, y), 单位为g
+    pub tilt: (f32, f32),
+    /// 锁存的加速度计读数(x, y)
+    pub accel: (u16, u16),
+}
+
+impl MBC7 {
+    fn rom1_bank(&self) -> usize {
+        self.rom_bank_sel as usize % self.rom_banks.len()
+    }
+
+    fn enabled(&self) -> bool {
+        self.ram_enable1 && self.ram_enable2
+    }
+
+    fn read_reg(&self, addr: Addr) -> Word {
+        let (x, y) = self.accel;
+        match addr >> 4 & 0x0F {
+            0x2 => x as Word,
+            0x3 => (x >> 8) as Word,
+            0x4 => y as Word,
+            0x5 => (y >> 8) as Word,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_reg(&mut self, addr: Addr, data: Word) {
+        match (addr >> 4 & 0x0F, data) {
+            (0x0, 0x55) => self.accel = (ACCEL_ERASED, ACCEL_ERASED),
+            (0x1, 0xAA) if self.accel == (ACCEL_ERASED, ACCEL_ERASED) => {
+                let (x, y) = self.tilt;
+                let reading = |g: f32| (ACCEL_CENTER as f32 + g * ACCEL_PER_G) as u16;
+                self.accel = (reading(-x), reading(y));
+            }
+            (0x8, _) => self.eeprom.write(data),
+            _ => {}
+        }
+    }
+
+    /// 设置倾斜, `x`向右为正, `y`向下为正, 单位为g
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt = (x.clamp(-2.0, 2.0), y.clamp(-2.0, 2.0));
+    }
+}
+
+impl MBC for MBC7 {
+    fn read(&self, addr: Addr) -> Word {
+        match addr {
+            ROM0_ADDR_LOW_BOUND..=ROM0_ADDR_HIGH_BOUND => {
+                self.rom_banks[0][(addr - ROM0_ADDR_LOW_BOUND) as usize]
+            }
+            ROM1_ADDR_LOW_BOUND..=ROM1_ADDR_HIGH_BOUND => {
+                self.rom_banks[self.rom1_bank()][(addr - ROM1_ADDR_LOW_BOUND) as usize]
+            }
+            0xA000..=0xAFFF if self.enabled() => self.read_reg(addr),
+            RAM_ADDR_LOW_BOUND..=RAM_ADDR_HIGH_BOUND => 0xFF,
+            _ => {
+                warn!("illegal read cart at address: 0x{addr:04X}");
+                0xFF
+            }
+        }
+    }
+
+    fn write(&mut self, addr: Addr, data: Word) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enable1 = data == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank_sel = data & 0x7F,
+            0x4000..=0x5FFF => self.ram_enable2 = data == 0x40,
+            0x6000..=0x7FFF => {}
+            0xA000..=0xAFFF if self.enabled() => self.write_reg(addr, data),
+            RAM_ADDR_LOW_BOUND..=RAM_ADDR_HIGH_BOUND => {}
+            _ => warn!("illegal write cart at address: 0x{addr:04X}"),
+        }
+    }
+
+    fn rom_banks_mut(&mut self) -> &mut Box<[RomBank]> {
+        &mut self.rom_banks
+    }
+
+    fn rom_bank(&self, addr: Addr) -> usize {
+        if addr <= ROM0_ADDR_HIGH_BOUND {
+            0
+        } else {
+            self.rom1_bank()
+        }
+    }
+
+    fn cart_rom(&self) -> &Rom {
+        let rom = self.rom_banks.as_ref();
+        slice_as_bytes(rom)
+    }
+
+    fn sram(&self) -> &[u8] {
+        &self.eeprom.data
+    }
+
+    fn sram_mut(&mut self) -> &mut [u8] {
+        &mut self.eeprom.data
+    }
+
+    /// 0xA000-0xBFFF不直接映射EEPROM
+    fn ram_bank(&self) -> Option<usize> {
+        None
+    }
+
+    fn new(rom: Box<[u8]>, _: usize, _: bool, _: i64) -> EmuResult<Self> {
+        let rom_banks: Box<[RomBank]> = unsafe { bytes_to_slice(rom) };
+        Ok(Self {
+            rom_banks,
+            rom_bank_sel: 1,
+            ram_enable1: false,
+            ram_enable2: false,
+            eeprom: Eeprom::new(),
+            tilt: (0.0, 0.0),
+            accel: (ACCEL_ERASED, ACCEL_ERASED),
+        })
+    }
+}
@@ -0,0 +1,229 @@
+use super::{RamBank, RomBank, MBC, RAM_BANK_SIZE};
+use crate::{
+    dev::cart::{
+        Rom, RAM_ADDR_HIGH_BOUND, RAM_ADDR_LOW_BOUND, ROM0_ADDR_HIGH_BOUND, ROM0_ADDR_LOW_BOUND,
+        ROM1_ADDR_HIGH_BOUND, ROM1_ADDR_LOW_BOUND,
+    },
+    error::EmuResult,
+    types::{Addr, Word},
+    utils::bytes::{bytes_to_slice, slice_as_bytes},
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+/// ref https://gbdev.io/pandocs/MMM01.html
+/// 多游戏合集. 上电时ROM末尾的32KB(菜单)映射到0x0000-0x7FFF, 菜单设置好游戏所在的基址和掩码后
+/// 写入映射位进入映射模式, 之后的行为类似MBC1, 被锁定的寄存器位直到复位前不能再修改
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+pub struct MMM01 {
+    /// 不写入存档, 恢复状态时从已加载的卡带取回
+    #[serde(skip)]
+    pub rom_banks: Box<[RomBank]>,
+    #[serde_as(as = "Box<[[_; RAM_BANK_SIZE]]>")]
+    pub ram_banks: Box<[RamBank]>,
+    /// 是否已离开菜单进入映射模式
+    pub mapped: bool,
+    pub ram_enable: bool,
+    /// ROM bank的bit 0-4
+    pub rom_bank_low: u8,
+    /// ROM bank的bit 5-6, 映射后锁定
+    pub rom_bank_mid: u8,
+    /// ROM bank的bit 7-8, 映射后锁定
+    pub rom_bank_high: u8,
+    /// RAM bank的bit 0-1
+    pub ram_bank_low: u8,
+    /// RAM bank的bit 2-3, 映射后锁定
+    pub ram_bank_high: u8,
+    /// 置位的位对应`rom_bank_low`的bit 1-4, 映射后不能再修改, 0x0000-0x3FFF也使用这些位
+    pub rom_bank_mask: u8,
+    /// 置位的位对应`ram_bank_low`的bit 0-1, 映射后不能再修改
+    pub ram_bank_mask: u8,
+    /// 同MBC1的模式寄存器
+    pub mode: bool,
+    /// 禁止修改`mode`
+    pub mode_locked: bool,
+    /// 交换`rom_bank_mid`和`ram_bank_low`的作用
+    pub multiplex: bool,
+}
+
+impl MMM01 {
+    /// (0x0000-0x3FFF, 0x4000-0x7FFF)映射的ROM bank
+    fn rom_banks_sel(&self) -> (usize, usize) {
+        let len = self.rom_banks.len();
+        if !self.mapped {
+            // 所有bank位都为1, 即ROM末尾的32KB
+            return (len.saturating_sub(2), len.saturating_sub(1));
+        }
+        let fixed = self.rom_bank_mask << 1;
+        let high = (self.rom_bank_high as usize) << 7;
+        let mid = if self.multiplex {
+            self.ram_bank_low
+        } else {
+            self.rom_bank_mid
+        };
+        let upper = high | (mid as usize) << 5;
+        let rom1 = upper | self.rom_bank_low as usize;
+        let rom0 = match (self.multiplex, self.mode) {
+            (true, false) => high,
+            _ => upper,
+        } | (self.rom_bank_low & fixed) as usize;
+        // 可写的低位为0时同MBC1映射下一个bank, 不考虑固定的位和高位
+        let rom1 = if self.rom_bank_low & !fixed == 0 {
+            rom1 + 1
+        } else {
+            rom1
+        };
+        (rom0 % len, rom1 % len)
+    }
+
+    fn ram(&self) -> Option<&RamBank> {
+        self.ram_bank().map(|bank| &self.ram_banks[bank])
+    }
+
+    fn ram_mut(&mut self) -> Option<&mut RamBank> {
+        let bank = self.ram_bank()?;
+        Some(&mut self.ram_banks[bank])
+    }
+
+    fn set_ram_enable(&mut self, data: Word) {
+        self.ram_enable = data & 0x0F == 0x0A;
+        if !self.mapped {
+            self.ram_bank_mask = data >> 4 & 0x03;
+            self.mapped = data & 0x40 != 0;
+        }
+    }
+
+    fn set_rom_bank(&mut self, data: Word) {
+        if self.mapped {
+            let fixed = self.rom_bank_mask << 1;
+            self.rom_bank_low = self.rom_bank_low & fixed | data & 0x1F & !fixed;
+        } else {
+            self.rom_bank_low = data & 0x1F;
+            self.rom_bank_mid = data >> 5 & 0x03;
+        }
+    }
+
+    fn set_ram_bank(&mut self, data: Word) {
+        if self.mapped {
+            let fixed = self.ram_bank_mask;
+            self.ram_bank_low = self.ram_bank_low & fixed | data & 0x03 & !fixed;
+        } else {
+            self.ram_bank_low = data & 0x03;
+            self.ram_bank_high = data >> 2 & 0x03;
+            self.rom_bank_high = data >> 4 & 0x03;
+            self.mode_locked = data & 0x40 != 0;
+        }
+    }
+
+    fn set_mode(&mut self, data: Word) {
+        if !self.mode_locked {
+            self.mode = data & 0x01 != 0;
+        }
+        if !self.mapped {
+            self.rom_bank_mask = data >> 2 & 0x0F;
+            self.multiplex = data & 0x40 != 0;
+        }
+    }
+}
+
+impl MBC for MMM01 {
+    fn read(&self, addr: Addr) -> Word {
+        let (rom0, rom1) = self.rom_banks_sel();
+        match addr {
+            ROM0_ADDR_LOW_BOUND..=ROM0_ADDR_HIGH_BOUND => {
+                self.rom_banks[rom0][(addr - ROM0_ADDR_LOW_BOUND) as usize]
+            }
+            ROM1_ADDR_LOW_BOUND..=ROM1_ADDR_HIGH_BOUND => {
+                self.rom_banks[rom1][(addr - ROM1_ADDR_LOW_BOUND) as usize]
+            }
+            RAM_ADDR_LOW_BOUND..=RAM_ADDR_HIGH_BOUND => match (self.ram_enable, self.ram()) {
+                (true, Some(ram)) => ram[(addr - RAM_ADDR_LOW_BOUND) as usize],
+                _ => 0xFF,
+            },
+            _ => {
+                warn!("illegal read cart at address: 0x{addr:04X}");
+                0xFF
+            }
+        }
+    }
+
+    fn write(&mut self, addr: Addr, data: Word) {
+        match addr {
+            0x0000..=0x1FFF => self.set_ram_enable(data),
+            0x2000..=0x3FFF => self.set_rom_bank(data),
+            0x4000..=0x5FFF => self.set_ram_bank(data),
+            0x6000..=0x7FFF => self.set_mode(data),
+            RAM_ADDR_LOW_BOUND..=RAM_ADDR_HIGH_BOUND => {
+                if self.ram_enable {
+                    if let Some(ram) = self.ram_mut() {
+                        ram[(addr - RAM_ADDR_LOW_BOUND) as usize] = data;
+                    }
+                }
+            }
+            _ => warn!("illegal write cart at address: 0x{addr:04X}"),
+        }
+    }
+
+    fn rom_banks_mut(&mut self) -> &mut Box<[RomBank]> {
+        &mut self.rom_banks
+    }
+
+    fn rom_bank(&self, addr: Addr) -> usize {
+        let (rom0, rom1) = self.rom_banks_sel();
+        if addr <= ROM0_ADDR_HIGH_BOUND {
+            rom0
+        } else {
+            rom1
+        }
+    }
+
+    fn cart_rom(&self) -> &Rom {
+        let rom = self.rom_banks.as_ref();
+        slice_as_bytes(rom)
+    }
+
+    fn sram(&self) -> &[u8] {
+        self.ram_banks.as_flattened()
+    }
+
+    fn sram_mut(&mut self) -> &mut [u8] {
+        self.ram_banks.as_flattened_mut()
+    }
+
+    fn ram_bank(&self) -> Option<usize> {
+        if self.ram_banks.is_empty() {
+            return None;
+        }
+        let low = if self.multiplex {
+            self.rom_bank_mid
+        } else {
+            self.ram_bank_low
+        };
+        let bank = (self.ram_bank_high as usize) << 2 | low as usize;
+        Some(bank % self.ram_banks.len())
+    }
+
+    fn new(rom: Box<[u8]>, ram_size: usize, _: bool, _: i64) -> EmuResult<Self> {
+        let rom_banks: Box<[RomBank]> = unsafe { bytes_to_slice(rom) };
+        let ram_banks_num = ram_size / RAM_BANK_SIZE;
+        let ram_banks = vec![[0; RAM_BANK_SIZE]; ram_banks_num].into_boxed_slice();
+        Ok(Self {
+            rom_banks,
+            ram_banks,
+            mapped: false,
+            ram_enable: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_mask: 0,
+            mode: false,
+            mode_locked: false,
+            multiplex: false,
+        })
+    }
+}
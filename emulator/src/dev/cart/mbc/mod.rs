@@ -9,9 +9,14 @@ use crate::{
     types::{Addr, Word},
 };
 
+pub mod huc1;
+pub mod huc3;
 pub mod mbc1;
 pub mod mbc2;
 pub mod mbc3;
+pub mod mbc5;
+pub mod mbc7;
+pub mod mmm01;
 pub mod no_mbc;
 pub mod rtc;
 
@@ -23,6 +28,7 @@ pub const RAM_BANK_SIZE: usize = 8 * KB;
 pub type RomBank = [Word; ROM_BANK_SIZE];
 pub type RamBank = [Word; RAM_BANK_SIZE];
 
+#[allow(clippy::upper_case_acronyms)]
 pub trait MBC: Sized {
     fn new(rom: Box<[u8]>, ram_size: usize, has_rtc: bool, timestamp: i64) -> EmuResult<Self>;
 
@@ -38,7 +44,7 @@ pub trait MBC: Sized {
         }
     }
 
-    fn write(&mut self, addr: Addr, data: Word) {
+    fn write(&mut self, addr: Addr, _data: Word) {
         match addr {
             ROM0_ADDR_LOW_BOUND..=ROM0_ADDR_HIGH_BOUND => todo!(),
             ROM1_ADDR_LOW_BOUND..=ROM1_ADDR_HIGH_BOUND => todo!(),
@@ -50,4 +56,70 @@ pub trait MBC: Sized {
     }
 
     fn cart_rom(&self) -> &Rom;
+
+    /// `addr`(0x0000-0x7FFF)处映射的ROM bank
+    fn rom_bank(&self, addr: Addr) -> usize {
+        if addr <= ROM0_ADDR_HIGH_BOUND {
+            0
+        } else {
+            1
+        }
+    }
+
+    fn rom_banks_mut(&mut self) -> &mut Box<[RomBank]>;
+
+    /// 卡带外部RAM, 按bank顺序排列, 即`.sav`文件的内容
+    fn sram(&self) -> &[u8] {
+        &[]
+    }
+
+    fn sram_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+
+    /// 0xA000-0xBFFF映射的外部RAM bank, 不考虑RAM是否启用, 映射的不是RAM时返回`None`
+    fn ram_bank(&self) -> Option<usize> {
+        (!self.sram().is_empty()).then_some(0)
+    }
+
+    /// 外部RAM地址`addr`在`sram`中的下标
+    fn ram_offset(&self, addr: Addr) -> Option<usize> {
+        let offset = self.ram_bank()? * RAM_BANK_SIZE + (addr - RAM_ADDR_LOW_BOUND) as usize;
+        (offset < self.sram().len()).then_some(offset)
+    }
+
+    /// 读取当前映射的内容, 不输出日志, 忽略外部RAM的启用状态
+    fn peek(&self, addr: Addr) -> Word {
+        match addr {
+            ROM0_ADDR_LOW_BOUND..=ROM1_ADDR_HIGH_BOUND => {
+                let offset = self.rom_bank(addr) * ROM_BANK_SIZE + addr as usize % ROM_BANK_SIZE;
+                self.cart_rom().get(offset).copied().unwrap_or(0xFF)
+            }
+            RAM_ADDR_LOW_BOUND..=RAM_ADDR_HIGH_BOUND => self.peek_ram(addr),
+            _ => 0xFF,
+        }
+    }
+
+    fn peek_ram(&self, addr: Addr) -> Word {
+        self.ram_offset(addr)
+            .map_or(0xFF, |offset| self.sram()[offset])
+    }
+
+    /// 绕过MBC寄存器直接修改当前映射的ROM或外部RAM
+    fn poke(&mut self, addr: Addr, data: Word) {
+        match addr {
+            ROM0_ADDR_LOW_BOUND..=ROM1_ADDR_HIGH_BOUND => {
+                let bank = self.rom_bank(addr);
+                if let Some(rom) = self.rom_banks_mut().get_mut(bank) {
+                    rom[addr as usize % ROM_BANK_SIZE] = data;
+                }
+            }
+            RAM_ADDR_LOW_BOUND..=RAM_ADDR_HIGH_BOUND => {
+                if let Some(offset) = self.ram_offset(addr) {
+                    self.sram_mut()[offset] = data;
+                }
+            }
+            _ => {}
+        }
+    }
 }
@@ -1,5 +1,3 @@
-use std::mem::size_of;
-
 use crate::{
     anyerror,
     dev::cart::{
@@ -8,23 +6,22 @@ use crate::{
     },
     error::EmuResult,
     types::{Addr, Word},
-    utils::bytes::{bytes_to_value, slice_as_bytes},
+    utils::bytes::{bytes_to_slice, slice_as_bytes},
 };
 use log::warn;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
 use super::{RamBank, RomBank, MBC, RAM_BANK_SIZE, ROM_BANK_SIZE};
-type RomBanks = [RomBank; 2];
-type RamBanks = RamBank;
 
 #[serde_as]
 #[derive(Serialize, Deserialize)]
 pub struct NoMBC {
-    #[serde_as(as = "Box<[[_;ROM_BANK_SIZE];2]>")]
-    rom: Box<RomBanks>,
+    /// 固定2个bank, 不写入存档
+    #[serde(skip)]
+    rom: Box<[RomBank]>,
     #[serde_as(as = "Option<Box<[_;RAM_BANK_SIZE]>>")]
-    ram: Option<Box<RamBanks>>,
+    ram: Option<Box<RamBank>>,
 }
 
 impl MBC for NoMBC {
@@ -63,22 +60,40 @@ impl MBC for NoMBC {
         }
     }
 
+    fn rom_banks_mut(&mut self) -> &mut Box<[RomBank]> {
+        &mut self.rom
+    }
+
     fn cart_rom(&self) -> &Rom {
         let rom = self.rom.as_ref();
         slice_as_bytes(rom)
     }
 
+    fn sram(&self) -> &[u8] {
+        match &self.ram {
+            Some(ram) => ram.as_slice(),
+            None => &[],
+        }
+    }
+
+    fn sram_mut(&mut self) -> &mut [u8] {
+        match &mut self.ram {
+            Some(ram) => ram.as_mut_slice(),
+            None => &mut [],
+        }
+    }
+
     fn new(rom: Box<[u8]>, ram_size: usize, _: bool, _: i64) -> EmuResult<Self> {
-        if rom.len() != size_of::<RomBanks>() {
+        if rom.len() != 2 * ROM_BANK_SIZE {
             return anyerror!(
                 "invalid rom size: no mbc cart only allow 32KB rom size, found {rom_size}",
                 rom_size = rom.len()
             );
         };
-        let rom = unsafe { bytes_to_value::<RomBanks>(rom) };
+        let rom = unsafe { bytes_to_slice(rom) };
         let ram = if ram_size == 0 {
             None
-        } else if ram_size == size_of::<RamBanks>() {
+        } else if ram_size == RAM_BANK_SIZE {
             Some(Box::new([0; _]))
         } else {
             return anyerror!(
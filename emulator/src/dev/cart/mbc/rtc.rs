@@ -11,7 +11,13 @@ const HOUR: i64 = MIN * 60;
 const DAY: i64 = HOUR * 24;
 const OVERFLOW: i64 = DAY * 512;
 
+/// `.sav`末尾附加的RTC数据长度(VBA-M/BGB格式)
+pub const RTC_SAVE_SIZE: usize = 48;
+/// 部分模拟器使用32位时间戳
+pub const RTC_SAVE_SIZE_32: usize = 44;
+
 #[derive(Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
 pub struct RTC {
     sec: Word,
     min: Word,
@@ -87,33 +93,82 @@ impl RTC {
         }
     }
 
+    /// 当前时间和锁存的寄存器各5个小端u32, 随后是以秒为单位的小端u64 UNIX时间戳
+    pub fn export(&self) -> [u8; RTC_SAVE_SIZE] {
+        let mut data = [0; RTC_SAVE_SIZE];
+        let latched = [self.sec, self.min, self.hour, self.dl, self.dh];
+        let regs = [self.time_regs(), latched];
+        for (chunk, &reg) in data.chunks_exact_mut(4).zip(regs.iter().flatten()) {
+            chunk.copy_from_slice(&(reg as u32).to_le_bytes());
+        }
+        data[40..].copy_from_slice(&(self.time / SEC).to_le_bytes());
+        data
+    }
+
+    /// `data`的长度为`RTC_SAVE_SIZE`或`RTC_SAVE_SIZE_32`
+    pub fn import(&mut self, data: &[u8]) {
+        let reg = |idx: usize| data[idx * 4];
+        // 32位时间戳零扩展
+        let mut timestamp = [0; 8];
+        timestamp[..data.len() - 40].copy_from_slice(&data[40..]);
+        self.time = i64::from_le_bytes(timestamp) * SEC;
+        [self.sec, self.min, self.hour, self.dl, self.dh] =
+            [reg(0), reg(1), reg(2), reg(3), reg(4)];
+        self.update_epoch();
+        [self.sec, self.min, self.hour, self.dl, self.dh] =
+            [reg(5), reg(6), reg(7), reg(8), reg(9)];
+        self.latching = false;
+        self.latched = false;
+    }
+
+    /// (当天的分钟数, 天数), HuC3的时钟只精确到分钟
+    pub fn clock(&self) -> (u16, u16) {
+        (self.hour as u16 * 60 + self.min as u16, self.days())
+    }
+
+    /// 设置时钟, 天数超出9位的部分被截断
+    pub fn set_clock(&mut self, minutes: u16, days: u16) {
+        self.sec = 0;
+        self.min = (minutes % 60) as Word;
+        self.hour = (minutes / 60 % 24) as Word;
+        self.dl = days as Word;
+        self.dh = self.dh.setval_at(0, days & 0x100 != 0).clear_at(7);
+        self.update_epoch()
+    }
+
     fn update_time_regs(&mut self) {
+        [self.sec, self.min, self.hour, self.dl, self.dh] = self.time_regs();
+    }
+
+    fn time_regs(&self) -> [Word; 5] {
         let ms = self.time - self.epoch;
-        let sec = ms / 1000;
-        self.sec = (sec % 60) as Word;
+        let sec = ms / SEC;
         let min = sec / 60;
-        self.min = (min % 60) as Word;
         let hour = min / 60;
-        self.hour = (hour % 24) as Word;
         let day = hour / 24;
-        self.dl = (day & 0xFF) as Word;
-        self.day_master_bit_mut().setval(day & 0x100 != 0);
-        self.day_overflow_mut().setval(day >= 512);
+        let dh = self
+            .dh
+            .setval_at(0, day & 0x100 != 0)
+            .setval_at(7, day >= 512);
+        [
+            (sec % 60) as Word,
+            (min % 60) as Word,
+            (hour % 24) as Word,
+            (day & 0xFF) as Word,
+            dh,
+        ]
     }
 
     fn days(&self) -> u16 {
         (self.dl as u16) | (self.dh.at(0) as u16) << 8
     }
 
-    fn day_overflow_mut(&mut self) -> BitProxy {
-        BitProxy::new(&mut self.dh, 7)
-    }
-
     fn day_overflow(&self) -> bool {
         self.dh.test(7)
     }
 
-    fn halt_mut(&mut self) -> BitProxy {
+    #[allow(unused)]
+    fn halt_mut(&mut self) -> BitProxy<'_> {
         BitProxy::new(&mut self.dh, 6)
     }
 
@@ -121,10 +176,7 @@ impl RTC {
         self.dh.test(6)
     }
 
-    fn day_master_bit_mut(&mut self) -> BitProxy {
-        BitProxy::new(&mut self.dh, 0)
-    }
-
+    #[allow(unused)]
     fn day_master_bit(&self) -> bool {
         self.dh.test(0)
     }
@@ -133,7 +185,7 @@ impl RTC {
         let duration = self.sec as i64 * SEC
             + self.min as i64 * MIN
             + self.hour as i64 * HOUR
-            + self.days() as i64 * HOUR
+            + self.days() as i64 * DAY
             + if self.day_overflow() { 1 } else { 0 } * OVERFLOW;
         self.epoch = self.time - duration;
     }
@@ -1,16 +1,29 @@
 use self::header::Header;
 use crate::{
     dev::MemoryRegion,
-    error::{EmuErr, EmuResult, EmulatorError::UnknownMBCType},
+    error::{EmuErr, EmuResult, InvalidSaveSize, NoBattery, UnknownMBCType},
     types::{Addr, Word},
 };
 use header::MBCType;
-use log::{debug, error};
-use mbc::{mbc1::MBC1, mbc2::MBC2, mbc3::MBC3, no_mbc::NoMBC, MBC};
+use mbc::{
+    huc1::HuC1,
+    huc3::HuC3,
+    mbc1::MBC1,
+    mbc2::MBC2,
+    mbc3::MBC3,
+    mbc5::MBC5,
+    mbc7::MBC7,
+    mmm01::MMM01,
+    no_mbc::NoMBC,
+    rtc::{RTC, RTC_SAVE_SIZE, RTC_SAVE_SIZE_32},
+    RomBank, MBC,
+};
+pub use mbc::{RAM_BANK_SIZE, ROM_BANK_SIZE};
 use serde::{Deserialize, Serialize};
 
 mod header;
 mod mbc;
+pub mod patch;
 
 const ROM0_ADDR_LOW_BOUND: Addr = 0x0000;
 const ROM0_ADDR_HIGH_BOUND: Addr = 0x3FFF;
@@ -27,22 +40,47 @@ pub enum Cart {
     MBC1(MBC1),
     MBC2(MBC2),
     MBC3(MBC3),
+    MBC5(MBC5),
+    MMM01(MMM01),
+    HuC1(HuC1),
+    HuC3(HuC3),
+    MBC7(MBC7),
 }
 
 impl Cart {
     pub fn new(rom: Box<Rom>, timestamp: i64) -> EmuResult<Self> {
-        let (ram_size, mbc_type, has_rtc) = {
-            let header = Header::from_rom(&rom)?;
+        let (ram_size, mbc_type, has_rtc, has_rumble) = {
+            let offset = Header::mmm01_offset(&rom).unwrap_or(0);
+            let header = Header::from_rom(&rom[offset..])?;
             let mbc_type = header.mbc_type();
             let has_rtc = header.has_rtc();
+            let has_rumble = header.has_rumble();
             let ram_size = header.ram_size();
-            (ram_size, mbc_type, has_rtc)
+            (ram_size, mbc_type, has_rtc, has_rumble)
+        };
+        // 过量转储和补丁产生的ROM可能大于头部声明的大小或不足整数个bank,
+        // 按实际长度用0xFF补齐到整数个bank, bank数量也由实际长度决定
+        let rom = if rom.len().is_multiple_of(ROM_BANK_SIZE) {
+            rom
+        } else {
+            let mut rom = rom.into_vec();
+            rom.resize(rom.len().next_multiple_of(ROM_BANK_SIZE), 0xFF);
+            rom.into_boxed_slice()
         };
         match mbc_type {
             Some(MBCType::NoMBC) => Ok(Cart::NoMBC(NoMBC::new(rom, ram_size, has_rtc, timestamp)?)),
             Some(MBCType::MBC1) => Ok(Cart::MBC1(MBC1::new(rom, ram_size, has_rtc, timestamp)?)),
             Some(MBCType::MBC2) => Ok(Cart::MBC2(MBC2::new(rom, ram_size, has_rtc, timestamp)?)),
             Some(MBCType::MBC3) => Ok(Cart::MBC3(MBC3::new(rom, ram_size, has_rtc, timestamp)?)),
+            Some(MBCType::MBC5) => {
+                let mut mbc = MBC5::new(rom, ram_size, has_rtc, timestamp)?;
+                mbc.has_rumble = has_rumble;
+                Ok(Cart::MBC5(mbc))
+            }
+            Some(MBCType::MMM01) => Ok(Cart::MMM01(MMM01::new(rom, ram_size, has_rtc, timestamp)?)),
+            Some(MBCType::HuC1) => Ok(Cart::HuC1(HuC1::new(rom, ram_size, has_rtc, timestamp)?)),
+            Some(MBCType::HuC3) => Ok(Cart::HuC3(HuC3::new(rom, ram_size, has_rtc, timestamp)?)),
+            Some(MBCType::MBC7) => Ok(Cart::MBC7(MBC7::new(rom, ram_size, has_rtc, timestamp)?)),
             None => EmuErr(UnknownMBCType),
         }
     }
@@ -53,15 +91,182 @@ impl Cart {
             Cart::MBC1(c) => c.cart_rom(),
             Cart::MBC2(c) => c.cart_rom(),
             Cart::MBC3(c) => c.cart_rom(),
+            Cart::MBC5(c) => c.cart_rom(),
+            Cart::MMM01(c) => c.cart_rom(),
+            Cart::HuC1(c) => c.cart_rom(),
+            Cart::HuC3(c) => c.cart_rom(),
+            Cart::MBC7(c) => c.cart_rom(),
+        }
+    }
+
+    /// `addr`(0x0000-0x7FFF)处映射的ROM bank
+    pub fn rom_bank(&self, addr: Addr) -> usize {
+        match self {
+            Cart::NoMBC(c) => c.rom_bank(addr),
+            Cart::MBC1(c) => c.rom_bank(addr),
+            Cart::MBC2(c) => c.rom_bank(addr),
+            Cart::MBC3(c) => c.rom_bank(addr),
+            Cart::MBC5(c) => c.rom_bank(addr),
+            Cart::MMM01(c) => c.rom_bank(addr),
+            Cart::HuC1(c) => c.rom_bank(addr),
+            Cart::HuC3(c) => c.rom_bank(addr),
+            Cart::MBC7(c) => c.rom_bank(addr),
+        }
+    }
+
+    /// 读取当前映射的ROM或外部RAM, 没有副作用和日志, 忽略外部RAM的启用状态
+    pub fn peek(&self, addr: Addr) -> Word {
+        match self {
+            Cart::NoMBC(c) => c.peek(addr),
+            Cart::MBC1(c) => c.peek(addr),
+            Cart::MBC2(c) => c.peek(addr),
+            Cart::MBC3(c) => c.peek(addr),
+            Cart::MBC5(c) => c.peek(addr),
+            Cart::MMM01(c) => c.peek(addr),
+            Cart::HuC1(c) => c.peek(addr),
+            Cart::HuC3(c) => c.peek(addr),
+            Cart::MBC7(c) => c.peek(addr),
+        }
+    }
+
+    /// 绕过MBC寄存器直接修改当前映射的ROM或外部RAM
+    pub fn poke(&mut self, addr: Addr, data: Word) {
+        match self {
+            Cart::NoMBC(c) => c.poke(addr, data),
+            Cart::MBC1(c) => c.poke(addr, data),
+            Cart::MBC2(c) => c.poke(addr, data),
+            Cart::MBC3(c) => c.poke(addr, data),
+            Cart::MBC5(c) => c.poke(addr, data),
+            Cart::MMM01(c) => c.poke(addr, data),
+            Cart::HuC1(c) => c.poke(addr, data),
+            Cart::HuC3(c) => c.poke(addr, data),
+            Cart::MBC7(c) => c.poke(addr, data),
+        }
+    }
+
+    fn rom_banks_mut(&mut self) -> &mut Box<[RomBank]> {
+        match self {
+            Cart::NoMBC(c) => c.rom_banks_mut(),
+            Cart::MBC1(c) => c.rom_banks_mut(),
+            Cart::MBC2(c) => c.rom_banks_mut(),
+            Cart::MBC3(c) => c.rom_banks_mut(),
+            Cart::MBC5(c) => c.rom_banks_mut(),
+            Cart::MMM01(c) => c.rom_banks_mut(),
+            Cart::HuC1(c) => c.rom_banks_mut(),
+            Cart::HuC3(c) => c.rom_banks_mut(),
+            Cart::MBC7(c) => c.rom_banks_mut(),
+        }
+    }
+
+    /// 存档中的卡带不含ROM, 恢复状态时与已加载的卡带交换ROM
+    pub fn swap_rom(&mut self, other: &mut Cart) {
+        std::mem::swap(self.rom_banks_mut(), other.rom_banks_mut())
+    }
+
+    pub fn sram(&self) -> &[u8] {
+        match self {
+            Cart::NoMBC(c) => c.sram(),
+            Cart::MBC1(c) => c.sram(),
+            Cart::MBC2(c) => c.sram(),
+            Cart::MBC3(c) => c.sram(),
+            Cart::MBC5(c) => c.sram(),
+            Cart::MMM01(c) => c.sram(),
+            Cart::HuC1(c) => c.sram(),
+            Cart::HuC3(c) => c.sram(),
+            Cart::MBC7(c) => c.sram(),
         }
     }
 
+    pub fn sram_mut(&mut self) -> &mut [u8] {
+        match self {
+            Cart::NoMBC(c) => c.sram_mut(),
+            Cart::MBC1(c) => c.sram_mut(),
+            Cart::MBC2(c) => c.sram_mut(),
+            Cart::MBC3(c) => c.sram_mut(),
+            Cart::MBC5(c) => c.sram_mut(),
+            Cart::MMM01(c) => c.sram_mut(),
+            Cart::HuC1(c) => c.sram_mut(),
+            Cart::HuC3(c) => c.sram_mut(),
+            Cart::MBC7(c) => c.sram_mut(),
+        }
+    }
+
+    fn rtc(&self) -> Option<&RTC> {
+        match self {
+            Cart::MBC3(MBC3 { rtc, .. }) => rtc.as_ref(),
+            Cart::HuC3(HuC3 { rtc, .. }) => Some(rtc),
+            _ => None,
+        }
+    }
+
+    fn rtc_mut(&mut self) -> Option<&mut RTC> {
+        match self {
+            Cart::MBC3(MBC3 { rtc, .. }) => rtc.as_mut(),
+            Cart::HuC3(HuC3 { rtc, .. }) => Some(rtc),
+            _ => None,
+        }
+    }
+
+    /// 导出`.sav`: 外部RAM, 带RTC的MBC3和HuC3在末尾附加RTC数据
+    pub fn export_sav(&self) -> EmuResult<Box<[u8]>> {
+        if !self.header().has_battery() {
+            return EmuErr(NoBattery);
+        }
+        let mut sav = self.sram().to_vec();
+        if let Some(rtc) = self.rtc() {
+            sav.extend_from_slice(&rtc.export());
+        }
+        Ok(sav.into_boxed_slice())
+    }
+
+    /// 导入`.sav`, 带RTC的卡带允许缺少末尾的RTC数据
+    pub fn import_sav(&mut self, sav: &[u8]) -> EmuResult {
+        if !self.header().has_battery() {
+            return EmuErr(NoBattery);
+        }
+        let ram_size = self.sram().len();
+        let rtc = self.rtc_mut();
+        let rtc_size = sav.len().wrapping_sub(ram_size);
+        match rtc {
+            Some(rtc) if rtc_size == RTC_SAVE_SIZE || rtc_size == RTC_SAVE_SIZE_32 => {
+                rtc.import(&sav[ram_size..])
+            }
+            _ if rtc_size == 0 => {}
+            _ => {
+                return EmuErr(InvalidSaveSize {
+                    expected: ram_size,
+                    actual: sav.len(),
+                })
+            }
+        }
+        self.sram_mut().copy_from_slice(&sav[..ram_size]);
+        Ok(())
+    }
+
+    /// MMM01合集返回菜单的卡带头
     pub fn header(&self) -> &Header {
-        unsafe { Header::from_rom_unchecked(self.rom()) }
+        let rom = self.rom();
+        let offset = match self {
+            Cart::MMM01(_) => Header::mmm01_offset(rom).unwrap_or(0),
+            _ => 0,
+        };
+        unsafe { Header::from_rom_unchecked(&rom[offset..]) }
+    }
+
+    /// 震动马达是否开启, 没有马达的卡带始终为`false`
+    pub fn rumble(&self) -> bool {
+        matches!(self, Cart::MBC5(MBC5 { rumble: true, .. }))
+    }
+
+    /// 设置MBC7加速度计的倾斜, 其他卡带忽略
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        if let Cart::MBC7(c) = self {
+            c.set_tilt(x, y)
+        }
     }
 
     pub fn update_rtc(&mut self, timestamp: i64) {
-        if let Cart::MBC3(MBC3 { rtc: Some(rtc), .. }) = self {
+        if let Some(rtc) = self.rtc_mut() {
             rtc.update(timestamp)
         }
     }
@@ -74,6 +279,11 @@ impl MemoryRegion for Cart {
             Cart::MBC1(c) => c.read(addr),
             Cart::MBC2(c) => c.read(addr),
             Cart::MBC3(c) => c.read(addr),
+            Cart::MBC5(c) => c.read(addr),
+            Cart::MMM01(c) => c.read(addr),
+            Cart::HuC1(c) => c.read(addr),
+            Cart::HuC3(c) => c.read(addr),
+            Cart::MBC7(c) => c.read(addr),
         }
     }
 
@@ -83,6 +293,11 @@ impl MemoryRegion for Cart {
             Cart::MBC1(c) => c.write(addr, data),
             Cart::MBC2(c) => c.write(addr, data),
             Cart::MBC3(c) => c.write(addr, data),
+            Cart::MBC5(c) => c.write(addr, data),
+            Cart::MMM01(c) => c.write(addr, data),
+            Cart::HuC1(c) => c.write(addr, data),
+            Cart::HuC3(c) => c.write(addr, data),
+            Cart::MBC7(c) => c.write(addr, data),
         }
     }
 }
@@ -128,3 +343,346 @@ mod tsify_derive {
 }
 
 pub use tsify_derive::{CartInfo, LoadCartResult};
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::fs;
+
+    use super::{mbc::rtc::RTC_SAVE_SIZE, Cart, RAM_BANK_SIZE, ROM_BANK_SIZE};
+    use crate::{dev::MemoryRegion, error::InvalidSaveSize};
+
+    /// 重新计算`rom`开头的卡带头校验和
+    pub(crate) fn fix_checksum(rom: &mut [u8]) {
+        rom[0x14D] = rom[0x134..0x14D]
+            .iter()
+            .fold(0u8, |x, &b| x.wrapping_sub(b).wrapping_sub(1));
+    }
+
+    /// 由dmg-acid2的卡带头构造`banks`个bank的ROM, 每个bank的第一个字节为bank号
+    pub(crate) fn synth_rom(cart_type: u8, ram_size: u8, banks: usize) -> Vec<u8> {
+        let header = fs::read("../public/roms/dmg-acid2.gb").unwrap();
+        let mut rom = vec![0; banks * ROM_BANK_SIZE];
+        rom[0x100..0x150].copy_from_slice(&header[0x100..0x150]);
+        rom[0x147] = cart_type;
+        rom[0x148] = (banks.trailing_zeros() - 1) as u8;
+        rom[0x149] = ram_size;
+        fix_checksum(&mut rom);
+        for (bank, chunk) in rom.chunks_mut(ROM_BANK_SIZE).enumerate() {
+            chunk[0] = bank as u8;
+        }
+        rom
+    }
+
+    fn synth_cart(cart_type: u8, ram_size: u8, banks: usize) -> Cart {
+        Cart::new(synth_rom(cart_type, ram_size, banks).into_boxed_slice(), 0).unwrap()
+    }
+
+    #[test]
+    fn test_sav() {
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        let mut cart = Cart::new(rom.clone().into_boxed_slice(), 0).unwrap();
+        cart.sram_mut()[0x2000] = 0x42;
+        let sav = cart.export_sav().unwrap();
+        assert_eq!(sav.len(), 32 * 1024);
+
+        let mut cart = Cart::new(rom.into_boxed_slice(), 0).unwrap();
+        cart.import_sav(&sav).unwrap();
+        assert_eq!(cart.sram()[0x2000], 0x42);
+        assert_eq!(
+            *cart.import_sav(&sav[1..]).unwrap_err(),
+            InvalidSaveSize {
+                expected: sav.len(),
+                actual: sav.len() - 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_rom_size() {
+        // 2MB, 头部声明32KB
+        let rom = fs::read("../public/roms/mbc1_rom_banks.gb").unwrap();
+        let mut cart = Cart::new(rom.into_boxed_slice(), 0).unwrap();
+        cart.write(0x2000, 0x1F);
+        cart.write(0x4000, 0x03);
+        assert_eq!(cart.rom_bank(0x4000), 0x7F);
+        // 不足整数个bank, 末尾补齐0xFF
+        let rom = fs::read("../public/roms/mbctest.gb").unwrap();
+        let len = rom.len();
+        let cart = Cart::new(rom.into_boxed_slice(), 0).unwrap();
+        assert_eq!(cart.rom().len(), len.next_multiple_of(ROM_BANK_SIZE));
+        assert!(cart.rom()[len..].iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn test_mbc5() {
+        // MBC5+RAM+BATTERY, 8MB ROM, 32KB RAM
+        let mut cart = synth_cart(0x1B, 0x03, 512);
+        assert!(matches!(cart, Cart::MBC5(_)));
+        cart.write(0x2000, 0x12);
+        cart.write(0x3000, 0x01);
+        assert_eq!(cart.rom_bank(0x4000), 0x112);
+        assert_eq!(cart.read(0x4000), 0x12);
+        // 0x4000-0x7FFF可以映射bank 0
+        cart.write(0x3000, 0x00);
+        cart.write(0x2000, 0x00);
+        assert_eq!(cart.read(0x4000), cart.read(0x0000));
+
+        cart.write(0x0000, 0x0A);
+        cart.write(0x4000, 0x03);
+        cart.write(0xA000, 0x42);
+        assert_eq!(cart.sram()[3 * RAM_BANK_SIZE], 0x42);
+        assert!(!cart.rumble());
+
+        // MBC5+RUMBLE+RAM+BATTERY, bit 3控制马达
+        let mut cart = synth_cart(0x1E, 0x03, 512);
+        cart.write(0x0000, 0x0A);
+        cart.write(0x4000, 0x0B);
+        assert!(cart.rumble());
+        cart.write(0xA000, 0x42);
+        assert_eq!(cart.sram()[3 * RAM_BANK_SIZE], 0x42);
+        cart.write(0x4000, 0x03);
+        assert!(!cart.rumble());
+    }
+
+    /// 1MB的MBC1卡带, 合集在bank 0x10处也有卡带头
+    fn mbc1_1mb(multicart: bool) -> Cart {
+        let mut rom = synth_rom(0x01, 0x00, 64);
+        if multicart {
+            let game = 0x10 * ROM_BANK_SIZE;
+            rom.copy_within(0x100..0x150, game + 0x100);
+        }
+        Cart::new(rom.into_boxed_slice(), 0).unwrap()
+    }
+
+    #[test]
+    fn test_mbc1_ram() {
+        // 没有RAM时不能启用, 读到0xFF
+        let mut cart = synth_cart(0x01, 0x00, 4);
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x42);
+        assert_eq!(cart.read(0xA000), 0xFF);
+        assert!(cart.sram().is_empty());
+
+        // MBC1+RAM+BATTERY, 32KB RAM
+        let mut cart = synth_cart(0x03, 0x03, 4);
+        cart.write(0x0000, 0x0A);
+        cart.write(0x6000, 0x01);
+        cart.write(0x4000, 0x02);
+        cart.write(0xA000, 0x42);
+        assert_eq!(cart.read(0xA000), 0x42);
+        assert_eq!(cart.sram()[2 * RAM_BANK_SIZE], 0x42);
+    }
+
+    #[test]
+    fn test_mbc1_multicart() {
+        let mut cart = mbc1_1mb(true);
+        assert!(matches!(&cart, Cart::MBC1(c) if c.multicart));
+        cart.write(0x4000, 0x01);
+        cart.write(0x2000, 0x02);
+        assert_eq!(cart.read(0x4000), 0x12);
+        // 第一个寄存器的bit 4不连接, 写入0x10时映射每个游戏的bank 0
+        cart.write(0x2000, 0x10);
+        assert_eq!(cart.read(0x4000), 0x10);
+        cart.write(0x6000, 0x01);
+        cart.write(0x4000, 0x03);
+        assert_eq!(cart.read(0x0000), 0x30);
+        assert_eq!(cart.rom_bank(0x0000), 0x30);
+
+        let mut cart = mbc1_1mb(false);
+        assert!(matches!(&cart, Cart::MBC1(c) if !c.multicart));
+        cart.write(0x4000, 0x01);
+        cart.write(0x2000, 0x02);
+        assert_eq!(cart.read(0x4000), 0x22);
+        assert_eq!(cart.read(0x0000), 0x00);
+    }
+
+    /// `banks`个bank的MMM01+RAM+BATTERY合集, 32KB RAM, 菜单和卡带头位于最后两个bank
+    fn mmm01(banks: usize) -> Cart {
+        let mut rom = synth_rom(0x0D, 0x03, banks);
+        let menu = (banks - 2) * ROM_BANK_SIZE;
+        rom.copy_within(0x100..0x150, menu + 0x100);
+        Cart::new(rom.into_boxed_slice(), 0).unwrap()
+    }
+
+    #[test]
+    fn test_mmm01() {
+        let mut cart = mmm01(8);
+        assert!(matches!(cart, Cart::MMM01(_)));
+        assert!(cart.header().has_battery());
+        assert_eq!(cart.sram().len(), 4 * RAM_BANK_SIZE);
+        // 菜单映射到0x0000-0x7FFF
+        assert_eq!(cart.read(0x0000), 6);
+        assert_eq!(cart.read(0x4000), 7);
+
+        // 选择位于bank 2-3的游戏: 固定ROM bank的bit 1-4和RAM bank的bit 1, 然后进入映射模式
+        cart.write(0x2000, 0x02);
+        cart.write(0x4000, 0x02);
+        cart.write(0x6000, 0x0F << 2);
+        cart.write(0x0000, 0x40 | 0x20);
+        assert_eq!(cart.read(0x0000), 2);
+        assert_eq!(cart.read(0x4000), 3);
+        assert_eq!(cart.rom_bank(0x0000), 2);
+
+        // 游戏只能修改未固定的位
+        cart.write(0x2000, 0x1F);
+        assert_eq!(cart.read(0x4000), 3);
+        cart.write(0x2000, 0x00);
+        assert_eq!(cart.read(0x4000), 3);
+        // 映射后不能回到菜单, 也不能修改基址和掩码
+        cart.write(0x0000, 0x0A);
+        cart.write(0x6000, 0x00);
+        cart.write(0x2000, 0x01);
+        assert_eq!(cart.read(0x0000), 2);
+        assert_eq!(cart.read(0x4000), 3);
+
+        cart.write(0x4000, 0x01);
+        cart.write(0xA000, 0x42);
+        assert_eq!(cart.sram()[3 * RAM_BANK_SIZE], 0x42);
+
+        // 复用模式下RAM bank的低位作为ROM bank的bit 5-6, 模式0时0x0000-0x3FFF不使用这些位.
+        // 可写的低位为0时0x4000-0x7FFF映射下一个bank
+        let mut cart = mmm01(128);
+        cart.write(0x2000, 0x02);
+        cart.write(0x4000, 0x01);
+        cart.write(0x6000, 0x40 | 0x0F << 2);
+        cart.write(0x0000, 0x40);
+        assert_eq!(cart.read(0x0000), 0x02);
+        assert_eq!(cart.read(0x4000), 0x23);
+        cart.write(0x2000, 0x01);
+        assert_eq!(cart.read(0x4000), 0x23);
+    }
+
+    #[test]
+    fn test_huc1() {
+        let mut cart = synth_cart(0xFF, 0x03, 64);
+        assert!(matches!(cart, Cart::HuC1(_)));
+        assert!(cart.header().has_battery());
+        cart.write(0x2000, 0x3F);
+        assert_eq!(cart.read(0x4000), 0x3F);
+        cart.write(0x4000, 0x02);
+        cart.write(0xA000, 0x42);
+        assert_eq!(cart.sram()[2 * RAM_BANK_SIZE], 0x42);
+        // 红外模式: 读到没有光, 写入不影响RAM
+        cart.write(0x0000, 0x0E);
+        assert_eq!(cart.read(0xA000), 0xC0);
+        cart.write(0xA000, 0x01);
+        cart.write(0x0000, 0x00);
+        assert_eq!(cart.read(0xA000), 0x42);
+    }
+
+    #[test]
+    fn test_huc3() {
+        const MIN: i64 = 60_000;
+        let mut cart = synth_cart(0xFE, 0x03, 64);
+        assert!(matches!(cart, Cart::HuC3(_)));
+        let command = |cart: &mut Cart, cmd: u8| {
+            cart.write(0x0000, 0x0B);
+            cart.write(0xA000, cmd);
+            cart.write(0x0000, 0x0D);
+            assert_eq!(cart.read(0xA000) & 0x01, 0x01);
+            cart.write(0xA000, 0xFE);
+            cart.write(0x0000, 0x0C);
+            cart.read(0xA000) & 0x0F
+        };
+        // 1天1小时30分
+        command(&mut cart, 0x40);
+        command(&mut cart, 0x50);
+        for nibble in [0xA, 0x5, 0x0, 0x1, 0x0, 0x0, 0x0] {
+            command(&mut cart, 0x30 | nibble);
+        }
+        command(&mut cart, 0x61);
+        cart.update_rtc(24 * 60 * MIN + 5 * MIN);
+
+        command(&mut cart, 0x60);
+        command(&mut cart, 0x40);
+        let nibbles: Vec<_> = (0..7).map(|_| command(&mut cart, 0x10)).collect();
+        assert_eq!(nibbles, [0xF, 0x5, 0x0, 0x2, 0x0, 0x0, 0x0]);
+
+        // RAM只在模式0x0A时可写
+        cart.write(0x0000, 0x00);
+        cart.write(0xA000, 0x42);
+        assert_eq!(cart.read(0xA000), 0x00);
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x42);
+        assert_eq!(cart.read(0xA000), 0x42);
+        let sav = cart.export_sav().unwrap();
+        assert_eq!(sav.len(), 4 * RAM_BANK_SIZE + RTC_SAVE_SIZE);
+    }
+
+    /// 在MBC7的EEPROM上移入`bits`的低`n`位, 返回每个时钟之后的DO
+    fn eeprom_shift(cart: &mut Cart, bits: u32, n: u32) -> u32 {
+        (0..n).rev().fold(0, |acc, i| {
+            let di = ((bits >> i & 1) as u8) << 1;
+            cart.write(0xA080, 0x80 | di);
+            cart.write(0xA080, 0xC0 | di);
+            acc << 1 | (cart.read(0xA080) & 0x01) as u32
+        })
+    }
+
+    #[test]
+    fn test_mbc7() {
+        let mut cart = synth_cart(0x22, 0x00, 64);
+        assert!(matches!(cart, Cart::MBC7(_)));
+        cart.write(0x2000, 0x3F);
+        assert_eq!(cart.read(0x4000), 0x3F);
+        // 两个启用寄存器都打开前读不到寄存器
+        cart.write(0x0000, 0x0A);
+        assert_eq!(cart.read(0xA020), 0xFF);
+        cart.write(0x4000, 0x40);
+
+        cart.set_tilt(0.5, -1.0);
+        cart.write(0xA000, 0x55);
+        cart.write(0xA010, 0xAA);
+        let x = cart.read(0xA020) as u16 | (cart.read(0xA030) as u16) << 8;
+        let y = cart.read(0xA040) as u16 | (cart.read(0xA050) as u16) << 8;
+        assert_eq!((x, y), (0x81D0 - 0x38, 0x81D0 - 0x70));
+        // 未擦除时不重新锁存
+        cart.set_tilt(0.0, 0.0);
+        cart.write(0xA010, 0xAA);
+        assert_eq!(cart.read(0xA020), (0x81D0u16 - 0x38) as u8);
+
+        // 命令为起始位, 2位操作码和8位地址. 未使能写入时WRITE无效
+        let write = |cart: &mut Cart| {
+            eeprom_shift(cart, 0x505, 11);
+            eeprom_shift(cart, 0xBEEF, 16);
+            cart.write(0xA080, 0x00);
+        };
+        write(&mut cart);
+        assert_eq!(&cart.sram()[10..12], [0xFF, 0xFF]);
+        // EWEN
+        eeprom_shift(&mut cart, 0x4C0, 11);
+        cart.write(0xA080, 0x00);
+        write(&mut cart);
+        assert_eq!(&cart.sram()[10..12], [0xEF, 0xBE]);
+        // READ: 地址之后先输出一个0, 再输出16位数据
+        assert_eq!(eeprom_shift(&mut cart, 0x605, 11) & 1, 0);
+        assert_eq!(eeprom_shift(&mut cart, 0, 16), 0xBEEF);
+        cart.write(0xA080, 0x00);
+
+        let sav = cart.export_sav().unwrap();
+        assert_eq!(sav.len(), 256);
+    }
+
+    #[test]
+    fn test_sav_rtc() {
+        // 1天1小时1分1秒
+        const ELAPSED: i64 = 90_061_000;
+        let rom = fs::read("../public/roms/rtc3test.gb").unwrap();
+        let mut cart = Cart::new(rom.clone().into_boxed_slice(), 0).unwrap();
+        cart.update_rtc(ELAPSED);
+        let sav = cart.export_sav().unwrap();
+        assert_eq!(sav.len(), RTC_SAVE_SIZE);
+
+        let mut cart = Cart::new(rom.into_boxed_slice(), ELAPSED + 10_000).unwrap();
+        cart.import_sav(&sav).unwrap();
+        cart.update_rtc(ELAPSED + 1000);
+        cart.write(0x0000, 0x0A);
+        let regs: Vec<_> = (0x08..=0x0B)
+            .map(|reg| {
+                cart.write(0x4000, reg);
+                cart.read(0xA000)
+            })
+            .collect();
+        assert_eq!(regs, [2, 1, 1, 1]);
+    }
+}
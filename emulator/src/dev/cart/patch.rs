@@ -0,0 +1,379 @@
+//! ROM补丁, 按文件头识别格式:
+//!
+//! - IPS(`PATCH`): 按偏移覆盖数据, 支持RLE和末尾的截断长度, 没有校验
+//! - UPS(`UPS1`): 与原ROM异或, 末尾有原ROM, 目标ROM和补丁自身的CRC32
+//! - BPS(`BPS1`): 由原ROM和目标ROM中的片段拼接, 校验同UPS
+//!
+//! ref https://zerosoft.zophar.net/ips.php
+//! ref https://www.romhacking.net/documents/746/
+
+use std::convert::TryInto;
+
+use crate::error::{EmuErr, EmuResult, InvalidPatch, PatchCrcMismatch};
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: usize = 0x454F46;
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+/// UPS和BPS末尾的三个CRC32
+const FOOTER_SIZE: usize = 12;
+/// 目标ROM的最大长度, 与MBC5能寻址的ROM相同, 分配内存前检查
+const MAX_TARGET_SIZE: usize = 8 * 1024 * 1024;
+
+fn invalid<T>(msg: &str) -> EmuResult<T> {
+    EmuErr(InvalidPatch {
+        msg: msg.to_string(),
+    })
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> EmuResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .map_or_else(|| invalid("unexpected end of patch"), Ok)?;
+        match self.data.get(self.pos..end) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            }
+            None => invalid("unexpected end of patch"),
+        }
+    }
+
+    fn byte(&mut self) -> EmuResult<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    fn be(&mut self, len: usize) -> EmuResult<usize> {
+        let bytes = self.bytes(len)?;
+        Ok(bytes.iter().fold(0, |acc, &b| acc << 8 | b as usize))
+    }
+
+    /// UPS/BPS的变长整数, 每字节7位, 最高位为1时结束
+    fn varint(&mut self) -> EmuResult<usize> {
+        let overflow = || invalid("integer overflow");
+        let mut value = 0usize;
+        let mut shift = 1usize;
+        loop {
+            let x = self.byte()?;
+            value = (x as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|v| v.checked_add(value))
+                .map_or_else(overflow, Ok)?;
+            if x & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_mul(0x80).map_or_else(overflow, Ok)?;
+            value = value.checked_add(shift).map_or_else(overflow, Ok)?;
+        }
+    }
+}
+
+/// 对`rom`应用补丁, 返回修改后的ROM
+pub fn apply(rom: &[u8], patch: &[u8]) -> EmuResult<Vec<u8>> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(UPS_MAGIC) {
+        apply_ups(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        invalid("unknown patch format")
+    }
+}
+
+fn apply_ips(rom: &[u8], patch: &[u8]) -> EmuResult<Vec<u8>> {
+    let mut out = rom.to_vec();
+    let mut r = Reader {
+        data: patch,
+        pos: IPS_MAGIC.len(),
+    };
+    loop {
+        let offset = r.be(3)?;
+        if offset == IPS_EOF {
+            break;
+        }
+        let (len, fill) = match r.be(2)? {
+            0 => (r.be(2)?, Some(r.byte()?)),
+            len => (len, None),
+        };
+        if out.len() < offset + len {
+            out.resize(offset + len, 0);
+        }
+        match fill {
+            Some(fill) => out[offset..offset + len].fill(fill),
+            None => out[offset..offset + len].copy_from_slice(r.bytes(len)?),
+        }
+    }
+    // EOF之后可选的3字节截断长度
+    if r.pos + 3 == patch.len() {
+        out.truncate(r.be(3)?);
+    }
+    Ok(out)
+}
+
+/// 读取UPS/BPS头部的原ROM和目标ROM长度, 返回目标ROM长度
+fn read_sizes(rom: &[u8], r: &mut Reader) -> EmuResult<usize> {
+    let source_size = r.varint()?;
+    let target_size = r.varint()?;
+    if source_size != rom.len() {
+        return invalid("source size mismatch");
+    }
+    if target_size > MAX_TARGET_SIZE {
+        return invalid("target size too large");
+    }
+    Ok(target_size)
+}
+
+/// 校验补丁和原ROM, 返回UPS/BPS正文的读取器和目标ROM的CRC32
+fn open_checked<'a>(rom: &[u8], patch: &'a [u8]) -> EmuResult<(Reader<'a>, u32)> {
+    if patch.len() < UPS_MAGIC.len() + FOOTER_SIZE {
+        return invalid("unexpected end of patch");
+    }
+    let body_end = patch.len() - FOOTER_SIZE;
+    let crc = |at: usize| u32::from_le_bytes(patch[at..at + 4].try_into().unwrap());
+    let (source_crc, target_crc, patch_crc) = (crc(body_end), crc(body_end + 4), crc(body_end + 8));
+    check_crc("patch", patch_crc, &patch[..patch.len() - 4])?;
+    check_crc("source", source_crc, rom)?;
+    let reader = Reader {
+        data: &patch[..body_end],
+        pos: UPS_MAGIC.len(),
+    };
+    Ok((reader, target_crc))
+}
+
+fn check_crc(kind: &'static str, expected: u32, data: &[u8]) -> EmuResult {
+    let actual = crc32fast::hash(data);
+    if actual == expected {
+        Ok(())
+    } else {
+        EmuErr(PatchCrcMismatch {
+            kind,
+            expected,
+            actual,
+        })
+    }
+}
+
+fn apply_ups(rom: &[u8], patch: &[u8]) -> EmuResult<Vec<u8>> {
+    let (mut r, target_crc) = open_checked(rom, patch)?;
+    let target_size = read_sizes(rom, &mut r)?;
+    let mut out = rom.to_vec();
+    out.resize(target_size, 0);
+    let mut pos = 0;
+    while r.pos < r.data.len() {
+        pos += r.varint()?;
+        // 异或数据以0结束, 结束符对应一个未修改的字节
+        loop {
+            let x = r.byte()?;
+            if x == 0 {
+                pos += 1;
+                break;
+            }
+            match out.get_mut(pos) {
+                Some(b) => *b ^= x,
+                None => return invalid("write beyond target size"),
+            }
+            pos += 1;
+        }
+    }
+    check_crc("target", target_crc, &out)?;
+    Ok(out)
+}
+
+fn apply_bps(rom: &[u8], patch: &[u8]) -> EmuResult<Vec<u8>> {
+    let (mut r, target_crc) = open_checked(rom, patch)?;
+    let target_size = read_sizes(rom, &mut r)?;
+    let metadata_size = r.varint()?;
+    r.bytes(metadata_size)?;
+
+    let mut out = Vec::with_capacity(target_size);
+    let (mut source_offset, mut target_offset) = (0usize, 0usize);
+    let relative = |offset: &mut usize, r: &mut Reader| -> EmuResult {
+        let d = r.varint()?;
+        let delta = d >> 1;
+        *offset = if d & 1 == 0 {
+            offset.checked_add(delta)
+        } else {
+            offset.checked_sub(delta)
+        }
+        .map_or_else(|| invalid("copy out of range"), Ok)?;
+        Ok(())
+    };
+    while r.pos < r.data.len() {
+        let data = r.varint()?;
+        let len = (data >> 2) + 1;
+        if out.len() + len > target_size {
+            return invalid("write beyond target size");
+        }
+        match data & 3 {
+            // SourceRead: 复制原ROM中同一位置的数据
+            0 => match rom.get(out.len()..out.len() + len) {
+                Some(src) => out.extend_from_slice(src),
+                None => return invalid("copy out of range"),
+            },
+            // TargetRead: 补丁中的数据
+            1 => out.extend_from_slice(r.bytes(len)?),
+            // SourceCopy: 原ROM中任意位置的数据
+            2 => {
+                relative(&mut source_offset, &mut r)?;
+                match source_offset
+                    .checked_add(len)
+                    .and_then(|end| rom.get(source_offset..end))
+                {
+                    Some(src) => out.extend_from_slice(src),
+                    None => return invalid("copy out of range"),
+                }
+                source_offset += len;
+            }
+            // TargetCopy: 已输出的数据, 可以与正在输出的部分重叠, 逐字节复制
+            _ => {
+                relative(&mut target_offset, &mut r)?;
+                for _ in 0..len {
+                    match out.get(target_offset) {
+                        Some(&b) => out.push(b),
+                        None => return invalid("copy out of range"),
+                    }
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+    if out.len() != target_size {
+        return invalid("target size mismatch");
+    }
+    check_crc("target", target_crc, &out)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::apply;
+    use crate::error::{InvalidPatch, PatchCrcMismatch};
+
+    const SOURCE: &[u8] = b"hello world";
+
+    fn varint(mut n: usize, out: &mut Vec<u8>) {
+        loop {
+            let x = (n & 0x7F) as u8;
+            n >>= 7;
+            if n == 0 {
+                out.push(0x80 | x);
+                return;
+            }
+            out.push(x);
+            n -= 1;
+        }
+    }
+
+    /// 附加原ROM, 目标ROM和补丁的CRC32
+    fn finish(mut patch: Vec<u8>, target: &[u8]) -> Vec<u8> {
+        patch.extend_from_slice(&crc32fast::hash(SOURCE).to_le_bytes());
+        patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+        patch.extend_from_slice(&crc32fast::hash(&patch).to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn test_ips() {
+        let mut patch = b"PATCH".to_vec();
+        // 0x0006处写入"W", 0x000B处RLE写入3个'!'
+        patch.extend_from_slice(&[0x00, 0x00, 0x06, 0x00, 0x01, b'W']);
+        patch.extend_from_slice(&[0x00, 0x00, 0x0B, 0x00, 0x00, 0x00, 0x03, b'!']);
+        patch.extend_from_slice(b"EOF");
+        assert_eq!(apply(SOURCE, &patch).unwrap(), b"hello World!!!");
+        patch.extend_from_slice(&[0x00, 0x00, 0x0C]);
+        assert_eq!(apply(SOURCE, &patch).unwrap(), b"hello World!");
+        assert!(matches!(
+            *apply(SOURCE, &patch[..12]).unwrap_err(),
+            InvalidPatch { .. }
+        ));
+    }
+
+    #[test]
+    fn test_ups() {
+        let target = b"hello World!";
+        let mut patch = b"UPS1".to_vec();
+        varint(SOURCE.len(), &mut patch);
+        varint(target.len(), &mut patch);
+        varint(6, &mut patch);
+        patch.extend_from_slice(&[b'w' ^ b'W', 0]);
+        varint(3, &mut patch);
+        patch.extend_from_slice(&[b'!', 0]);
+        let patch = finish(patch, target);
+        assert_eq!(apply(SOURCE, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn test_bps() {
+        let target = b"hello there world!!!";
+        let mut patch = b"BPS1".to_vec();
+        varint(SOURCE.len(), &mut patch);
+        varint(target.len(), &mut patch);
+        varint(0, &mut patch);
+        // SourceRead "hello "
+        varint((6 - 1) << 2, &mut patch);
+        // TargetRead "there "
+        varint((6 - 1) << 2 | 1, &mut patch);
+        patch.extend_from_slice(b"there ");
+        // SourceCopy "world"
+        varint((5 - 1) << 2 | 2, &mut patch);
+        varint(6 << 1, &mut patch);
+        // TargetRead "!", TargetCopy "!!"
+        varint(1, &mut patch);
+        patch.push(b'!');
+        varint((2 - 1) << 2 | 3, &mut patch);
+        varint(17 << 1, &mut patch);
+        let patch = finish(patch, target);
+        assert_eq!(apply(SOURCE, &patch).unwrap(), target);
+
+        assert!(matches!(
+            *apply(b"hello World", &patch).unwrap_err(),
+            PatchCrcMismatch { kind: "source", .. }
+        ));
+        let mut corrupted = patch.clone();
+        corrupted[10] ^= 1;
+        assert!(matches!(
+            *apply(SOURCE, &corrupted).unwrap_err(),
+            PatchCrcMismatch { kind: "patch", .. }
+        ));
+    }
+
+    #[test]
+    fn test_malformed() {
+        let invalid = |patch: Vec<u8>, expected: &str| {
+            let patch = finish(patch, b"");
+            assert!(matches!(
+                &*apply(SOURCE, &patch).unwrap_err(),
+                InvalidPatch { msg } if msg == expected
+            ));
+        };
+        // 目标ROM过大, 分配内存前拒绝
+        for magic in [b"UPS1", b"BPS1"].iter() {
+            let mut patch = magic.to_vec();
+            varint(SOURCE.len(), &mut patch);
+            varint(usize::MAX >> 8, &mut patch);
+            invalid(patch, "target size too large");
+        }
+        // 元数据长度溢出
+        let mut patch = b"BPS1".to_vec();
+        varint(SOURCE.len(), &mut patch);
+        varint(4, &mut patch);
+        varint(usize::MAX - 4, &mut patch);
+        invalid(patch, "unexpected end of patch");
+        // SourceCopy的偏移加长度溢出
+        let mut patch = b"BPS1".to_vec();
+        varint(SOURCE.len(), &mut patch);
+        varint(4, &mut patch);
+        varint(0, &mut patch);
+        varint((4 - 1) << 2 | 2, &mut patch);
+        varint((usize::MAX >> 8) << 1, &mut patch);
+        invalid(patch, "copy out of range");
+    }
+}
@@ -0,0 +1,240 @@
+//! 金手指, 支持两种格式:
+//!
+//! - Game Genie: `ABC-DEF-GHI`或`ABC-DEF`, 替换CPU从ROM读到的数据. 9位的代码带有比较值,
+//!   只在原数据与之相同时替换, 避免改动映射到同一地址的其他bank
+//! - GameShark: `ttvvaaaa`, 每帧向RAM(0xA000-0xDFFF)写入数据, `tt`为外部RAM的bank
+//!
+//! ref https://gbdev.io/pandocs/Shark_Cheats.html
+
+use crate::{
+    error::{EmuErr, EmuResult, InvalidCheat},
+    types::{Addr, Word},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatKind {
+    GameGenie {
+        addr: Addr,
+        data: Word,
+        compare: Option<Word>,
+    },
+    GameShark {
+        bank: u8,
+        addr: Addr,
+        data: Word,
+    },
+}
+
+impl CheatKind {
+    pub fn parse(code: &str) -> EmuResult<Self> {
+        let invalid = || {
+            EmuErr(InvalidCheat {
+                code: code.to_string(),
+            })
+        };
+        let digits: Option<Vec<_>> = code
+            .trim()
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_digit(16).map(|d| d as u16))
+            .collect();
+        let Some(d) = digits else {
+            return invalid();
+        };
+        let kind = match d.len() {
+            // AB: 新数据, FCDE ^ 0xF000: 地址, GI: 比较值异或0xBA后循环左移2位, H未使用
+            6 | 9 => {
+                let addr = (d[5] << 12 | d[2] << 8 | d[3] << 4 | d[4]) ^ 0xF000;
+                let compare =
+                    (d.len() == 9).then(|| ((d[6] << 4 | d[8]) as Word).rotate_right(2) ^ 0xBA);
+                CheatKind::GameGenie {
+                    addr,
+                    data: (d[0] << 4 | d[1]) as Word,
+                    compare,
+                }
+            }
+            // tt: bank, vv: 数据, aaaa: 小端地址
+            8 if !code.contains('-') => CheatKind::GameShark {
+                bank: (d[0] << 4 | d[1]) as u8,
+                data: (d[2] << 4 | d[3]) as Word,
+                addr: d[6] << 12 | d[7] << 8 | d[4] << 4 | d[5],
+            },
+            _ => return invalid(),
+        };
+        match kind {
+            CheatKind::GameGenie { addr, .. } if addr >= 0x8000 => invalid(),
+            CheatKind::GameShark { addr, .. } if !(0xA000..=0xDFFF).contains(&addr) => invalid(),
+            _ => Ok(kind),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cheat {
+    pub id: u32,
+    /// 用户输入的代码
+    pub code: String,
+    pub enabled: bool,
+    pub kind: CheatKind,
+}
+
+/// 按添加顺序匹配, 同一地址有多个Game Genie代码时先添加的优先
+#[derive(Default)]
+pub struct Cheats {
+    next_id: u32,
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    /// 添加并启用, 返回编号
+    pub fn add(&mut self, code: &str) -> EmuResult<u32> {
+        let kind = CheatKind::parse(code)?;
+        self.next_id += 1;
+        self.cheats.push(Cheat {
+            id: self.next_id,
+            code: code.trim().to_uppercase(),
+            enabled: true,
+            kind,
+        });
+        Ok(self.next_id)
+    }
+
+    /// 返回是否存在
+    pub fn remove(&mut self, id: u32) -> bool {
+        let len = self.cheats.len();
+        self.cheats.retain(|cheat| cheat.id != id);
+        len != self.cheats.len()
+    }
+
+    /// 返回是否存在
+    pub fn set_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        match self.cheats.iter_mut().find(|cheat| cheat.id == id) {
+            Some(cheat) => {
+                cheat.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cheats.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cheat> {
+        self.cheats.iter()
+    }
+
+    fn enabled(&self) -> impl Iterator<Item = CheatKind> + '_ {
+        self.cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .map(|cheat| cheat.kind)
+    }
+
+    /// CPU从ROM的`addr`处读到`data`时, 经Game Genie替换后的数据
+    pub fn patch_rom(&self, addr: Addr, data: Word) -> Word {
+        self.enabled()
+            .find_map(|kind| match kind {
+                CheatKind::GameGenie {
+                    addr: a,
+                    data: new,
+                    compare,
+                } if a == addr && compare.is_none_or(|c| c == data) => Some(new),
+                _ => None,
+            })
+            .unwrap_or(data)
+    }
+
+    /// 每帧需要写入的(bank, 地址, 数据)
+    pub fn ram_writes(&self) -> impl Iterator<Item = (u8, Addr, Word)> + '_ {
+        self.enabled().filter_map(|kind| match kind {
+            CheatKind::GameShark { bank, addr, data } => Some((bank, addr, data)),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::CheatKind;
+    use crate::{
+        dev::cart::RAM_BANK_SIZE,
+        emulator::{HeadlessEmulator, CYCLES_PER_FRAME},
+    };
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            CheatKind::parse("3E1-01F-EA5").unwrap(),
+            CheatKind::GameGenie {
+                addr: 0x0101,
+                data: 0x3E,
+                compare: Some(0xC3)
+            }
+        );
+        assert_eq!(
+            CheatKind::parse("3E1-01F").unwrap(),
+            CheatKind::GameGenie {
+                addr: 0x0101,
+                data: 0x3E,
+                compare: None
+            }
+        );
+        assert_eq!(
+            CheatKind::parse("014238D3").unwrap(),
+            CheatKind::GameShark {
+                bank: 0x01,
+                addr: 0xD338,
+                data: 0x42
+            }
+        );
+        // 地址不在ROM或RAM中
+        assert!(CheatKind::parse("3E1-017").is_err());
+        assert!(CheatKind::parse("01420080").is_err());
+        assert!(CheatKind::parse("0142-38D3").is_err());
+        assert!(CheatKind::parse("XYZ-01F").is_err());
+    }
+
+    #[test]
+    fn test_cheats() {
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        let cheats = emulator.cheats_mut();
+        let genie = cheats.add("3e1-01f-ea5").unwrap();
+        let wrong = cheats.add("3F1-00F-0A0").unwrap();
+        let shark = cheats.add("014200C0").unwrap();
+        cheats.add("025500A0").unwrap();
+        assert_eq!(cheats.iter().next().unwrap().code, "3E1-01F-EA5");
+
+        let bus = &mut emulator.core_mut().bus;
+        assert_eq!(bus.read(0x0101).unwrap(), 0x3E);
+        // 比较值不符时不替换
+        assert_eq!(bus.read(0x0100).unwrap(), 0x00);
+        assert!(bus.cheats.set_enabled(genie, false));
+        assert_eq!(bus.read(0x0101).unwrap(), 0xC3);
+        assert!(bus.cheats.remove(wrong));
+        assert!(!bus.cheats.remove(wrong));
+
+        // 启动时会清空WRAM, 先跳过
+        emulator.run_cycles(CYCLES_PER_FRAME * 60).unwrap();
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.peek(0xC000), 0x42);
+        let sram = emulator.core().bus.cart.as_ref().unwrap().sram();
+        assert_eq!(sram[2 * RAM_BANK_SIZE], 0x55);
+
+        // 恢复状态后仍然生效
+        let save = emulator.save(0).unwrap();
+        emulator.load(&save).unwrap();
+        emulator.poke(0xC000, 0);
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.peek(0xC000), 0x42);
+        assert!(emulator.cheats_mut().set_enabled(shark, false));
+        emulator.poke(0xC000, 0);
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.peek(0xC000), 0);
+    }
+}
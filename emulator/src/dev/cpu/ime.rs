@@ -1,21 +1,13 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct InterruptMasterEnableRegsiter {
     enabled: bool,
     enabling_countdown: u8,
 }
 
-impl Default for InterruptMasterEnableRegsiter {
-    fn default() -> Self {
-        Self {
-            enabled: false,
-            enabling_countdown: 0,
-        }
-    }
-}
-
 impl InterruptMasterEnableRegsiter {
+    #[allow(unused)]
     pub fn new() -> Self {
         Default::default()
     }
@@ -285,7 +285,7 @@ impl CPU {
     }
 
     pub fn mnemonic(opcode: OpCode) -> &'static str {
-        const MNEMONICS: &[&'static str; 256] = &[
+        const MNEMONICS: &[&str; 256] = &[
             "0x00 NOP",
             "0x01 LD BC IMM16",
             "0x02 LD (BC) A",
@@ -543,7 +543,7 @@ impl CPU {
             "0xFE CP IMM8",
             "0xFF RST 0x0038",
         ];
-        *unsafe { MNEMONICS.get_unchecked(opcode as usize) }
+        unsafe { MNEMONICS.get_unchecked(opcode as usize) }
     }
 
     fn inst_0x00_nop(_: &mut CPU, _: &mut Bus) -> InstExecResult {
@@ -552,7 +552,7 @@ impl CPU {
 
     fn inst_illegal(&mut self, bus: &mut Bus) -> InstExecResult {
         let addr = self.pc() - 1;
-        let opcode = bus.read(addr)?;
+        let opcode = bus.fetch(addr)?;
         EmuErr(IllegalInstruction { opcode, addr })
     }
 
@@ -1013,7 +1013,7 @@ impl CPU {
     #[inline]
     fn read_word(&mut self, bus: &mut Bus) -> EmuResult<Word> {
         let pc = self.pc();
-        let res = bus.read(pc)?;
+        let res = bus.fetch(pc)?;
         self.pc_inc();
         Ok(res)
     }
@@ -1095,8 +1095,8 @@ impl CPU {
     #[inline]
     fn read_dword(&mut self, bus: &mut Bus) -> EmuResult<DWord> {
         let pc = self.pc();
-        let low = bus.read(pc)?;
-        let high = bus.read(pc + 1)?;
+        let low = bus.fetch(pc)?;
+        let high = bus.fetch(pc + 1)?;
         self.pc_inc_by(2);
         let ret = (high as DWord) << 8 | low as DWord;
         Ok(ret)
@@ -1,4 +1,5 @@
 use crate::{
+    debug::Frame,
     dev::{
         bus::Bus,
         cpu::{ime::InterruptMasterEnableRegsiter, inst::Inst, regs::Regs},
@@ -47,10 +48,33 @@ impl CPU {
             if let Some(int_entry) = bus.int_entry(ime) {
                 self.handle_int(bus, int_entry)
             } else {
+                let (pc, sp) = (self.pc(), self.sp());
+                // 调试器未启用时只有这一次判断
+                let active = bus.debugger.active();
+                let mut bank = None;
+                if active {
+                    if bus.debugger.check_breakpoint(self, || bus.rom_bank(pc)) {
+                        return Ok(0);
+                    }
+                    // 在执行前取得, 指令可能切换bank
+                    if bus.debugger.needs_bank() {
+                        bank = bus.rom_bank(pc);
+                    }
+                    if bus.debugger.tracer.enabled() {
+                        let pcmem = [0, 1, 2, 3].map(|i| bus.peek(pc.wrapping_add(i)));
+                        let debugger = &mut bus.debugger;
+                        let label = debugger.symbols.name(bank, pc);
+                        debugger.tracer.trace(self, pcmem, label);
+                    }
+                }
                 let opcode = self.fetch_opcode(bus)?;
                 self.pc_inc();
                 let inst = Self::decode_inst(opcode);
                 let cycles = self.exec_inst(bus, inst)?;
+                if active {
+                    bus.debugger.profile(bank, pc, cycles);
+                    self.track_call(bus, opcode, pc, sp);
+                }
                 self.ime.countdown();
                 Ok(cycles)
             }
@@ -58,6 +82,10 @@ impl CPU {
             if bus.has_int() {
                 self.halted = false;
             }
+            if bus.debugger.profiler.enabled() {
+                let pc = self.pc();
+                bus.debugger.profile(bus.rom_bank(pc), pc, 4);
+            }
             self.ime.countdown();
             Ok(4)
         }
@@ -65,15 +93,8 @@ impl CPU {
 
     pub fn dump(&self, bus: &Bus) -> CPUStateDump {
         let pc = self.pc();
-        let inst = bus
-            .read(pc)
-            .map(|op| Self::mnemonic(op))
-            .unwrap_or("UNKNOWN");
-        let three_words_at_pc = [
-            bus.read(pc).unwrap_or(0),
-            bus.read(pc + 1).unwrap_or(0),
-            bus.read(pc + 2).unwrap_or(0),
-        ];
+        let three_words_at_pc = [0, 1, 2].map(|i| bus.peek(pc.wrapping_add(i)));
+        let inst = Self::mnemonic(three_words_at_pc[0]);
         CPUStateDump {
             ime: self.ime.enabled(),
             halted: self.halted,
@@ -102,13 +123,51 @@ impl CPU {
 
     fn handle_int(&mut self, bus: &mut Bus, entry: Addr) -> EmuResult<ClockCycle> {
         self.ime.disable();
-        self.push_dword(bus, self.pc())?;
+        let pc = self.pc();
+        self.push_dword(bus, pc)?;
         self.jp(entry);
+        if bus.debugger.active() {
+            let target_bank = bus.rom_bank(entry);
+            bus.debugger.push_frame(Frame {
+                call_site: pc,
+                bank: bus.rom_bank(pc),
+                target: entry,
+                target_bank,
+                sp: self.sp(),
+                interrupt: true,
+            });
+            // 中断分派的周期计入中断处理函数
+            bus.debugger.profile(target_bank, entry, 20);
+        }
         Ok(20)
     }
 
+    /// 维护调试器的调用栈, 由SP的变化判断条件调用和返回是否发生
+    #[inline]
+    fn track_call(&self, bus: &mut Bus, opcode: OpCode, pc: Addr, sp: Addr) {
+        match opcode {
+            0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC | 0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7
+            | 0xFF
+                if self.sp() == sp.wrapping_sub(2) =>
+            {
+                bus.debugger.push_frame(Frame {
+                    call_site: pc,
+                    bank: bus.rom_bank(pc),
+                    target: self.pc(),
+                    target_bank: bus.rom_bank(self.pc()),
+                    sp: self.sp(),
+                    interrupt: false,
+                })
+            }
+            0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9 if self.sp() == sp.wrapping_add(2) => {
+                bus.debugger.pop_frames(self.sp())
+            }
+            _ => {}
+        }
+    }
+
     fn fetch_opcode(&self, bus: &mut Bus) -> EmuResult<OpCode> {
-        bus.read(self.pc())
+        bus.fetch(self.pc())
     }
 
     fn exec_inst(&mut self, bus: &mut Bus, inst: Inst) -> EmuResult<ClockCycle> {
@@ -34,8 +34,9 @@ pub const CARRY: Word = 4;
 pub struct Regs([DWord; 6]);
 
 impl Default for Regs {
+    /// 启动ROM结束后的状态: AF=01B0 BC=0013 DE=00D8 HL=014D
     fn default() -> Self {
-        Self([0x0001, 0x13FF, 0xC100, 0x0384, 0xFFFE, 0x0100])
+        Self([0x01B0, 0x0013, 0x00D8, 0x014D, 0xFFFE, 0x0100])
     }
 }
 
@@ -238,7 +239,7 @@ impl Regs {
     }
 
     #[inline]
-    pub fn zero_flag_mut(&mut self) -> BitProxy {
+    pub fn zero_flag_mut(&mut self) -> BitProxy<'_> {
         BitProxy::new(self.f_mut(), Self::ZERO_FLAG)
     }
 
@@ -248,7 +249,7 @@ impl Regs {
     }
 
     #[inline]
-    pub fn negative_flag_mut(&mut self) -> BitProxy {
+    pub fn negative_flag_mut(&mut self) -> BitProxy<'_> {
         BitProxy::new(self.f_mut(), Self::NEGATIVE_FLAG)
     }
 
@@ -258,7 +259,7 @@ impl Regs {
     }
 
     #[inline]
-    pub fn half_carry_flag_mut(&mut self) -> BitProxy {
+    pub fn half_carry_flag_mut(&mut self) -> BitProxy<'_> {
         BitProxy::new(self.f_mut(), Self::HALF_CARRY_FLAG)
     }
 
@@ -268,7 +269,7 @@ impl Regs {
     }
 
     #[inline]
-    pub fn carry_flag_mut(&mut self) -> BitProxy {
+    pub fn carry_flag_mut(&mut self) -> BitProxy<'_> {
         BitProxy::new(self.f_mut(), Self::CARRY_FLAG)
     }
 }
@@ -33,15 +33,9 @@ pub const INT_VBLANK_ENTRY: Addr = 0x40;
 pub const INTERRUPT_FLAG_REGISTER_ADDR: Addr = 0xFF0F;
 pub const INTERRUPT_MASK_REGISTER_ADDR: Addr = 0xFFFF;
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize, Default)]
 pub struct InterruptMaskRegsiter(Word);
 
-impl Default for InterruptMaskRegsiter {
-    fn default() -> Self {
-        Self(0x00)
-    }
-}
-
 impl InterruptMaskRegsiter {
     pub fn new() -> Self {
         Self::default()
@@ -61,15 +55,9 @@ impl InterruptMaskRegsiter {
     }
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize, Default)]
 pub struct InterruptFlagRegister(Word);
 
-impl Default for InterruptFlagRegister {
-    fn default() -> Self {
-        Self(0x00)
-    }
-}
-
 impl InterruptFlagRegister {
     pub fn new() -> Self {
         Default::default()
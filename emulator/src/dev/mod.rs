@@ -1,11 +1,11 @@
 use crate::types::{Addr, Word};
-use int_regs::IRQ;
 use log::warn;
 use std::default::Default;
 
 pub mod apu;
 pub mod bus;
 pub mod cart;
+pub mod cheat;
 pub mod cpu;
 pub mod gamepad;
 pub mod int_regs;
@@ -18,6 +18,7 @@ pub trait Reset {
     fn reset(&mut self);
 }
 
+/// 手动实现`Reset`的设备不能再实现`Default`, 它们的`new`允许`clippy::new_without_default`
 impl<T> Reset for T
 where
     T: Default,
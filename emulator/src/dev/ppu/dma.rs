@@ -43,7 +43,7 @@ impl DMA {
 
     pub fn tick(&mut self) -> Option<(Word, Word)> {
         self.ticks = self.ticks.wrapping_add(1);
-        if self.ticks % 4 != 0 || !self.active {
+        if !self.ticks.is_multiple_of(4) || !self.active {
             return None;
         }
         if self.start_delay > 0 {
@@ -108,6 +108,23 @@ impl PPU {
         self.fetcher.state = FetchState::Data0;
         self.fetcher.fetch_x += 8;
     }
+    /// 对象在当前扫描行的图块和图块内的行, 8x16的对象跨越两个连续的图块
+    fn object_tile_row(&self, obj: &Object) -> (usize, usize) {
+        let obj_height = self.lcdc.obj_height();
+        let ty = self.ly + 16 - obj.y;
+        let ty = if obj.y_flip() {
+            obj_height.wrapping_sub(1).wrapping_sub(ty)
+        } else {
+            ty
+        };
+        let tile_idx = if obj_height == 16 {
+            obj.tile_idx.clear_at(0)
+        } else {
+            obj.tile_idx
+        };
+        (tile_idx as usize + (ty / 8) as usize, (ty % 8) as usize)
+    }
+
     fn get_data0(&mut self) {
         if self.lcdc.window_bg_enabled() {
             let (i, j) = self.fetcher.bgw_data_idx;
@@ -121,30 +138,18 @@ impl PPU {
             };
         }
         if self.lcdc.obj_enabled() {
-            let obj_height = self.lcdc.obj_height();
             for (i, obj) in self.fetcher.objects_to_draw.iter().enumerate() {
-                let ty = self.ly + 16 - obj.y;
-                let ty = if obj.y_flip() {
-                    obj_height.wrapping_sub(1).wrapping_sub(ty)
-                } else {
-                    ty
-                };
-                let tile_idx = obj.tile_idx;
-                let tile_idx = if obj_height == 16 {
-                    tile_idx.clear_at(0)
-                } else {
-                    tile_idx
-                };
+                let (tile_idx, ty) = self.object_tile_row(obj);
                 unsafe {
                     *self
                         .fetcher
                         .objects_fetched_data
-                        .get_unchecked_mut(i as usize)
+                        .get_unchecked_mut(i)
                         .get_unchecked_mut(0) = *self
                         .vram
                         .tiles_area()
-                        .get_unchecked(tile_idx as usize)
-                        .get_unchecked(ty as usize)
+                        .get_unchecked(tile_idx)
+                        .get_unchecked(ty)
                         .get_unchecked(0)
                 };
             }
@@ -164,30 +169,18 @@ impl PPU {
             };
         }
         if self.lcdc.obj_enabled() {
-            let obj_height = self.lcdc.obj_height();
             for (i, obj) in self.fetcher.objects_to_draw.iter().enumerate() {
-                let ty = self.ly + 16 - obj.y;
-                let ty = if obj.y_flip() {
-                    obj_height.wrapping_sub(1).wrapping_sub(ty)
-                } else {
-                    ty
-                };
-                let tile_idx = obj.tile_idx;
-                let tile_idx = if obj_height == 16 {
-                    tile_idx.clear_at(0)
-                } else {
-                    tile_idx
-                };
+                let (tile_idx, ty) = self.object_tile_row(obj);
                 unsafe {
                     *self
                         .fetcher
                         .objects_fetched_data
-                        .get_unchecked_mut(i as usize)
+                        .get_unchecked_mut(i)
                         .get_unchecked_mut(1) = *self
                         .vram
                         .tiles_area()
-                        .get_unchecked(tile_idx as usize)
-                        .get_unchecked(ty as usize)
+                        .get_unchecked(tile_idx)
+                        .get_unchecked(ty)
                         .get_unchecked(1)
                 };
             }
@@ -250,7 +243,7 @@ impl PPU {
                 for (j, obj) in self.fetcher.objects_to_draw.iter().enumerate() {
                     let x = (obj.x as i32) - 8;
                     let offset = (i as i32) - x;
-                    if offset < 0 || offset > 7 {
+                    if !(0..=7).contains(&offset) {
                         continue;
                     }
                     let [b1, b2] = *unsafe { self.fetcher.objects_fetched_data.get_unchecked(j) };
@@ -283,8 +276,8 @@ impl PPU {
     }
 
     fn get_background_tile(&mut self) {
-        let y = self.ly + self.scy;
-        let x = self.fetcher.fetch_x + self.scx;
+        let y = self.ly.wrapping_add(self.scy);
+        let x = self.fetcher.fetch_x.wrapping_add(self.scx);
         let tile_idx = TilePos::from_point(x, y).to_idx();
         let &data_idx = unsafe {
             self.vram
@@ -344,7 +337,7 @@ impl PPU {
                     .find(|(_, other)| other.x > obj.x)
                     .map(|(idx, _)| idx)
                     .unwrap_or(self.fetcher.row_intersect_objects.len());
-                self.fetcher.row_intersect_objects.insert(pos, obj.clone());
+                self.fetcher.row_intersect_objects.insert(pos, *obj);
                 if self.fetcher.row_intersect_objects.len() >= 10 {
                     break;
                 }
@@ -4,17 +4,11 @@ use crate::{output::screen::ScreenOutput, types::Word};
 
 use super::{graphic::PPU_XRES, PPU};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 pub(super) struct LCDDriver {
     pub draw_x: Word,
 }
 
-impl Default for LCDDriver {
-    fn default() -> Self {
-        Self { draw_x: 0 }
-    }
-}
-
 impl LCDDriver {
     pub(super) fn new() -> Self {
         Default::default()
@@ -3,7 +3,6 @@ use std::ops::{Deref, DerefMut};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    dev::Reset,
     types::Word,
     utils::bits::{BitMap, BitProxy},
 };
@@ -58,7 +57,7 @@ impl LCDStat {
         self.0 = (self.0 & 0b1111_1100) | (mode as Word)
     }
 
-    pub fn lyc_flag_mut(&mut self) -> BitProxy {
+    pub fn lyc_flag_mut(&mut self) -> BitProxy<'_> {
         BitProxy::new(self, 2)
     }
 
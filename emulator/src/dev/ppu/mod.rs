@@ -36,7 +36,7 @@ const LCDC_REG_ADDR: Addr = 0xFF40;
 const LCDS_REG_ADDR: Addr = 0xFF41;
 const SCY_REG_ADDR: Addr = 0xFF42;
 const SCX_REG_ADDR: Addr = 0xFF43;
-const LY_REG_ADDR: Addr = 0xFF44;
+pub(crate) const LY_REG_ADDR: Addr = 0xFF44;
 const LYC_REG_ADDR: Addr = 0xFF45;
 const DMA_REG_ADDR: Addr = 0xFF46;
 const BGP_REG_ADDR: Addr = 0xFF47;
@@ -173,6 +173,7 @@ impl Reset for PPU {
 }
 
 impl PPU {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             lcdc: LCDControl(0b1001_0001),
@@ -199,6 +200,11 @@ impl PPU {
         }
     }
 
+    /// 当前扫描行
+    pub fn ly(&self) -> Word {
+        self.ly
+    }
+
     fn enabled(&self) -> bool {
         self.lcdc.enabled()
     }
@@ -313,7 +319,7 @@ impl PPU {
 
     fn tick_drawing(&mut self, output: &mut impl ScreenOutput) -> IRQ {
         let mut irq = IRQ_NONE;
-        if self.line_cycles % 2 == 0 {
+        if self.line_cycles.is_multiple_of(2) {
             self.fetcher_update();
             if self.lcd_driver.draw_x >= PPU_XRES {
                 self.set_mode(WorkMode::HBlank);
@@ -392,3 +398,79 @@ impl PPU {
         self.lcdc.window_enabled() && self.wx <= 166 && self.wy < PPU_YRES
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        graphic::{ScreenBitmap, PPU_CYCLES_PER_LINE, PPU_LINES_PER_FRAME},
+        PPU,
+    };
+    use crate::{dev::MemoryRegion, output::screen::BufferedScreenOutput, types::Addr};
+
+    /// 把`tile`号图块的每一行都填充为颜色`color`
+    fn fill_tile(ppu: &mut PPU, tile: Addr, color: u8) {
+        let (lo, hi) = (0xFF * (color & 1), 0xFF * (color >> 1));
+        for row in 0..8 {
+            let addr = 0x8000 + tile * 16 + row * 2;
+            ppu.vram.write(addr, lo);
+            ppu.vram.write(addr + 1, hi);
+        }
+    }
+
+    /// 运行两帧, 返回最后完成的一帧
+    fn render(ppu: &mut PPU) -> Box<ScreenBitmap> {
+        let mut output = BufferedScreenOutput::new();
+        for _ in 0..2 * PPU_CYCLES_PER_LINE * PPU_LINES_PER_FRAME as u32 {
+            ppu.tick(&mut output);
+        }
+        ppu.update_screen(&mut output);
+        Box::new(*output.frame())
+    }
+
+    #[test]
+    fn test_tall_objects() {
+        let mut ppu = PPU::new();
+        fill_tile(&mut ppu, 2, 1);
+        fill_tile(&mut ppu, 3, 2);
+        // 8x16的对象忽略图块号的bit 0, 上半部分为图块2, 下半部分为图块3
+        for (i, &attrs) in [0x00, 0x40].iter().enumerate() {
+            let addr = 0xFE00 + i as Addr * 4;
+            ppu.oam.write(addr, 16);
+            ppu.oam.write(addr + 1, 8 + i as u8 * 8);
+            ppu.oam.write(addr + 2, 3);
+            ppu.oam.write(addr + 3, attrs);
+        }
+        ppu.write(0xFF48, 0xE4);
+        // 只显示对象, 8x16
+        ppu.write(0xFF40, 0x86);
+        let frame = render(&mut ppu);
+        let (top, bottom) = (frame[0][0], frame[15][0]);
+        assert_ne!(top, bottom);
+        assert_eq!(frame[7][0], top);
+        assert_eq!(frame[8][0], bottom);
+        assert_ne!(frame[16][0], top);
+        assert_ne!(frame[16][0], bottom);
+        // 垂直翻转时两个图块也交换
+        assert_eq!(frame[0][8], bottom);
+        assert_eq!(frame[15][8], top);
+    }
+
+    #[test]
+    fn test_scroll_wraps() {
+        let mut ppu = PPU::new();
+        fill_tile(&mut ppu, 1, 3);
+        // 背景右下角的图块
+        ppu.vram.write(0x9800 + 31 * 32 + 31, 1);
+        ppu.write(0xFF42, 0xF8);
+        ppu.write(0xFF43, 0xF8);
+        ppu.write(0xFF47, 0xE4);
+        // 显示背景, 图块数据位于0x8000
+        ppu.write(0xFF40, 0x91);
+        let frame = render(&mut ppu);
+        // 左上角的8x8像素来自背景右下角, 之后回绕到背景左上角
+        assert_eq!(frame[7][7], frame[0][0]);
+        assert_ne!(frame[8][8], frame[0][0]);
+        assert_ne!(frame[0][8], frame[0][0]);
+        assert_ne!(frame[8][0], frame[0][0]);
+    }
+}
@@ -52,6 +52,7 @@ impl MemoryRegion for OAM {
 }
 
 impl OAM {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self(Box::new([0; OAM_SIZE]))
     }
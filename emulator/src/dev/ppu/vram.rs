@@ -50,6 +50,7 @@ impl MemoryRegion for VRAM {
 }
 
 impl VRAM {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self(Box::new([0; VRAM_SIZE]))
     }
@@ -1,7 +1,6 @@
 use std::ops::{Deref, DerefMut};
 
 use serde::{Deserialize, Serialize};
-use serde_with::serde_as;
 
 use super::{
     bus::{HRAM_LOW_BOUND, HRAM_SIZE, WRAM_LOW_BOUND, WRAM_SIZE},
@@ -47,6 +46,7 @@ impl DerefMut for WRAM {
 }
 
 impl WRAM {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self(Box::new([0; WRAM_SIZE]))
     }
@@ -67,6 +67,7 @@ impl MemoryRegion for HighRam {
 }
 
 impl HighRam {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self(Box::new([0; HRAM_SIZE]))
     }
@@ -1,9 +1,8 @@
 use super::{
     int_regs::{IRQ, IRQ_NONE, IRQ_SERIAL},
-    MemoryRegion, Reset,
+    MemoryRegion,
 };
 use crate::{
-    external::emulator_serial_callback,
     output::serial::SerialOutput,
     types::{Addr, Word},
     utils::bits::BitMap,
@@ -103,7 +102,7 @@ impl Serial {
 
     pub fn tick(&mut self, output: &mut impl SerialOutput) -> IRQ {
         self.ticks = self.ticks.wrapping_add(1);
-        if self.ticks % 512 != 0 {
+        if !self.ticks.is_multiple_of(512) {
             // 当 ticks不是512的倍数时， 串口设备不进行工作,
             // 使得串口设备的工作频率为 8192Hz
             IRQ_NONE
@@ -1,256 +1,1001 @@
-use std::io::Cursor;
-
 use crate::{
-    dev::{Bus, LoadCartResult, Reset, CPU},
+    debug::{
+        disasm::{self, Instruction},
+        symbols::Symbols,
+        Debugger, StopReason,
+    },
+    dev::{
+        bus::Region,
+        cart::{patch, Cart, CartInfo},
+        cheat::Cheats,
+        int_regs::IRQ_VBLANK,
+        ppu::graphic::{PPU_CYCLES_PER_LINE, PPU_LINES_PER_FRAME},
+        Bus, Reset, CPU,
+    },
     dump::CPUStateDump,
-    error::{EmuResult, EmulatorError, RunWhenAborting},
+    error::{
+        CorruptedSaveState, EmuErr, EmuResult, InvalidMovie, MovieActive, NoCartridge,
+        RunWhenAborting, SaveStateMismatch,
+    },
     output::{
-        audio::WebAudioOutput,
-        log::{init_logger, log_flush},
-        screen::{WebScreenOutput, WebTileOutput},
-        serial::{SerialOutput, WebSerialOutput},
+        audio::{AudioOutput, NullAudioOutput},
+        screen::{BufferedScreenOutput, NullTileOutput, ScreenOutput, TileOutput},
+        serial::{BufferedSerialOutput, SerialOutput},
     },
-    types::ClockCycle,
+    types::{Addr, ClockCycle, Word},
 };
-use ::log::error;
-// use brotli::{enc::BrotliEncoderParams, CompressorReader, CompressorWriter};
-use flate2::read::ZlibDecoder;
-use flate2::write::ZlibEncoder;
-use flate2::Compression;
+use movie::{Movie, MovieHeader, MovieMode, MovieStart};
+use rewind::Rewind;
 use serde::{Deserialize, Serialize};
-use tsify::Tsify;
-use tsify_derive::{EmulatorStepInput, EmulatorUpdateInput};
-use wasm_bindgen::prelude::*;
-use web_sys::OffscreenCanvasRenderingContext2d;
+use state::StateInfo;
+
+pub mod movie;
+pub mod rewind;
+pub mod state;
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+#[cfg(target_arch = "wasm32")]
+pub use web::{EmulatorUpdateResult, WasmEmulator};
 
 pub const BASE_CLOCK: u32 = 4_194_304;
-pub const VISUAL_FREQ_HZ: f64 = 59.7;
+/// 一帧(154行, 每行456周期)的时钟周期数
+pub const CYCLES_PER_FRAME: ClockCycle = PPU_LINES_PER_FRAME as ClockCycle * PPU_CYCLES_PER_LINE;
+/// 约59.73Hz
+pub const VISUAL_FREQ_HZ: f64 = BASE_CLOCK as f64 / CYCLES_PER_FRAME as f64;
 
-#[wasm_bindgen(js_name = WasmEmulator)]
-pub struct Emulator {
-    core: Core,
-    freq_scale: f64,
-    serial_output: WebSerialOutput,
-    screen_output: WebScreenOutput,
-    tile_output: WebTileOutput,
-    audio_output: WebAudioOutput,
+/// `run_cycles`/`run_frame`/`run_scanline`的结果
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RunResult {
+    /// 实际执行的时钟周期数, 指令不可分割, 可能略多于要求的周期数
+    pub cycles: ClockCycle,
+    /// 期间是否进入VBlank, 即产生了新的一帧
+    pub new_frame: bool,
+    /// 因断点或观察点提前停止
+    pub stop: Option<StopReason>,
+}
+
+/// 运行到何时停止
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RunUntil {
+    Cycles,
+    /// 进入VBlank
+    Frame,
+    /// LY变化
+    Scanline,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Core {
-    cpu: CPU,
-    bus: Bus,
+    pub cpu: CPU,
+    pub bus: Bus,
     aborted: bool,
     cycles: ClockCycle,
 }
 
-// Function `__wbg_instanceof_JsType_24d65669860e1289` should have snake_case name, e.g. `__wbg_instanceof_js_type_24d65669860e1289`
-#[allow(non_snake_case)]
-mod tsify_derive {
-    use serde::Deserialize;
-
-    use super::*;
-    #[derive(Serialize, Tsify)]
-    #[tsify(into_wasm_abi)]
-    pub struct EmulatorUpdateResult {
-        pub cycles: ClockCycle,
-        pub cpu: CPUStateDump,
-        pub err: Option<String>,
+impl Default for Core {
+    fn default() -> Self {
+        Self {
+            cpu: CPU::new(),
+            bus: Bus::new(),
+            aborted: false,
+            cycles: 0,
+        }
     }
+}
 
-    #[derive(Deserialize, Tsify)]
-    #[tsify(from_wasm_abi)]
-    pub struct EmulatorUpdateInput {
-        pub btns: u8,
-        pub timestamp: f64,
+impl Core {
+    pub fn new() -> Self {
+        Default::default()
     }
+}
 
-    #[derive(Deserialize, Tsify)]
-    #[tsify(from_wasm_abi)]
-    pub struct EmulatorStepInput {
-        pub btns: u8,
-        pub timestamp: f64,
-    }
+/// 与宿主环境无关的模拟器, 画面/图块/音频/串口输出由宿主提供
+pub struct Emulator<S, T, A, O> {
+    core: Core,
+    /// 已加载ROM的CRC32, 用于校验存档
+    rom_crc32: u32,
+    rewind: Rewind,
+    movie: Option<MovieMode>,
+    screen_output: S,
+    tile_output: T,
+    audio_output: A,
+    serial_output: O,
 }
 
-pub use tsify_derive::EmulatorUpdateResult;
+/// 不依赖任何宿主环境的模拟器, 用于测试和命令行工具
+pub type HeadlessEmulator =
+    Emulator<BufferedScreenOutput, NullTileOutput, NullAudioOutput, BufferedSerialOutput>;
 
-#[wasm_bindgen(js_class = WasmEmulator)]
-impl Emulator {
-    #[wasm_bindgen(constructor)]
-    pub fn new(freq_scale: f64, volume: f32) -> Emulator {
+impl<S, T, A, O> Default for Emulator<S, T, A, O>
+where
+    S: ScreenOutput + Default,
+    T: TileOutput + Default,
+    A: AudioOutput + Default,
+    O: SerialOutput + Default,
+{
+    fn default() -> Self {
+        Self::new(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        )
+    }
+}
+
+impl<S, T, A, O> Emulator<S, T, A, O>
+where
+    S: ScreenOutput,
+    T: TileOutput,
+    A: AudioOutput,
+    O: SerialOutput,
+{
+    pub fn new(screen_output: S, tile_output: T, audio_output: A, serial_output: O) -> Self {
         Self {
-            core: Core {
-                cpu: CPU::new(),
-                bus: Bus::new(),
-                aborted: false,
-                cycles: 0,
-            },
-            serial_output: WebSerialOutput::new(),
-            screen_output: WebScreenOutput::new(),
-            tile_output: WebTileOutput::new(),
-            audio_output: WebAudioOutput::new(volume),
-            freq_scale,
+            core: Core::new(),
+            rom_crc32: 0,
+            rewind: Rewind::default(),
+            movie: None,
+            screen_output,
+            tile_output,
+            audio_output,
+            serial_output,
+        }
+    }
+
+    pub fn core(&self) -> &Core {
+        &self.core
+    }
+
+    pub fn core_mut(&mut self) -> &mut Core {
+        &mut self.core
+    }
+
+    pub fn screen_output(&self) -> &S {
+        &self.screen_output
+    }
+
+    pub fn screen_output_mut(&mut self) -> &mut S {
+        &mut self.screen_output
+    }
+
+    pub fn tile_output(&self) -> &T {
+        &self.tile_output
+    }
+
+    pub fn tile_output_mut(&mut self) -> &mut T {
+        &mut self.tile_output
+    }
+
+    pub fn audio_output(&self) -> &A {
+        &self.audio_output
+    }
+
+    pub fn audio_output_mut(&mut self) -> &mut A {
+        &mut self.audio_output
+    }
+
+    pub fn serial_output(&self) -> &O {
+        &self.serial_output
+    }
+
+    pub fn serial_output_mut(&mut self) -> &mut O {
+        &mut self.serial_output
+    }
+
+    pub fn debugger(&self) -> &Debugger {
+        &self.core.bus.debugger
+    }
+
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.core.bus.debugger
+    }
+
+    pub fn cheats(&self) -> &Cheats {
+        &self.core.bus.cheats
+    }
+
+    /// 金手指在恢复状态后保留, 加载其他卡带时清空
+    pub fn cheats_mut(&mut self) -> &mut Cheats {
+        &mut self.core.bus.cheats
+    }
+
+    /// 反汇编PC前`before`条和PC起`after`条指令
+    pub fn disassemble(&self, before: usize, after: usize) -> Vec<Instruction> {
+        disasm::disassemble_around(&self.core.bus, self.core.cpu.pc(), before, after)
+    }
+
+    /// 加载RGBDS格式的`.sym`符号文件, 替换已有的符号, 返回符号数. 加载卡带时清空符号
+    pub fn load_symbols(&mut self, text: &str) -> EmuResult<usize> {
+        let symbols = Symbols::parse(text)?;
+        let len = symbols.len();
+        self.core.bus.debugger.symbols = symbols;
+        Ok(len)
+    }
+
+    /// 调用栈, 当前位置在前, 之后依次是各层的调用处, 有符号时显示为`符号+偏移`
+    pub fn backtrace(&self) -> Vec<String> {
+        let bus = &self.core.bus;
+        let pc = self.core.cpu.pc();
+        let mut lines = vec![bus.debugger.format_addr(bus.rom_bank(pc), pc)];
+        lines.extend(bus.debugger.call_stack().iter().rev().map(|frame| {
+            let site = bus.debugger.format_addr(frame.bank, frame.call_site);
+            if frame.interrupt {
+                format!("{site} (interrupt)")
+            } else {
+                site
+            }
+        }));
+        lines
+    }
+
+    /// 无副作用地读取内存, 见[`Bus::peek`]
+    pub fn peek(&self, addr: Addr) -> Word {
+        self.core.bus.peek(addr)
+    }
+
+    /// 读取指定ROM或外部RAM bank, 见[`Bus::peek_bank`]
+    pub fn peek_bank(&self, bank: usize, addr: Addr) -> Word {
+        self.core.bus.peek_bank(bank, addr)
+    }
+
+    /// 直接修改内存, 见[`Bus::poke`]
+    pub fn poke(&mut self, addr: Addr, data: Word) {
+        self.core.bus.poke(addr, data)
+    }
+
+    pub fn region(&self, region: Region) -> &[Word] {
+        self.core.bus.region(region)
+    }
+
+    /// 上电以来执行的时钟周期数
+    pub fn cycles(&self) -> ClockCycle {
+        self.core.cycles
+    }
+
+    pub fn aborted(&self) -> bool {
+        self.core.aborted
+    }
+
+    /// 加载卡带, 出错时不影响已加载的卡带. 录像或回放期间不能加载
+    pub fn load_cart(&mut self, rom: Box<[u8]>, timestamp: i64) -> EmuResult<CartInfo> {
+        self.check_no_movie()?;
+        let rom_crc32 = crc32fast::hash(&rom);
+        let cart = Cart::new(rom, timestamp)?;
+        let info = cart.header().info();
+        if self.core.bus.cart.is_some() {
+            self.reset_core()
+        }
+        // 金手指只对同一个卡带保留
+        if rom_crc32 != self.rom_crc32 {
+            self.core.bus.cheats.clear();
         }
+        self.rom_crc32 = rom_crc32;
+        self.rewind.clear();
+        self.core.bus.debugger.symbols = Symbols::default();
+        self.core.bus.debugger.clear_call_stack();
+        self.core.bus.cart = Some(cart);
+        Ok(info)
     }
-    #[wasm_bindgen(js_name = initLogger)]
-    pub fn init_logger() {
-        init_logger();
+
+    /// 用已加载卡带的ROM重新上电并导入电池存档`sav`, 保留调试器, 金手指和录像状态
+    fn power_cycle(&mut self, timestamp: i64, sav: Option<&[u8]>) -> EmuResult {
+        let Some(cart) = &self.core.bus.cart else {
+            return EmuErr(NoCartridge);
+        };
+        let mut cart = Cart::new(cart.rom().into(), timestamp)?;
+        if let Some(sav) = sav {
+            cart.import_sav(sav)?;
+        }
+        self.reset_core();
+        self.core.bus.cart = Some(cart);
+        Ok(())
+    }
+
+    fn check_no_movie(&self) -> EmuResult {
+        if self.movie.is_some() {
+            EmuErr(MovieActive)
+        } else {
+            Ok(())
+        }
     }
 
-    #[wasm_bindgen(js_name = update)]
-    pub fn update(
+    /// 依次应用IPS/UPS/BPS补丁后加载卡带, 补丁后的ROM同样要通过卡带头和长度校验.
+    /// 出错时不影响已加载的卡带
+    pub fn load_cart_patched(
         &mut self,
-        EmulatorUpdateInput { btns, timestamp }: EmulatorUpdateInput,
-    ) -> EmulatorUpdateResult {
+        rom: Box<[u8]>,
+        patches: &[&[u8]],
+        timestamp: i64,
+    ) -> EmuResult<CartInfo> {
+        let mut rom = rom;
+        for patch in patches {
+            rom = patch::apply(&rom, patch)?.into_boxed_slice();
+        }
+        self.load_cart(rom, timestamp)
+    }
+
+    /// 更新按键状态和卡带实时时钟. 录像或回放时按键和时间由[`Self::movie_frame`]提供, 忽略此调用
+    pub fn update_input(&mut self, btns: Word, timestamp: i64) {
+        if self.movie.is_none() {
+            self.apply_input(btns, timestamp)
+        }
+    }
+
+    fn apply_input(&mut self, btns: Word, timestamp: i64) {
         if let Some(cart) = &mut self.core.bus.cart {
-            cart.update_rtc(timestamp as _)
+            cart.update_rtc(timestamp)
         }
         self.core.bus.btns.update(btns);
-        let cycles = ((BASE_CLOCK as f64) * self.freq_scale / VISUAL_FREQ_HZ) as u32;
-        let err = self._update(cycles);
-        let cpu = self.core.cpu.dump(&mut self.core.bus);
-        let cycles = self.core.cycles;
-        self.core.bus.ppu.update_tiles(&mut self.tile_output);
-        self.core.bus.ppu.update_screen(&mut self.screen_output);
-        if self.freq_scale == 1.0 {
-            self.audio_output.update();
+    }
+
+    /// 至少执行`cycles`个时钟周期, 出错后模拟器进入中止状态
+    pub fn run(&mut self, cycles: ClockCycle) -> EmuResult {
+        self.run_cycles(cycles).map(|_| ())
+    }
+
+    /// 至少执行`cycles`个时钟周期
+    pub fn run_cycles(&mut self, cycles: ClockCycle) -> EmuResult<RunResult> {
+        self.run_with(cycles, RunUntil::Cycles)
+    }
+
+    /// 运行到下一次进入VBlank时所在指令结束. LCD关闭时没有VBlank, 运行一帧的周期数后返回
+    pub fn run_frame(&mut self) -> EmuResult<RunResult> {
+        self.run_with(CYCLES_PER_FRAME, RunUntil::Frame)
+    }
+
+    /// 运行到LY变化时所在指令结束. LCD关闭时运行一行的周期数后返回
+    pub fn run_scanline(&mut self) -> EmuResult<RunResult> {
+        self.run_with(PPU_CYCLES_PER_LINE, RunUntil::Scanline)
+    }
+
+    /// 录像或回放期间只能通过[`Self::movie_frame`]运行, 否则录像会缺少帧
+    fn run_with(&mut self, cycles: ClockCycle, until: RunUntil) -> EmuResult<RunResult> {
+        self.check_no_movie()?;
+        self.run_unchecked(cycles, until)
+    }
+
+    fn run_unchecked(&mut self, cycles: ClockCycle, until: RunUntil) -> EmuResult<RunResult> {
+        if self.core.aborted {
+            return EmuErr(RunWhenAborting);
         }
-        self.audio_output.clear_buffer();
-        self.serial_output.flush();
-        log_flush();
-        EmulatorUpdateResult { cycles, cpu, err }
+        let res = self.step(cycles, until)?;
+        self.rewind.capture(&self.core)?;
+        Ok(res)
     }
 
-    #[wasm_bindgen(js_name = step)]
-    pub fn step(
+    /// 至多执行`cycles`个时钟周期, 每条指令后检查`stop`, 返回是否因`stop`提前停止
+    pub fn run_until(
         &mut self,
-        EmulatorStepInput { btns, timestamp }: EmulatorStepInput,
-    ) -> EmulatorUpdateResult {
-        if let Some(cart) = &mut self.core.bus.cart {
-            cart.update_rtc(timestamp as _)
+        cycles: ClockCycle,
+        mut stop: impl FnMut(&Self) -> bool,
+    ) -> EmuResult<bool> {
+        if self.core.aborted {
+            return EmuErr(RunWhenAborting);
         }
-        self.core.bus.btns.update(btns);
-        let err = self._update(1);
-        let cpu = self.core.cpu.dump(&mut self.core.bus);
-        let cycles = self.core.cycles;
+        self.check_no_movie()?;
+        let mut clocks = 0;
+        while clocks < cycles {
+            match self.tick_instr() {
+                Ok((cycles, _)) => clocks += cycles,
+                Err(err) => {
+                    self.core.aborted = true;
+                    return Err(err);
+                }
+            }
+            if stop(self) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// 将画面和图块提交给输出设备, 并刷新串口缓冲
+    pub fn present(&mut self) {
         self.core.bus.ppu.update_tiles(&mut self.tile_output);
         self.core.bus.ppu.update_screen(&mut self.screen_output);
-        self.audio_output.clear_buffer();
         self.serial_output.flush();
-        log_flush();
-        EmulatorUpdateResult { cycles, cpu, err }
     }
 
-    #[wasm_bindgen(js_name = loadCart)]
-    pub fn load_cart(&mut self, rom: Box<[u8]>, timestamp: f64) -> LoadCartResult {
-        if self.core.bus.cart.is_some() {
-            self.reset()
+    pub fn dump(&self) -> CPUStateDump {
+        self.core.cpu.dump(&self.core.bus)
+    }
+
+    /// 保存状态, `timestamp`为创建时间
+    pub fn save(&self, timestamp: i64) -> EmuResult<Box<[u8]>> {
+        let Some(cart) = &self.core.bus.cart else {
+            return EmuErr(NoCartridge);
+        };
+        let header = cart.header();
+        let info = StateInfo {
+            title: header.title().into_owned(),
+            header_checksum: header.header_checksum(),
+            rom_crc32: self.rom_crc32,
+            timestamp,
+            cycles: self.core.cycles,
+            thumbnail: self.screen_output.last_frame().map(state::thumbnail),
+        };
+        state::write(&info, &self.core)
+    }
+
+    /// 恢复状态, 存档必须属于已加载的卡带
+    /// 录像或回放期间不能读档
+    pub fn load(&mut self, save: &[u8]) -> EmuResult {
+        self.check_no_movie()?;
+        self.load_state(save)
+    }
+
+    fn load_state(&mut self, save: &[u8]) -> EmuResult {
+        let rom_crc32 = self.rom_crc32;
+        let Some(cart) = &self.core.bus.cart else {
+            return EmuErr(NoCartridge);
+        };
+        let core = state::read(save, |info| {
+            if info.rom_crc32 == rom_crc32 {
+                Ok(())
+            } else {
+                EmuErr(SaveStateMismatch {
+                    expected: cart.header().title().into_owned(),
+                    actual: info.title.clone(),
+                })
+            }
+        })?;
+        self.replace_core(core)
+    }
+
+    /// 设置倒带的快照间隔(帧)和内存预算(字节), 预算为0时关闭倒带
+    pub fn set_rewind(&mut self, interval_frames: u32, budget: usize) {
+        self.rewind = Rewind::new(interval_frames, budget);
+    }
+
+    pub fn rewind_buffer(&self) -> &Rewind {
+        &self.rewind
+    }
+
+    /// 回到上一个快照并运行一帧以生成画面, 没有快照时返回`false`.
+    /// 按住倒带时由宿主每帧调用一次
+    pub fn rewind(&mut self) -> EmuResult<bool> {
+        // 录像中的帧不能撤回
+        if self.movie.is_some() {
+            return Ok(false);
         }
-        self.core.aborted = false;
-        self.core.bus.load_cart(rom, timestamp as _).into()
+        let Some(core) = self.rewind.pop()? else {
+            return Ok(false);
+        };
+        self.replace_core(core)?;
+        // 不经过`run_frame`, 避免生成新的快照
+        self.step(CYCLES_PER_FRAME, RunUntil::Frame)?;
+        Ok(true)
     }
 
-    #[wasm_bindgen(js_name = save)]
-    pub fn save(&self) -> Option<Box<[u8]>> {
-        let mut output = Vec::new();
-        let output1 = ZlibEncoder::new(&mut output, Compression::best());
-        // let writer = CompressorWriter::new(&mut output, 4096, 9, 21);
-        if let Err(err) = bincode::serialize_into(output1, &self.core) {
-            error!("{err}");
-            None
-        } else {
-            Some(output.into_boxed_slice())
+    /// 换入不含ROM的`Core`(来自存档或快照), 沿用已加载卡带的ROM
+    fn replace_core(&mut self, mut core: Core) -> EmuResult {
+        match (&mut core.bus.cart, &mut self.core.bus.cart) {
+            (Some(saved), Some(cart)) => saved.swap_rom(cart),
+            (_, None) => return EmuErr(NoCartridge),
+            (None, _) => {
+                return EmuErr(CorruptedSaveState {
+                    msg: "missing cartridge".to_string(),
+                })
+            }
         }
+        core.bus.debugger = std::mem::take(&mut self.core.bus.debugger);
+        core.bus.debugger.clear_call_stack();
+        core.bus.cheats = std::mem::take(&mut self.core.bus.cheats);
+        self.core = core;
+        Ok(())
+    }
+
+    /// 开始录像. `from_state`为`false`时重新上电从头开始, 否则从当前状态开始.
+    /// 录像期间RTC由模拟时间驱动, `timestamp`为第0帧的RTC时间
+    pub fn start_recording(&mut self, from_state: bool, timestamp: i64) -> EmuResult {
+        self.check_no_movie()?;
+        let Some(cart) = &self.core.bus.cart else {
+            return EmuErr(NoCartridge);
+        };
+        let title = cart.header().title().into_owned();
+        let start = if from_state {
+            MovieStart::State(self.save(timestamp)?)
+        } else {
+            // 重新上电不丢失电池存档, 回放时从同一份存档开始
+            let sav = if cart.header().has_battery() {
+                Some(cart.export_sav()?)
+            } else {
+                None
+            };
+            self.power_cycle(timestamp, sav.as_deref())?;
+            MovieStart::PowerOn { sav }
+        };
+        self.movie = Some(MovieMode::Recording(Movie {
+            header: MovieHeader {
+                title,
+                rom_crc32: self.rom_crc32,
+                rtc_base: timestamp,
+                start,
+            },
+            inputs: Vec::new(),
+        }));
+        Ok(())
     }
 
-    #[wasm_bindgen(js_name = load)]
-    pub fn load(&mut self, save: Box<[u8]>) -> bool {
-        let input = Cursor::new(save);
-        // let reader = CompressorReader::new(cursor, 4096, 9, 21);
-        let input = ZlibDecoder::new(input);
-        match bincode::deserialize_from(input) {
-            Ok(state) => {
-                self.core = state;
-                true
+    /// 回放录像, 录像必须属于已加载的卡带
+    pub fn start_playback(&mut self, movie: &[u8]) -> EmuResult {
+        self.check_no_movie()?;
+        let movie = Movie::decode(movie)?;
+        let Some(cart) = &self.core.bus.cart else {
+            return EmuErr(NoCartridge);
+        };
+        if movie.header.rom_crc32 != self.rom_crc32 {
+            return EmuErr(InvalidMovie {
+                msg: format!(
+                    "movie belongs to {:?}, but {:?} is loaded",
+                    movie.header.title,
+                    cart.header().title()
+                ),
+            });
+        }
+        match &movie.header.start {
+            MovieStart::PowerOn { sav } => {
+                self.power_cycle(movie.header.rtc_base, sav.as_deref())?
             }
-            Err(err) => {
-                error!("{err}");
-                false
+            MovieStart::State(save) => self.load_state(save)?,
+        }
+        self.movie = Some(MovieMode::Playing { movie, frame: 0 });
+        Ok(())
+    }
+
+    /// 是否正在录像或回放
+    pub fn movie_active(&self) -> bool {
+        self.movie.is_some()
+    }
+
+    /// 录像或回放一帧: 录像时记录`btns`, 回放时忽略`btns`而使用录像中的按键.
+    /// 回放结束或没有录像时返回`false`且不运行
+    pub fn movie_frame(&mut self, btns: Word) -> EmuResult<bool> {
+        let (btns, timestamp) = match &mut self.movie {
+            Some(MovieMode::Recording(movie)) => {
+                let timestamp = movie.timestamp(movie.inputs.len());
+                movie.inputs.push(btns);
+                (btns, timestamp)
             }
+            Some(MovieMode::Playing { movie, frame }) => match movie.inputs.get(*frame) {
+                Some(&btns) => {
+                    let timestamp = movie.timestamp(*frame);
+                    *frame += 1;
+                    (btns, timestamp)
+                }
+                None => {
+                    self.movie = None;
+                    return Ok(false);
+                }
+            },
+            None => return Ok(false),
+        };
+        self.apply_input(btns, timestamp);
+        self.run_unchecked(CYCLES_PER_FRAME, RunUntil::Frame)?;
+        Ok(true)
+    }
+
+    /// 结束录像或回放, 录像时返回编码后的录像文件
+    pub fn stop_movie(&mut self) -> EmuResult<Option<Box<[u8]>>> {
+        match self.movie.take() {
+            Some(MovieMode::Recording(movie)) => movie.encode().map(Some),
+            _ => Ok(None),
         }
     }
 
-    #[wasm_bindgen(js_name = reset)]
-    pub fn reset(&mut self) {
-        self.core.cycles = 0;
-        self.core.aborted = false;
-        self.core.cpu.reset();
-        self.core.bus.reset();
-        self.audio_output.reset();
+    /// 卡带的震动马达是否开启, 由宿主在每帧之后查询
+    pub fn rumble(&self) -> bool {
+        self.core
+            .bus
+            .cart
+            .as_ref()
+            .is_some_and(|cart| cart.rumble())
+    }
+
+    /// 输入倾斜(单位为g, `x`向右为正, `y`向下为正), 供带加速度计的MBC7卡带读取
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        if let Some(cart) = &mut self.core.bus.cart {
+            cart.set_tilt(x, y)
+        }
     }
 
-    #[wasm_bindgen(js_name = setScreenCanvas)]
-    pub fn set_screen_canvas(&mut self, canvas: OffscreenCanvasRenderingContext2d) {
-        self.screen_output.set_canvas(canvas);
+    /// 导出卡带电池存档(`.sav`)
+    pub fn export_sav(&self) -> EmuResult<Box<[u8]>> {
+        match &self.core.bus.cart {
+            Some(cart) => cart.export_sav(),
+            None => EmuErr(NoCartridge),
+        }
     }
 
-    #[wasm_bindgen(js_name = setTilesCanvas)]
-    pub fn set_tiles_canvas(&mut self, canvas: OffscreenCanvasRenderingContext2d) {
-        self.tile_output.set_canvas(canvas);
+    /// 导入卡带电池存档(`.sav`)
+    pub fn import_sav(&mut self, sav: &[u8]) -> EmuResult {
+        match &mut self.core.bus.cart {
+            Some(cart) => cart.import_sav(sav),
+            None => EmuErr(NoCartridge),
+        }
     }
 
-    #[wasm_bindgen(js_name = setVolume)]
-    pub fn set_volume(&mut self, volume: f32) {
-        self.audio_output.set_volume(volume);
+    /// 复位并卸下卡带, 录像或回放期间不能复位
+    pub fn reset(&mut self) -> EmuResult {
+        self.check_no_movie()?;
+        self.reset_core();
+        Ok(())
+    }
+
+    fn reset_core(&mut self) {
+        self.rewind.clear();
+        self.core.cycles = 0;
+        self.core.aborted = false;
+        self.core.cpu.reset();
+        self.core.bus.reset();
+        self.core.bus.debugger.clear_call_stack();
     }
 
-    #[wasm_bindgen(js_name = setFreqScale)]
-    pub fn set_freq_scale(&mut self, freq_scale: f64) {
-        self.freq_scale = freq_scale;
+    /// 执行一条指令, 录像或回放期间不能调用
+    pub fn tick(&mut self) -> EmuResult<ClockCycle> {
+        self.check_no_movie()?;
+        self.tick_instr().map(|(cycles, _)| cycles)
     }
 
-    fn _update(&mut self, cycles: ClockCycle) -> Option<String> {
-        if self.core.aborted {
-            return self.handle_err(RunWhenAborting);
+    /// 执行一条指令, 返回周期数和期间是否进入VBlank
+    fn tick_instr(&mut self) -> EmuResult<(ClockCycle, bool)> {
+        let cycles = self.core.cpu.tick(&mut self.core.bus)?;
+        let vblank = self.tick_devices(cycles)?;
+        if vblank {
+            self.core.bus.apply_cheats();
         }
-        let mut clocks = 0;
-        while clocks < cycles {
-            let res = self.tick();
-            match res {
-                EmuResult::Ok(cycles) => {
-                    clocks += cycles;
+        self.core.cycles = self.core.cycles.wrapping_add(cycles);
+        Ok((cycles, vblank))
+    }
+
+    /// 至多执行`cycles`个时钟周期(指令不可分割), 满足`until`时提前停止, 出错后进入中止状态
+    fn step(&mut self, cycles: ClockCycle, until: RunUntil) -> EmuResult<RunResult> {
+        let mut res = RunResult::default();
+        let ly = self.core.bus.ppu.ly();
+        // 丢弃宿主访问内存时留下的命中
+        self.core.bus.debugger.take_stop();
+        while res.cycles < cycles {
+            match self.tick_instr() {
+                Ok((cycles, vblank)) => {
+                    res.cycles += cycles;
+                    res.new_frame |= vblank;
                 }
-                EmuResult::Err(err) => {
-                    return self.handle_err(err);
+                Err(err) => {
+                    self.core.aborted = true;
+                    return Err(err);
                 }
             }
+            res.stop = self.core.bus.debugger.take_stop();
+            let stop = res.stop.is_some()
+                || match until {
+                    RunUntil::Cycles => false,
+                    RunUntil::Frame => res.new_frame,
+                    RunUntil::Scanline => self.core.bus.ppu.ly() != ly,
+                };
+            if stop {
+                break;
+            }
         }
-        None
+        Ok(res)
     }
 
-    fn handle_err(&mut self, err: impl AsRef<EmulatorError>) -> Option<String> {
-        self.core.aborted = true;
-        Some(err.as_ref().msg())
-    }
-
-    fn tick_devices(&mut self, cycles: ClockCycle) -> EmuResult {
+    /// 返回期间是否进入VBlank
+    fn tick_devices(&mut self, cycles: ClockCycle) -> EmuResult<bool> {
+        let mut vblank = false;
         for _ in 0..cycles {
             self.core.bus.apu.tick(&mut self.audio_output);
             let irq0 = self.core.bus.timer.tick();
             let irq1 = self.core.bus.serial.tick(&mut self.serial_output);
             self.core.bus.tick_dma()?;
             let irq2 = self.core.bus.ppu.tick(&mut self.screen_output);
+            vblank |= irq2 & IRQ_VBLANK != 0;
             let irq = irq0 | irq1 | irq2;
             self.core.bus.int_flag_reg.add(irq);
         }
-        Ok(())
+        Ok(vblank)
     }
+}
 
-    fn tick(&mut self) -> EmuResult<ClockCycle> {
-        let cycles = self.core.cpu.tick(&mut self.core.bus)?;
-        self.tick_devices(cycles)?;
-        self.core.cycles += cycles;
-        Ok(cycles)
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        io::{Read, Write},
+    };
+
+    use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+    use super::{state, HeadlessEmulator, BASE_CLOCK, CYCLES_PER_FRAME};
+    use crate::dev::cart::test::fix_checksum;
+    use crate::dev::ppu::graphic::{PPU_CYCLES_PER_LINE, PPU_YRES};
+    use crate::error::{
+        BoxedEmulatorError, CorruptedSaveState, IncompatibleSaveState, InvalidChecksum,
+        InvalidMovie, InvalidRomSize, InvalidSaveState, MovieActive, SaveStateMismatch,
+    };
+
+    const ROM_PATH: &str = "../public/roms/dmg-acid2.gb";
+
+    #[test]
+    fn test_headless() {
+        let rom = fs::read(ROM_PATH).unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        emulator.run(BASE_CLOCK).unwrap();
+        emulator.present();
+        assert!(emulator.cycles() >= BASE_CLOCK);
+
+        let save = emulator.save(0).unwrap();
+        let pc = emulator.dump().pc;
+        emulator.run(BASE_CLOCK / 60).unwrap();
+        emulator.load(&save).unwrap();
+        assert_eq!(emulator.dump().pc, pc);
+    }
+
+    #[test]
+    fn test_load_patched() {
+        let rom = fs::read(ROM_PATH).unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        // IPS: 把标题改为"PATCHED", 不修正头部校验和
+        let mut patch = b"PATCH\x00\x01\x34\x00\x07PATCHED".to_vec();
+        patch.extend_from_slice(b"EOF");
+        let err = emulator
+            .load_cart_patched(rom.clone().into_boxed_slice(), &[&patch], 0)
+            .unwrap_err();
+        assert!(matches!(*err, InvalidChecksum { .. }));
+
+        let mut patched = rom.clone();
+        patched[0x134..0x13B].copy_from_slice(b"PATCHED");
+        fix_checksum(&mut patched);
+        let mut fix = b"PATCH\x00\x01\x4D\x00\x01".to_vec();
+        fix.push(patched[0x14D]);
+        fix.extend_from_slice(b"EOF");
+        let info = emulator
+            .load_cart_patched(rom.clone().into_boxed_slice(), &[&patch, &fix], 0)
+            .unwrap();
+        assert!(info.title.starts_with("PATCHED"));
+        emulator.run(BASE_CLOCK / 60).unwrap();
+
+        // 截断后放不下卡带头的ROM被拒绝, 已加载的卡带和状态不变
+        let mut truncate = b"PATCHEOF".to_vec();
+        truncate.extend_from_slice(&[0x00, 0x01, 0x00]);
+        emulator.cheats_mut().add("00A-17B").unwrap();
+        let cycles = emulator.cycles();
+        let err = emulator
+            .load_cart_patched(rom.into_boxed_slice(), &[&truncate], 0)
+            .unwrap_err();
+        assert!(matches!(*err, InvalidRomSize { size: 0x100 }));
+        assert!(emulator
+            .core()
+            .bus
+            .cart
+            .as_ref()
+            .unwrap()
+            .header()
+            .title()
+            .starts_with("PATCHED"));
+        assert_eq!(emulator.cycles(), cycles);
+        assert_eq!(emulator.cheats().iter().count(), 1);
+        emulator.run(BASE_CLOCK / 60).unwrap();
+    }
+
+    #[test]
+    fn test_state_container() {
+        let rom = fs::read(ROM_PATH).unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        emulator.run(BASE_CLOCK / 10).unwrap();
+        emulator.present();
+        let save = emulator.save(1234).unwrap();
+
+        let info = state::read_info(&save).unwrap();
+        assert_eq!(info.title, "DMG-ACID2");
+        assert_eq!(info.timestamp, 1234);
+        assert_eq!(info.cycles, emulator.cycles());
+        assert_eq!(info.thumbnail.map(|t| t.len()), Some(160 * 144));
+
+        assert_eq!(*emulator.load(&save[1..]).unwrap_err(), InvalidSaveState);
+        // 解码`Core`后还有多余的数据
+        let mut body = Vec::new();
+        ZlibDecoder::new(&save[6..]).read_to_end(&mut body).unwrap();
+        body.push(0);
+        let mut encoder = ZlibEncoder::new(save[..6].to_vec(), Compression::fast());
+        encoder.write_all(&body).unwrap();
+        let trailing = encoder.finish().unwrap();
+        assert!(matches!(
+            *emulator.load(&trailing).unwrap_err(),
+            CorruptedSaveState { .. }
+        ));
+        let mut incompatible = save.to_vec();
+        incompatible[4] = 0xFF;
+        assert!(matches!(
+            *emulator.load(&incompatible).unwrap_err(),
+            IncompatibleSaveState {
+                version: 0x00FF,
+                ..
+            }
+        ));
+
+        let rom = fs::read("../public/roms/Tennis (World).gb").unwrap();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        assert!(matches!(
+            *emulator.load(&save).unwrap_err(),
+            SaveStateMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn test_state_without_rom() {
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        emulator.run(BASE_CLOCK).unwrap();
+        let save = emulator.save(0).unwrap();
+        assert!(save.len() < 64 * 1024);
+
+        let pc = emulator.dump().pc;
+        emulator.run(BASE_CLOCK / 60).unwrap();
+        emulator.load(&save).unwrap();
+        assert_eq!(emulator.dump().pc, pc);
+        assert_eq!(
+            emulator.core().bus.cart.as_ref().unwrap().rom().len(),
+            1024 * 1024
+        );
+        emulator.run(BASE_CLOCK / 60).unwrap();
+    }
+
+    #[test]
+    fn test_rewind() {
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        emulator.set_rewind(2, 1024 * 1024);
+
+        let mut captured = Vec::new();
+        for _ in 0..20 {
+            emulator.run(CYCLES_PER_FRAME).unwrap();
+            if emulator.rewind_buffer().len() > captured.len() {
+                captured.push(emulator.cycles());
+            }
+        }
+        assert_eq!(captured.len(), 10);
+        // 快照经过压缩
+        let snapshot_size = emulator.rewind_buffer().used() / captured.len();
+        assert!(snapshot_size > 0);
+        assert!(snapshot_size < bincode::serialize(&emulator.core).unwrap().len());
+
+        assert!(emulator.rewind().unwrap());
+        assert!(emulator.cycles() > captured[9]);
+        assert!(emulator.rewind().unwrap());
+        assert!(emulator.cycles() < captured[9]);
+        assert_eq!(emulator.rewind_buffer().len(), 8);
+
+        // 间隔过大时不会溢出
+        emulator.set_rewind(u32::MAX, 1024 * 1024);
+        emulator.run(CYCLES_PER_FRAME).unwrap();
+
+        emulator.set_rewind(1, snapshot_size * 3);
+        for _ in 0..10 {
+            emulator.run(CYCLES_PER_FRAME).unwrap();
+        }
+        assert!(emulator.rewind_buffer().used() <= snapshot_size * 3);
+        while emulator.rewind().unwrap() {}
+        assert!(emulator.rewind_buffer().is_empty());
+    }
+
+    #[test]
+    fn test_run_frame() {
+        let rom = fs::read(ROM_PATH).unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+
+        // 启动时关闭LCD写入VRAM, 先跳过
+        for _ in 0..10 {
+            emulator.run_frame().unwrap();
+        }
+        let mut total = 0;
+        for frame in 0..60 {
+            let res = emulator.run_frame().unwrap();
+            assert!(res.new_frame);
+            assert_eq!(emulator.core().bus.ppu.ly(), PPU_YRES);
+            if frame > 0 {
+                total += res.cycles;
+            }
+        }
+        // 每次停在进入VBlank的指令结束处, 误差不超过一条指令
+        assert!(total.abs_diff(59 * CYCLES_PER_FRAME) <= 24);
+
+        let res = emulator.run_scanline().unwrap();
+        assert!(!res.new_frame);
+        assert!(res.cycles <= PPU_CYCLES_PER_LINE + 24);
+        assert_eq!(emulator.core().bus.ppu.ly(), PPU_YRES + 1);
+
+        let res = emulator.run_cycles(CYCLES_PER_FRAME).unwrap();
+        assert!(res.new_frame && res.cycles >= CYCLES_PER_FRAME);
+    }
+
+    /// 录像后回放, 结束时的状态必须逐位一致
+    fn replay(emulator: &mut HeadlessEmulator, from_state: bool) {
+        emulator
+            .start_recording(from_state, 1_700_000_000_000)
+            .unwrap();
+        let mut seed = 0x1234_5678u32;
+        for _ in 0..300 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            assert!(emulator.movie_frame((seed >> 24) as u8).unwrap());
+            // 录像期间宿主的输入被忽略
+            emulator.update_input(0xFF, 0);
+        }
+        let movie = emulator.stop_movie().unwrap().unwrap();
+        emulator.present();
+        let expected = emulator.save(0).unwrap();
+
+        emulator.run(BASE_CLOCK).unwrap();
+        emulator.start_playback(&movie).unwrap();
+        let mut frames = 0;
+        while emulator.movie_frame(0).unwrap() {
+            frames += 1;
+        }
+        assert_eq!(frames, 300);
+        assert!(!emulator.movie_active());
+        emulator.present();
+        assert!(emulator.save(0).unwrap() == expected);
+    }
+
+    #[test]
+    fn test_movie() {
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        replay(&mut emulator, false);
+        emulator.run(BASE_CLOCK).unwrap();
+        replay(&mut emulator, true);
+
+        // 重新上电录像不清除符号, 录像期间不能加载, 复位或自由运行
+        emulator.load_symbols("00:0150 Start").unwrap();
+        emulator.start_recording(false, 0).unwrap();
+        assert_eq!(emulator.core().bus.debugger.symbols.len(), 1);
+        let rom = fs::read(ROM_PATH).unwrap().into_boxed_slice();
+        let save = emulator.save(0).unwrap();
+        let active = |err: BoxedEmulatorError| matches!(*err, MovieActive);
+        assert!(active(emulator.load_cart(rom, 0).unwrap_err()));
+        assert!(active(emulator.load(&save).unwrap_err()));
+        assert!(active(emulator.reset().unwrap_err()));
+        assert!(active(emulator.run_frame().unwrap_err()));
+        assert!(active(emulator.start_recording(true, 0).unwrap_err()));
+        assert_eq!(emulator.cycles(), 0);
+        emulator.movie_frame(0).unwrap();
+        let movie = emulator.stop_movie().unwrap().unwrap();
+        let rom = fs::read(ROM_PATH).unwrap();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        assert!(matches!(
+            *emulator.start_playback(&movie).unwrap_err(),
+            InvalidMovie { .. }
+        ));
+    }
+
+    #[test]
+    fn test_movie_sram() {
+        let rom = fs::read("../public/roms/Pokemon-Red.gb").unwrap();
+        let mut emulator = HeadlessEmulator::default();
+        emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+        let mut sav = emulator.export_sav().unwrap();
+        sav[0x2000] = 0x42;
+        emulator.import_sav(&sav).unwrap();
+
+        // 重新上电录像和结束录像都保留电池存档
+        emulator.start_recording(false, 0).unwrap();
+        assert_eq!(emulator.export_sav().unwrap(), sav);
+        emulator.movie_frame(0).unwrap();
+        let movie = emulator.stop_movie().unwrap().unwrap();
+        assert_eq!(emulator.export_sav().unwrap()[0x2000], 0x42);
+
+        // 回放从录像开始时的存档出发
+        let mut other = sav.clone();
+        other[0x2000] = 0x00;
+        emulator.import_sav(&other).unwrap();
+        emulator.start_playback(&movie).unwrap();
+        assert_eq!(emulator.export_sav().unwrap(), sav);
     }
 }
@@ -0,0 +1,90 @@
+//! 输入录像格式: 4字节魔数, 小端u16格式版本, 之后是zlib压缩的[`MovieHeader`]和每帧的按键(bincode编码)
+//!
+//! 回放时从相同的初始状态出发, 每帧输入相同的按键, RTC由模拟时间驱动, 因此结果逐位一致
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use super::{BASE_CLOCK, CYCLES_PER_FRAME};
+use crate::{
+    error::{BoxedEmulatorError, EmuErr, EmuResult, InvalidMovie},
+    types::Word,
+};
+
+pub const MOVIE_MAGIC: &[u8; 4] = b"YGBM";
+/// `MovieHeader`的布局或帧的划分变化时递增
+pub const MOVIE_VERSION: u16 = 3;
+
+/// 录像的初始状态
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum MovieStart {
+    /// 重新加载卡带, 从上电开始. 带电池的卡带附带开始时的电池存档
+    PowerOn { sav: Option<Box<[u8]>> },
+    /// 从存档开始
+    State(Box<[u8]>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MovieHeader {
+    pub title: String,
+    pub rom_crc32: u32,
+    /// 第0帧的RTC时间, UNIX毫秒时间戳
+    pub rtc_base: i64,
+    pub start: MovieStart,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    pub header: MovieHeader,
+    /// 每帧传给`Buttons::update`的按键
+    pub inputs: Vec<Word>,
+}
+
+/// 正在录制或回放的录像
+pub(super) enum MovieMode {
+    Recording(Movie),
+    Playing { movie: Movie, frame: usize },
+}
+
+fn invalid(err: impl ToString) -> BoxedEmulatorError {
+    Box::new(InvalidMovie {
+        msg: err.to_string(),
+    })
+}
+
+impl Movie {
+    /// 第`frame`帧开始时的模拟时间, 只取决于帧数而与宿主时间无关
+    pub fn timestamp(&self, frame: usize) -> i64 {
+        let cycles = frame as i64 * CYCLES_PER_FRAME as i64;
+        self.header.rtc_base + cycles * 1000 / BASE_CLOCK as i64
+    }
+
+    pub fn encode(&self) -> EmuResult<Box<[u8]>> {
+        let mut output = MOVIE_MAGIC.to_vec();
+        output.extend_from_slice(&MOVIE_VERSION.to_le_bytes());
+        let mut encoder = ZlibEncoder::new(output, Compression::default());
+        bincode::serialize_into(&mut encoder, &self.header).map_err(invalid)?;
+        bincode::serialize_into(&mut encoder, &self.inputs).map_err(invalid)?;
+        let output = encoder.finish().map_err(invalid)?;
+        Ok(output.into_boxed_slice())
+    }
+
+    pub fn decode(data: &[u8]) -> EmuResult<Self> {
+        const HEADER_SIZE: usize = MOVIE_MAGIC.len() + 2;
+        if data.len() < HEADER_SIZE || &data[..MOVIE_MAGIC.len()] != MOVIE_MAGIC {
+            return EmuErr(InvalidMovie {
+                msg: "not a movie".to_string(),
+            });
+        }
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version != MOVIE_VERSION {
+            return EmuErr(InvalidMovie {
+                msg: format!("incompatible version {version}, expected {MOVIE_VERSION}"),
+            });
+        }
+        let mut input = ZlibDecoder::new(&data[HEADER_SIZE..]);
+        let header = bincode::deserialize_from(&mut input).map_err(invalid)?;
+        let inputs = bincode::deserialize_from(&mut input).map_err(invalid)?;
+        Ok(Self { header, inputs })
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use super::{Core, CYCLES_PER_FRAME};
+use crate::{anyerror, error::EmuResult, types::ClockCycle};
+
+/// 倒带缓冲: 每隔若干帧保存一个压缩的`Core`快照(不含ROM), 超出内存预算时丢弃最旧的快照
+#[derive(Default)]
+pub struct Rewind {
+    /// 快照间隔的时钟周期数
+    interval: ClockCycle,
+    /// 内存预算(字节), 为0时不保存快照
+    budget: usize,
+    used: usize,
+    /// 上一个快照时的周期数
+    last: ClockCycle,
+    snapshots: VecDeque<Box<[u8]>>,
+}
+
+/// 周期计数会回绕, 快照间隔不能超过它的范围
+const MAX_INTERVAL_FRAMES: u32 = ClockCycle::MAX / CYCLES_PER_FRAME;
+
+impl Rewind {
+    /// `interval_frames`限制在1到约17分钟之间
+    pub fn new(interval_frames: u32, budget: usize) -> Self {
+        Self {
+            interval: interval_frames.clamp(1, MAX_INTERVAL_FRAMES) * CYCLES_PER_FRAME,
+            budget,
+            ..Default::default()
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.budget > 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// 快照占用的字节数
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.used = 0;
+    }
+
+    /// 距上一个快照超过间隔时保存快照
+    pub fn capture(&mut self, core: &Core) -> EmuResult {
+        if !self.enabled() || core.cycles.wrapping_sub(self.last) < self.interval {
+            return Ok(());
+        }
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+        if let Err(err) = bincode::serialize_into(&mut encoder, core) {
+            return anyerror!("{err}");
+        }
+        let snapshot = match encoder.finish() {
+            Ok(snapshot) => snapshot.into_boxed_slice(),
+            Err(err) => return anyerror!("{err}"),
+        };
+        self.last = core.cycles;
+        self.used += snapshot.len();
+        self.snapshots.push_back(snapshot);
+        while self.used > self.budget {
+            match self.snapshots.pop_front() {
+                Some(oldest) => self.used -= oldest.len(),
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// 取出最近的快照, 返回的`Core`不含ROM
+    pub fn pop(&mut self) -> EmuResult<Option<Core>> {
+        let Some(snapshot) = self.snapshots.pop_back() else {
+            return Ok(None);
+        };
+        self.used -= snapshot.len();
+        match bincode::deserialize_from::<_, Core>(ZlibDecoder::new(&snapshot[..])) {
+            Ok(core) => {
+                self.last = core.cycles;
+                Ok(Some(core))
+            }
+            Err(err) => anyerror!("{err}"),
+        }
+    }
+}
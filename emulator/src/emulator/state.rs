@@ -0,0 +1,104 @@
+//! 存档格式: 4字节魔数, 小端u16格式版本, 之后是zlib压缩的[`StateInfo`]和[`Core`](bincode编码)
+//!
+//! 元数据位于压缩流开头, 读取时无需解码整个存档. 存档不含ROM
+
+use std::io::Read;
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use super::Core;
+use crate::{
+    dev::ppu::graphic::{ScreenBitmap, PALETTE},
+    error::{
+        BoxedEmulatorError, CorruptedSaveState, EmuErr, EmuResult, IncompatibleSaveState,
+        InvalidSaveState,
+    },
+    types::ClockCycle,
+};
+
+pub const STATE_MAGIC: &[u8; 4] = b"YGBS";
+/// `Core`或`StateInfo`的布局变化时递增
+pub const STATE_VERSION: u16 = 3;
+
+#[allow(non_snake_case)]
+mod tsify_derive {
+    use tsify::Tsify;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Tsify, Debug, Clone)]
+    #[tsify(into_wasm_abi)]
+    #[serde(rename_all = "camelCase")]
+    pub struct StateInfo {
+        pub title: String,
+        pub header_checksum: u8,
+        /// 存档不含ROM, 恢复时要求已加载的ROM与之相同
+        pub rom_crc32: u32,
+        /// 创建时间, UNIX毫秒时间戳
+        pub timestamp: i64,
+        pub cycles: ClockCycle,
+        /// 160x144, 每个像素为调色板下标(0-3)
+        pub thumbnail: Option<Box<[u8]>>,
+    }
+}
+
+pub use tsify_derive::StateInfo;
+
+pub fn thumbnail(frame: &ScreenBitmap) -> Box<[u8]> {
+    frame
+        .iter()
+        .flatten()
+        .map(|pixel| PALETTE.iter().position(|c| c == pixel).unwrap_or(0) as u8)
+        .collect()
+}
+
+fn corrupted(err: impl ToString) -> BoxedEmulatorError {
+    Box::new(CorruptedSaveState {
+        msg: err.to_string(),
+    })
+}
+
+pub fn write(info: &StateInfo, core: &Core) -> EmuResult<Box<[u8]>> {
+    let mut output = STATE_MAGIC.to_vec();
+    output.extend_from_slice(&STATE_VERSION.to_le_bytes());
+    let mut encoder = ZlibEncoder::new(output, Compression::fast());
+    bincode::serialize_into(&mut encoder, info).map_err(corrupted)?;
+    bincode::serialize_into(&mut encoder, core).map_err(corrupted)?;
+    let output = encoder.finish().map_err(corrupted)?;
+    Ok(output.into_boxed_slice())
+}
+
+fn open(save: &[u8]) -> EmuResult<ZlibDecoder<&[u8]>> {
+    const HEADER_SIZE: usize = STATE_MAGIC.len() + 2;
+    if save.len() < HEADER_SIZE || &save[..STATE_MAGIC.len()] != STATE_MAGIC {
+        return EmuErr(InvalidSaveState);
+    }
+    let version = u16::from_le_bytes([save[4], save[5]]);
+    if version != STATE_VERSION {
+        return EmuErr(IncompatibleSaveState {
+            version,
+            expected: STATE_VERSION,
+        });
+    }
+    Ok(ZlibDecoder::new(&save[HEADER_SIZE..]))
+}
+
+pub fn read_info(save: &[u8]) -> EmuResult<StateInfo> {
+    bincode::deserialize_from(open(save)?).map_err(corrupted)
+}
+
+/// 先读出元数据交由`check`检查, 通过后再解码`Core`
+pub fn read(save: &[u8], check: impl FnOnce(&StateInfo) -> EmuResult) -> EmuResult<Core> {
+    let mut input = open(save)?;
+    let info: StateInfo = bincode::deserialize_from(&mut input).map_err(corrupted)?;
+    check(&info)?;
+    let core = bincode::deserialize_from(&mut input).map_err(corrupted)?;
+    // 确认压缩流完整且没有多余的数据
+    let mut rest = Vec::new();
+    input.read_to_end(&mut rest).map_err(corrupted)?;
+    if !rest.is_empty() {
+        return Err(corrupted("trailing data"));
+    }
+    Ok(core)
+}
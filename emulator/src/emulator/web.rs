@@ -0,0 +1,568 @@
+use ::log::error;
+use serde::Serialize;
+use tsify::Tsify;
+use tsify_derive::{
+    CallStack, CheatInfo, CheatList, Disassembly, EmulatorStepInput, EmulatorUpdateInput,
+    ProfileOutput,
+};
+use wasm_bindgen::prelude::*;
+use web_sys::OffscreenCanvasRenderingContext2d;
+
+use super::{
+    state::{self, StateInfo},
+    Emulator,
+};
+use crate::{
+    debug::{disasm::Instruction, Access, Breakpoint, StopReason, Watchpoint},
+    dev::{bus::Region, LoadCartResult},
+    dump::CPUStateDump,
+    error::EmuResult,
+    output::{
+        audio::WebAudioOutput,
+        log::{init_logger, log_flush},
+        screen::{WebScreenOutput, WebTileOutput},
+        serial::WebSerialOutput,
+    },
+    types::ClockCycle,
+};
+
+type WebEmulator = Emulator<WebScreenOutput, WebTileOutput, WebAudioOutput, WebSerialOutput>;
+
+#[wasm_bindgen(js_name = WasmEmulator)]
+pub struct WasmEmulator {
+    emulator: WebEmulator,
+    freq_scale: f64,
+    /// 尚未运行的帧数, 按`freq_scale`累加, 非整数倍速时逐次补足
+    pending_frames: f64,
+    /// 上次通知宿主的震动马达状态
+    rumble: bool,
+}
+
+// Function `__wbg_instanceof_JsType_24d65669860e1289` should have snake_case name, e.g. `__wbg_instanceof_js_type_24d65669860e1289`
+#[allow(non_snake_case)]
+mod tsify_derive {
+    use serde::Deserialize;
+
+    use super::*;
+    #[derive(Serialize, Tsify)]
+    #[tsify(into_wasm_abi)]
+    pub struct EmulatorUpdateResult {
+        pub cycles: ClockCycle,
+        pub cpu: CPUStateDump,
+        pub err: Option<String>,
+        /// 因断点或观察点停止
+        pub stop: Option<String>,
+        /// 震动马达的状态, 只在变化时给出
+        pub rumble: Option<bool>,
+    }
+
+    #[derive(Serialize, Tsify)]
+    #[tsify(into_wasm_abi)]
+    pub struct Disassembly {
+        pub lines: Vec<Instruction>,
+    }
+
+    #[derive(Serialize, Tsify)]
+    #[tsify(into_wasm_abi)]
+    pub struct CallStack {
+        pub frames: Vec<String>,
+    }
+
+    #[derive(Serialize, Tsify)]
+    pub struct CheatInfo {
+        pub id: u32,
+        pub code: String,
+        pub enabled: bool,
+    }
+
+    #[derive(Serialize, Tsify)]
+    #[tsify(into_wasm_abi)]
+    pub struct CheatList {
+        pub cheats: Vec<CheatInfo>,
+    }
+
+    #[derive(Serialize, Tsify)]
+    #[tsify(into_wasm_abi)]
+    pub struct ProfileOutput {
+        pub report: String,
+        pub folded: String,
+    }
+
+    #[derive(Deserialize, Tsify)]
+    #[tsify(from_wasm_abi)]
+    pub struct EmulatorUpdateInput {
+        pub btns: u8,
+        pub timestamp: f64,
+    }
+
+    #[derive(Deserialize, Tsify)]
+    #[tsify(from_wasm_abi)]
+    pub struct EmulatorStepInput {
+        pub btns: u8,
+        pub timestamp: f64,
+    }
+}
+
+pub use tsify_derive::EmulatorUpdateResult;
+
+#[wasm_bindgen(js_class = WasmEmulator)]
+impl WasmEmulator {
+    #[wasm_bindgen(constructor)]
+    pub fn new(freq_scale: f64, volume: f32) -> WasmEmulator {
+        Self {
+            emulator: Emulator::new(
+                WebScreenOutput::new(),
+                WebTileOutput::new(),
+                WebAudioOutput::new(volume),
+                WebSerialOutput::new(),
+            ),
+            freq_scale,
+            pending_frames: 0.0,
+            rumble: false,
+        }
+    }
+
+    #[wasm_bindgen(js_name = initLogger)]
+    pub fn init_logger() {
+        init_logger();
+    }
+
+    #[wasm_bindgen(js_name = update)]
+    pub fn update(
+        &mut self,
+        EmulatorUpdateInput { btns, timestamp }: EmulatorUpdateInput,
+    ) -> EmulatorUpdateResult {
+        self.emulator.update_input(btns, timestamp as _);
+        self.pending_frames += self.freq_scale;
+        let mut res = Ok(None);
+        while self.pending_frames >= 1.0 && matches!(res, Ok(None)) {
+            self.pending_frames -= 1.0;
+            // 录像或回放期间每帧都要经过录像
+            res = if self.emulator.movie_active() {
+                self.emulator.movie_frame(btns).map(|_| None)
+            } else {
+                self.emulator.run_frame().map(|res| res.stop)
+            };
+        }
+        self.emulator.present();
+        if self.freq_scale == 1.0 {
+            self.emulator.audio_output_mut().update();
+        }
+        self.finish(res)
+    }
+
+    #[wasm_bindgen(js_name = step)]
+    pub fn step(
+        &mut self,
+        EmulatorStepInput { btns, timestamp }: EmulatorStepInput,
+    ) -> EmulatorUpdateResult {
+        self.emulator.update_input(btns, timestamp as _);
+        let res = self.emulator.run_cycles(1).map(|res| res.stop);
+        self.emulator.present();
+        self.finish(res)
+    }
+
+    /// 回到上一个快照, 没有快照时不做任何事
+    #[wasm_bindgen(js_name = rewind)]
+    pub fn rewind(&mut self) -> EmulatorUpdateResult {
+        let res = self.emulator.rewind().map(|_| None);
+        self.emulator.present();
+        self.finish(res)
+    }
+
+    #[wasm_bindgen(js_name = setRewind)]
+    pub fn set_rewind(&mut self, interval_frames: u32, budget: usize) {
+        self.emulator.set_rewind(interval_frames, budget);
+    }
+
+    /// 开始录像, `fromState`为`false`时从上电开始
+    #[wasm_bindgen(js_name = startRecording)]
+    pub fn start_recording(&mut self, from_state: bool, timestamp: f64) -> bool {
+        match self.emulator.start_recording(from_state, timestamp as _) {
+            Ok(()) => true,
+            Err(err) => {
+                error!("{err}");
+                false
+            }
+        }
+    }
+
+    #[wasm_bindgen(js_name = startPlayback)]
+    pub fn start_playback(&mut self, movie: Box<[u8]>) -> bool {
+        match self.emulator.start_playback(&movie) {
+            Ok(()) => true,
+            Err(err) => {
+                error!("{err}");
+                false
+            }
+        }
+    }
+
+    #[wasm_bindgen(js_name = movieActive)]
+    pub fn movie_active(&self) -> bool {
+        self.emulator.movie_active()
+    }
+
+    /// 录像或回放时代替`update`每帧调用一次, 回放结束后`movieActive`变为`false`
+    #[wasm_bindgen(js_name = movieFrame)]
+    pub fn movie_frame(&mut self, btns: u8) -> EmulatorUpdateResult {
+        let res = self.emulator.movie_frame(btns).map(|_| None);
+        self.emulator.present();
+        if self.freq_scale == 1.0 {
+            self.emulator.audio_output_mut().update();
+        }
+        self.finish(res)
+    }
+
+    /// 结束录像或回放, 录像时返回录像文件
+    #[wasm_bindgen(js_name = stopMovie)]
+    pub fn stop_movie(&mut self) -> Option<Box<[u8]>> {
+        match self.emulator.stop_movie() {
+            Ok(movie) => movie,
+            Err(err) => {
+                error!("{err}");
+                None
+            }
+        }
+    }
+
+    /// 在`addr`处设置断点, `bank`为负数时不限ROM bank, 返回断点编号
+    #[wasm_bindgen(js_name = addBreakpoint)]
+    pub fn add_breakpoint(&mut self, addr: u16, bank: i32) -> u32 {
+        let breakpoint = match usize::try_from(bank) {
+            Ok(bank) => Breakpoint::at_bank(addr, bank),
+            Err(_) => Breakpoint::at(addr),
+        };
+        self.emulator.debugger_mut().add_breakpoint(breakpoint)
+    }
+
+    /// 在符号处设置断点, 符号不存在时返回`undefined`
+    #[wasm_bindgen(js_name = addBreakpointAt)]
+    pub fn add_breakpoint_at(&mut self, symbol: &str) -> Option<u32> {
+        self.emulator.debugger_mut().add_breakpoint_at(symbol)
+    }
+
+    /// 观察`start..=end`, 返回观察点编号
+    #[wasm_bindgen(js_name = addWatchpoint)]
+    pub fn add_watchpoint(&mut self, start: u16, end: u16, read: bool, write: bool) -> u32 {
+        let access = match (read, write) {
+            (true, true) => Access::ReadWrite,
+            (true, false) => Access::Read,
+            _ => Access::Write,
+        };
+        self.emulator.debugger_mut().add_watchpoint(Watchpoint {
+            range: start..=end,
+            access,
+        })
+    }
+
+    /// 删除断点或观察点
+    #[wasm_bindgen(js_name = removeBreakpoint)]
+    pub fn remove_breakpoint(&mut self, id: u32) -> bool {
+        self.emulator.debugger_mut().remove(id)
+    }
+
+    #[wasm_bindgen(js_name = clearBreakpoints)]
+    pub fn clear_breakpoints(&mut self) {
+        self.emulator.debugger_mut().clear()
+    }
+
+    /// 开始按gameboy-doctor格式跟踪指令, 记录由`takeTrace`分块取走. `doctor`为`true`时LY总是读出0x90
+    #[wasm_bindgen(js_name = startTrace)]
+    pub fn start_trace(&mut self, doctor: bool) {
+        let tracer = &mut self.emulator.debugger_mut().tracer;
+        tracer.start_buffered();
+        tracer.set_doctor(doctor);
+    }
+
+    #[wasm_bindgen(js_name = takeTrace)]
+    pub fn take_trace(&mut self) -> Box<[u8]> {
+        self.emulator
+            .debugger_mut()
+            .tracer
+            .take()
+            .into_boxed_slice()
+    }
+
+    /// 结束跟踪, 返回尚未取走的记录
+    #[wasm_bindgen(js_name = stopTrace)]
+    pub fn stop_trace(&mut self) -> Box<[u8]> {
+        self.emulator
+            .debugger_mut()
+            .tracer
+            .stop()
+            .into_boxed_slice()
+    }
+
+    /// 开始性能分析, 丢弃之前的结果
+    #[wasm_bindgen(js_name = startProfile)]
+    pub fn start_profile(&mut self) {
+        self.emulator.debugger_mut().profiler.start()
+    }
+
+    /// 结束性能分析, 返回前`top`项的热点报告和folded stack, 未开始时返回`undefined`
+    #[wasm_bindgen(js_name = stopProfile)]
+    pub fn stop_profile(&mut self, top: usize) -> Option<ProfileOutput> {
+        let profile = self.emulator.debugger_mut().profiler.stop()?;
+        let symbols = &self.emulator.debugger().symbols;
+        Some(ProfileOutput {
+            report: profile.report(symbols, top),
+            folded: profile.folded(symbols),
+        })
+    }
+
+    /// 加载RGBDS格式的`.sym`符号文件, 需在加载卡带之后调用
+    #[wasm_bindgen(js_name = loadSymbols)]
+    pub fn load_symbols(&mut self, text: &str) -> bool {
+        match self.emulator.load_symbols(text) {
+            Ok(_) => true,
+            Err(err) => {
+                error!("{err}");
+                false
+            }
+        }
+    }
+
+    /// 没有断点, 跟踪和性能分析时也记录调用栈, 关闭时模拟更快
+    #[wasm_bindgen(js_name = setTrackCalls)]
+    pub fn set_track_calls(&mut self, track_calls: bool) {
+        self.emulator.debugger_mut().set_track_calls(track_calls);
+    }
+
+    /// 调用栈, 当前位置在前
+    #[wasm_bindgen(js_name = callStack)]
+    pub fn call_stack(&self) -> CallStack {
+        CallStack {
+            frames: self.emulator.backtrace(),
+        }
+    }
+
+    /// 反汇编PC前`before`条和PC起`after`条指令
+    #[wasm_bindgen(js_name = disassemble)]
+    pub fn disassemble(&self, before: usize, after: usize) -> Disassembly {
+        Disassembly {
+            lines: self.emulator.disassemble(before, after),
+        }
+    }
+
+    /// 添加Game Genie或GameShark金手指, 返回编号, 代码无效时返回`undefined`
+    #[wasm_bindgen(js_name = addCheat)]
+    pub fn add_cheat(&mut self, code: &str) -> Option<u32> {
+        match self.emulator.cheats_mut().add(code) {
+            Ok(id) => Some(id),
+            Err(err) => {
+                error!("{err}");
+                None
+            }
+        }
+    }
+
+    #[wasm_bindgen(js_name = removeCheat)]
+    pub fn remove_cheat(&mut self, id: u32) -> bool {
+        self.emulator.cheats_mut().remove(id)
+    }
+
+    #[wasm_bindgen(js_name = setCheatEnabled)]
+    pub fn set_cheat_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        self.emulator.cheats_mut().set_enabled(id, enabled)
+    }
+
+    #[wasm_bindgen(js_name = clearCheats)]
+    pub fn clear_cheats(&mut self) {
+        self.emulator.cheats_mut().clear()
+    }
+
+    #[wasm_bindgen(js_name = cheats)]
+    pub fn cheats(&self) -> CheatList {
+        let cheats = self.emulator.cheats().iter();
+        CheatList {
+            cheats: cheats
+                .map(|cheat| CheatInfo {
+                    id: cheat.id,
+                    code: cheat.code.clone(),
+                    enabled: cheat.enabled,
+                })
+                .collect(),
+        }
+    }
+
+    /// 无副作用地读取内存, 不触发观察点
+    #[wasm_bindgen(js_name = peek)]
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.emulator.peek(addr)
+    }
+
+    /// 读取指定ROM或外部RAM bank中的地址
+    #[wasm_bindgen(js_name = peekBank)]
+    pub fn peek_bank(&self, bank: usize, addr: u16) -> u8 {
+        self.emulator.peek_bank(bank, addr)
+    }
+
+    /// 直接修改内存, ROM和外部RAM绕过MBC写入当前映射的bank
+    #[wasm_bindgen(js_name = poke)]
+    pub fn poke(&mut self, addr: u16, value: u8) {
+        self.emulator.poke(addr, value)
+    }
+
+    /// 整块读取内存区域: `rom`, `vram`, `sram`(按`bank`), `wram`, `oam`, `hram`
+    #[wasm_bindgen(js_name = readRegion)]
+    pub fn read_region(&self, region: &str, bank: usize) -> Option<Box<[u8]>> {
+        let region = match region {
+            "rom" => Region::Rom,
+            "vram" => Region::Vram,
+            "sram" => Region::CartRam(bank),
+            "wram" => Region::Wram,
+            "oam" => Region::Oam,
+            "hram" => Region::Hram,
+            _ => {
+                error!("unknown memory region: {region}");
+                return None;
+            }
+        };
+        Some(self.emulator.region(region).into())
+    }
+
+    #[wasm_bindgen(js_name = loadCart)]
+    pub fn load_cart(&mut self, rom: Box<[u8]>, timestamp: f64) -> LoadCartResult {
+        let res = self.emulator.load_cart(rom, timestamp as _);
+        if res.is_ok() {
+            self.emulator.audio_output_mut().reset();
+        }
+        res.into()
+    }
+
+    /// 依次应用`patches`(`Uint8Array`数组, IPS/UPS/BPS)后加载卡带
+    #[wasm_bindgen(js_name = loadCartPatched)]
+    pub fn load_cart_patched(
+        &mut self,
+        rom: Box<[u8]>,
+        patches: js_sys::Array,
+        timestamp: f64,
+    ) -> LoadCartResult {
+        let patches: Vec<_> = patches
+            .iter()
+            .map(|patch| js_sys::Uint8Array::new(&patch).to_vec())
+            .collect();
+        let patches: Vec<_> = patches.iter().map(Vec::as_slice).collect();
+        let res = self
+            .emulator
+            .load_cart_patched(rom, &patches, timestamp as _);
+        if res.is_ok() {
+            self.emulator.audio_output_mut().reset();
+        }
+        res.into()
+    }
+
+    #[wasm_bindgen(js_name = save)]
+    pub fn save(&self, timestamp: f64) -> Option<Box<[u8]>> {
+        match self.emulator.save(timestamp as _) {
+            Ok(save) => Some(save),
+            Err(err) => {
+                error!("{err}");
+                None
+            }
+        }
+    }
+
+    #[wasm_bindgen(js_name = load)]
+    pub fn load(&mut self, save: Box<[u8]>) -> bool {
+        match self.emulator.load(&save) {
+            Ok(()) => true,
+            Err(err) => {
+                error!("{err}");
+                false
+            }
+        }
+    }
+
+    #[wasm_bindgen(js_name = stateInfo)]
+    pub fn state_info(save: Box<[u8]>) -> Option<StateInfo> {
+        match state::read_info(&save) {
+            Ok(info) => Some(info),
+            Err(err) => {
+                error!("{err}");
+                None
+            }
+        }
+    }
+
+    #[wasm_bindgen(js_name = exportSav)]
+    pub fn export_sav(&self) -> Option<Box<[u8]>> {
+        match self.emulator.export_sav() {
+            Ok(sav) => Some(sav),
+            Err(err) => {
+                error!("{err}");
+                None
+            }
+        }
+    }
+
+    #[wasm_bindgen(js_name = importSav)]
+    pub fn import_sav(&mut self, sav: Box<[u8]>) -> bool {
+        match self.emulator.import_sav(&sav) {
+            Ok(()) => true,
+            Err(err) => {
+                error!("{err}");
+                false
+            }
+        }
+    }
+
+    #[wasm_bindgen(js_name = reset)]
+    pub fn reset(&mut self) {
+        match self.emulator.reset() {
+            Ok(()) => self.emulator.audio_output_mut().reset(),
+            Err(err) => error!("{err}"),
+        }
+    }
+
+    #[wasm_bindgen(js_name = setScreenCanvas)]
+    pub fn set_screen_canvas(&mut self, canvas: OffscreenCanvasRenderingContext2d) {
+        self.emulator.screen_output_mut().set_canvas(canvas);
+    }
+
+    #[wasm_bindgen(js_name = setTilesCanvas)]
+    pub fn set_tiles_canvas(&mut self, canvas: OffscreenCanvasRenderingContext2d) {
+        self.emulator.tile_output_mut().set_canvas(canvas);
+    }
+
+    #[wasm_bindgen(js_name = setVolume)]
+    pub fn set_volume(&mut self, volume: f32) {
+        self.emulator.audio_output_mut().set_volume(volume);
+    }
+
+    /// MBC7卡带的倾斜, 单位为g, `x`向右为正, `y`向下为正
+    #[wasm_bindgen(js_name = setTilt)]
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.emulator.set_tilt(x, y);
+    }
+
+    #[wasm_bindgen(js_name = setFreqScale)]
+    pub fn set_freq_scale(&mut self, freq_scale: f64) {
+        self.freq_scale = freq_scale;
+        self.pending_frames = 0.0;
+    }
+
+    fn finish(&mut self, res: EmuResult<Option<StopReason>>) -> EmulatorUpdateResult {
+        let (stop, err) = match res {
+            Ok(stop) => (stop.map(|stop| stop.to_string()), None),
+            Err(err) => (None, Some(err.msg())),
+        };
+        let cpu = self.emulator.dump();
+        let cycles = self.emulator.cycles();
+        let rumble = self.emulator.rumble();
+        let rumble = (rumble != self.rumble).then(|| {
+            self.rumble = rumble;
+            rumble
+        });
+        self.emulator.audio_output_mut().clear_buffer();
+        log_flush();
+        EmulatorUpdateResult {
+            cycles,
+            cpu,
+            err,
+            stop,
+            rumble,
+        }
+    }
+}
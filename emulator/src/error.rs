@@ -24,6 +24,37 @@ pub enum EmulatorError {
     InvalidRomSize { size: usize },
     #[error("unknown mbc type")]
     UnknownMBCType,
+    /// 卡带没有电池, 不能导入导出存档
+    #[error("cartridge has no battery")]
+    NoBattery,
+    #[error("invalid save size: expected {expected} bytes, found {actual}")]
+    InvalidSaveSize { expected: usize, actual: usize },
+    /// 魔数不符, 不是本模拟器的存档
+    #[error("not a save state")]
+    InvalidSaveState,
+    #[error("incompatible save state version {version}, expected {expected}")]
+    IncompatibleSaveState { version: u16, expected: u16 },
+    /// 存档与当前卡带不符
+    #[error("save state belongs to {actual:?}, but {expected:?} is loaded")]
+    SaveStateMismatch { expected: String, actual: String },
+    #[error("corrupted save state: {msg}")]
+    CorruptedSaveState { msg: String },
+    #[error("invalid patch: {msg}")]
+    InvalidPatch { msg: String },
+    /// `kind`为`source`, `target`或`patch`
+    #[error("{kind} crc32 mismatch: expected 0x{expected:08X}, found 0x{actual:08X}")]
+    PatchCrcMismatch {
+        kind: &'static str,
+        expected: u32,
+        actual: u32,
+    },
+    #[error("invalid cheat code: {code:?}")]
+    InvalidCheat { code: String },
+    #[error("invalid movie: {msg}")]
+    InvalidMovie { msg: String },
+    /// 录像或回放期间不能加载, 复位或在录像之外运行
+    #[error("a movie is being recorded or played back, stop it first")]
+    MovieActive,
     #[error("{msg}")]
     AnyError { msg: String },
 }
@@ -36,10 +67,7 @@ impl EmulatorError {
     }
 
     pub fn is_stop(&self) -> bool {
-        match self {
-            EmulatorError::StopInstruction { .. } => true,
-            _ => false,
-        }
+        matches!(self, EmulatorError::StopInstruction { .. })
     }
 }
 
@@ -56,7 +84,7 @@ pub type EmuResult<T = (), E = BoxedEmulatorError> = std::result::Result<T, E>;
 macro_rules! anyerror {
     ($($arg:tt)*) => {{
         let msg = format!($($arg)*);
-        crate::error::EmuErr(crate::error::EmulatorError::AnyError { msg })
+        $crate::error::EmuErr($crate::error::EmulatorError::AnyError { msg })
     }};
 }
 
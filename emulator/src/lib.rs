@@ -1,11 +1,10 @@
-#![feature(exact_size_is_empty)]
 #![feature(slice_from_ptr_range)]
-#![feature(generic_arg_infer)]
-#![feature(let_chains)]
+pub mod debug;
 pub mod dev;
 pub mod dump;
 pub mod emulator;
 pub mod error;
+#[cfg(target_arch = "wasm32")]
 pub mod external;
 pub mod output;
 pub mod types;
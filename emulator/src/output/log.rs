@@ -1,6 +1,6 @@
 use std::cell::UnsafeCell;
 
-use crate::external::{console_log, emulator_log_callback};
+use crate::external::emulator_log_callback;
 use log::{Level, LevelFilter, Log};
 use serde::Serialize;
 use tsify::{JsValueSerdeExt, Tsify};
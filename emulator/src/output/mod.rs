@@ -1,4 +1,5 @@
 pub mod audio;
+#[cfg(target_arch = "wasm32")]
 pub mod log;
 pub mod screen;
 pub mod serial;
@@ -1,95 +1,182 @@
-use web_sys::OffscreenCanvasRenderingContext2d;
-
-use crate::{
-    dev::ppu::graphic::{
-        decode_tiles, RGBAPalette, RawTileMatrix, ScreenBitmap, TilesBitmap, NO_COLOR,
-        SCREEN_HEIGHT, SCREEN_WIDTH, TILES_HEIGHT, TILES_WIDTH,
-    },
-    utils::bytes::as_bytes,
-};
+use crate::dev::ppu::graphic::{RGBAPalette, RawTileMatrix, ScreenBitmap, NO_COLOR};
+
+#[cfg(target_arch = "wasm32")]
+pub use web::{WebScreenOutput, WebTileOutput};
+
 pub trait ScreenOutput {
     fn put_screen(&mut self, idx: u8);
     fn buffer(&mut self, idx: u8) -> &mut ScreenBitmap;
+
+    /// 最近一次呈现的完整帧, 用作存档缩略图
+    fn last_frame(&self) -> Option<&ScreenBitmap> {
+        None
+    }
+}
+
+pub trait TileOutput {
+    fn put_tile(&mut self, tiles: &RawTileMatrix, palette: &RGBAPalette);
 }
-pub struct WebScreenOutput {
-    canvas: Option<OffscreenCanvasRenderingContext2d>,
+
+/// 不依赖宿主环境的双缓冲屏幕输出, 最近一次`put_screen`的缓冲区即为当前帧
+pub struct BufferedScreenOutput {
     screen_buffers: [Box<ScreenBitmap>; 2],
+    front: u8,
 }
-impl WebScreenOutput {
-    pub fn new() -> Self {
+
+impl Default for BufferedScreenOutput {
+    fn default() -> Self {
         Self {
-            canvas: None,
             screen_buffers: [
                 Box::new([[NO_COLOR; 160]; 144]),
                 Box::new([[NO_COLOR; 160]; 144]),
             ],
+            front: 0,
         }
     }
+}
+
+impl BufferedScreenOutput {
+    pub fn new() -> Self {
+        Default::default()
+    }
 
-    pub fn set_canvas(&mut self, canvas: OffscreenCanvasRenderingContext2d) {
-        self.canvas = Some(canvas);
+    /// 最近一次呈现的完整帧
+    pub fn frame(&self) -> &ScreenBitmap {
+        &self.screen_buffers[self.front as usize]
     }
 }
 
-impl ScreenOutput for WebScreenOutput {
+impl ScreenOutput for BufferedScreenOutput {
     fn put_screen(&mut self, idx: u8) {
-        let u8s = unsafe {
-            js_sys::Uint8ClampedArray::view(as_bytes::<ScreenBitmap>(
-                self.screen_buffers.get_unchecked(idx as usize).as_ref(),
-            ))
-        };
-        let image_data = web_sys::ImageData::new_with_js_u8_clamped_array_and_sh(
-            &u8s,
-            SCREEN_WIDTH as _,
-            SCREEN_HEIGHT as _,
-        )
-        .unwrap();
-        if let Some(canvas) = &self.canvas {
-            canvas.put_image_data(&image_data, 0.0, 0.0).unwrap();
-        }
+        self.front = idx;
     }
 
     fn buffer(&mut self, idx: u8) -> &mut ScreenBitmap {
-        unsafe { self.screen_buffers.get_unchecked_mut(idx as usize).as_mut() }
+        &mut self.screen_buffers[idx as usize]
     }
-}
 
-pub trait TileOutput {
-    fn put_tile(&mut self, tiles: &RawTileMatrix, palette: &RGBAPalette);
+    fn last_frame(&self) -> Option<&ScreenBitmap> {
+        Some(self.frame())
+    }
 }
 
-pub struct WebTileOutput {
-    canvas: Option<OffscreenCanvasRenderingContext2d>,
-    tiles_buffer: Box<TilesBitmap>,
+/// 丢弃图块数据
+#[derive(Default)]
+pub struct NullTileOutput;
+
+impl TileOutput for NullTileOutput {
+    fn put_tile(&mut self, _: &RawTileMatrix, _: &RGBAPalette) {}
 }
 
-impl WebTileOutput {
-    pub fn new() -> Self {
-        Self {
-            canvas: None,
-            tiles_buffer: Box::new([[NO_COLOR; 128]; 192]),
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use web_sys::OffscreenCanvasRenderingContext2d;
+
+    use super::{ScreenOutput, TileOutput};
+    use crate::{
+        dev::ppu::graphic::{
+            decode_tiles, RGBAPalette, RawTileMatrix, ScreenBitmap, TilesBitmap, NO_COLOR,
+            SCREEN_HEIGHT, SCREEN_WIDTH, TILES_HEIGHT, TILES_WIDTH,
+        },
+        utils::bytes::as_bytes,
+    };
+
+    pub struct WebScreenOutput {
+        canvas: Option<OffscreenCanvasRenderingContext2d>,
+        screen_buffers: [Box<ScreenBitmap>; 2],
+        front: u8,
+    }
+
+    impl Default for WebScreenOutput {
+        fn default() -> Self {
+            Self {
+                canvas: None,
+                screen_buffers: [
+                    Box::new([[NO_COLOR; 160]; 144]),
+                    Box::new([[NO_COLOR; 160]; 144]),
+                ],
+                front: 0,
+            }
         }
     }
 
-    pub fn set_canvas(&mut self, canvas: OffscreenCanvasRenderingContext2d) {
-        self.canvas = Some(canvas);
+    impl WebScreenOutput {
+        pub fn new() -> Self {
+            Default::default()
+        }
+
+        pub fn set_canvas(&mut self, canvas: OffscreenCanvasRenderingContext2d) {
+            self.canvas = Some(canvas);
+        }
+    }
+
+    impl ScreenOutput for WebScreenOutput {
+        fn put_screen(&mut self, idx: u8) {
+            self.front = idx;
+            let u8s = unsafe {
+                js_sys::Uint8ClampedArray::view(as_bytes::<ScreenBitmap>(
+                    self.screen_buffers.get_unchecked(idx as usize).as_ref(),
+                ))
+            };
+            let image_data = web_sys::ImageData::new_with_js_u8_clamped_array_and_sh(
+                &u8s,
+                SCREEN_WIDTH as _,
+                SCREEN_HEIGHT as _,
+            )
+            .unwrap();
+            if let Some(canvas) = &self.canvas {
+                canvas.put_image_data(&image_data, 0.0, 0.0).unwrap();
+            }
+        }
+
+        fn buffer(&mut self, idx: u8) -> &mut ScreenBitmap {
+            unsafe { self.screen_buffers.get_unchecked_mut(idx as usize).as_mut() }
+        }
+
+        fn last_frame(&self) -> Option<&ScreenBitmap> {
+            Some(&self.screen_buffers[self.front as usize])
+        }
+    }
+
+    pub struct WebTileOutput {
+        canvas: Option<OffscreenCanvasRenderingContext2d>,
+        tiles_buffer: Box<TilesBitmap>,
+    }
+
+    impl Default for WebTileOutput {
+        fn default() -> Self {
+            Self {
+                canvas: None,
+                tiles_buffer: Box::new([[NO_COLOR; 128]; 192]),
+            }
+        }
+    }
+
+    impl WebTileOutput {
+        pub fn new() -> Self {
+            Default::default()
+        }
+
+        pub fn set_canvas(&mut self, canvas: OffscreenCanvasRenderingContext2d) {
+            self.canvas = Some(canvas);
+        }
     }
-}
 
-impl TileOutput for WebTileOutput {
-    fn put_tile(&mut self, tiles: &RawTileMatrix, palette: &RGBAPalette) {
-        decode_tiles(tiles, palette, &mut self.tiles_buffer);
-        let u8s = unsafe {
-            js_sys::Uint8ClampedArray::view(as_bytes::<TilesBitmap>(self.tiles_buffer.as_ref()))
-        };
-        let image_data = web_sys::ImageData::new_with_js_u8_clamped_array_and_sh(
-            &u8s,
-            TILES_WIDTH as _,
-            TILES_HEIGHT as _,
-        )
-        .unwrap();
-        if let Some(canvas) = &self.canvas {
-            canvas.put_image_data(&image_data, 0.0, 0.0).unwrap();
+    impl TileOutput for WebTileOutput {
+        fn put_tile(&mut self, tiles: &RawTileMatrix, palette: &RGBAPalette) {
+            decode_tiles(tiles, palette, &mut self.tiles_buffer);
+            let u8s = unsafe {
+                js_sys::Uint8ClampedArray::view(as_bytes::<TilesBitmap>(self.tiles_buffer.as_ref()))
+            };
+            let image_data = web_sys::ImageData::new_with_js_u8_clamped_array_and_sh(
+                &u8s,
+                TILES_WIDTH as _,
+                TILES_HEIGHT as _,
+            )
+            .unwrap();
+            if let Some(canvas) = &self.canvas {
+                canvas.put_image_data(&image_data, 0.0, 0.0).unwrap();
+            }
         }
     }
 }
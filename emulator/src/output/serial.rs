@@ -1,31 +1,65 @@
-use crate::external::emulator_serial_callback;
+#[cfg(target_arch = "wasm32")]
+pub use web::WebSerialOutput;
 
 pub trait SerialOutput {
     fn put_serial(&mut self, data: u8);
     fn flush(&mut self);
 }
 
-pub struct WebSerialOutput(Vec<u8>);
+/// 将串口发送的全部字节保存在内存中
+#[derive(Default)]
+pub struct BufferedSerialOutput(Vec<u8>);
 
-impl WebSerialOutput {
+impl BufferedSerialOutput {
     pub fn new() -> Self {
-        Self(Vec::new())
+        Default::default()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear()
     }
 }
 
-impl SerialOutput for WebSerialOutput {
+impl SerialOutput for BufferedSerialOutput {
     fn put_serial(&mut self, data: u8) {
         self.0.push(data);
-        if self.0.len() > 128 {
-            emulator_serial_callback(&self.0);
-            self.0.clear();
+    }
+
+    fn flush(&mut self) {}
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use super::SerialOutput;
+    use crate::external::emulator_serial_callback;
+
+    #[derive(Default)]
+    pub struct WebSerialOutput(Vec<u8>);
+
+    impl WebSerialOutput {
+        pub fn new() -> Self {
+            Default::default()
         }
     }
 
-    fn flush(&mut self) {
-        if self.0.len() > 0 {
-            emulator_serial_callback(&self.0);
-            self.0.clear();
+    impl SerialOutput for WebSerialOutput {
+        fn put_serial(&mut self, data: u8) {
+            self.0.push(data);
+            if self.0.len() > 128 {
+                emulator_serial_callback(&self.0);
+                self.0.clear();
+            }
+        }
+
+        fn flush(&mut self) {
+            if !self.0.is_empty() {
+                emulator_serial_callback(&self.0);
+                self.0.clear();
+            }
         }
     }
 }
@@ -10,16 +10,22 @@ pub fn as_bytes<T: Sized>(x: &T) -> &[u8] {
     unsafe { &*slice_from_raw_parts(x as *const _ as *const u8, size_of::<T>()) }
 }
 
+/// # Safety
+/// 任意字节序列都必须是`T`的合法值
 #[inline]
 pub unsafe fn as_bytes_mut<T: Sized>(x: &mut T) -> &mut [u8] {
     &mut *slice_from_raw_parts_mut(x as *mut _ as *mut u8, size_of::<T>())
 }
 
+/// # Safety
+/// `x`的长度和对齐必须满足`T`的要求, 且内容是`T`的合法值
 #[inline]
 pub unsafe fn from_bytes<T: Sized>(x: &[u8]) -> &T {
     &*(x.as_ptr() as *const _)
 }
 
+/// # Safety
+/// `x`的长度和对齐必须满足`T`的要求, 且内容是`T`的合法值
 #[inline]
 pub unsafe fn from_bytes_mut<T: Sized>(x: &mut [u8]) -> &mut T {
     &mut *(x.as_mut_ptr() as *mut _)
@@ -33,11 +39,15 @@ pub fn slice_as_bytes<T: Sized>(x: &[T]) -> &[u8] {
     unsafe { slice::from_ptr_range(start..end) }
 }
 
+/// # Safety
+/// `bytes`的长度必须等于`size_of::<T>()`, 且内容是`T`的合法值
 #[inline]
 pub unsafe fn bytes_to_value<T: Sized>(bytes: Box<[u8]>) -> Box<T> {
     Box::from_raw(Box::into_raw(bytes) as *mut _)
 }
 
+/// # Safety
+/// `bytes`的长度必须是`size_of::<T>()`的整数倍, 且内容是`T`的合法值
 #[inline]
 pub unsafe fn bytes_to_slice<T: Sized>(mut bytes: Box<[u8]>) -> Box<[T]> {
     let Range { start, end } = bytes.as_mut_ptr_range();
@@ -0,0 +1,106 @@
+//! Blargg测试ROM, 根据串口输出的"Passed"/"Failed"判断结果
+//!
+//! ROM按文件名在`../public/roms`下递归查找, 可用环境变量`BLARGG_ROM_DIR`指定其他目录.
+//! 不随仓库提供的ROM默认忽略, 放入ROM后用`cargo test --test blargg -- --ignored`运行, 找不到ROM时失败
+
+mod common;
+
+use std::fs;
+
+use common::{contains, find_rom, rom_dir};
+use emulator::emulator::{HeadlessEmulator, CYCLES_PER_FRAME};
+
+const DEFAULT_ROM_DIR: &str = "../public/roms";
+/// 检测到结果后再运行的帧数, 保证失败信息完整输出
+const TRAILING_FRAMES: u32 = 30;
+
+/// 合并ROM输出`01:ok  02:05 ...`, 单项ROM输出`Failed #n`
+fn failed_subtests(output: &str) -> Vec<u32> {
+    let mut failed: Vec<u32> = output
+        .split_whitespace()
+        .filter_map(|token| token.split_once(':'))
+        .filter(|(_, result)| *result != "ok")
+        .filter_map(|(num, _)| num.parse().ok())
+        .collect();
+    if failed.is_empty() {
+        if let Some((_, rest)) = output.split_once("Failed #") {
+            failed.extend(
+                rest.split(|c: char| !c.is_ascii_digit())
+                    .next()
+                    .and_then(|n| n.parse::<u32>().ok()),
+            );
+        }
+    }
+    failed
+}
+
+fn run_blargg(name: &str, timeout_frames: u32) {
+    let dir = rom_dir("BLARGG_ROM_DIR", DEFAULT_ROM_DIR);
+    let Some(path) = find_rom(&dir, name) else {
+        panic!("{name}: not found in {}", dir.display());
+    };
+    let rom = fs::read(&path).unwrap();
+    let mut emulator = HeadlessEmulator::default();
+    emulator.load_cart(rom.into_boxed_slice(), 0).unwrap();
+
+    let res = emulator
+        .run_until(timeout_frames * CYCLES_PER_FRAME, |emu| {
+            let output = emu.serial_output().bytes();
+            contains(output, b"Passed") || contains(output, b"Failed")
+        })
+        .and_then(|done| {
+            if done {
+                emulator.run(TRAILING_FRAMES * CYCLES_PER_FRAME)?;
+            }
+            Ok(done)
+        });
+    let output = String::from_utf8_lossy(emulator.serial_output().bytes()).into_owned();
+    match res {
+        Err(err) => panic!("{}: emulator error: {err}\n{output}", name),
+        Ok(false) => panic!(
+            "{}: no result within {timeout_frames} frames\n{output}",
+            name
+        ),
+        Ok(true) if output.contains("Failed") => panic!(
+            "{}: failed sub-tests {:?}\n{output}",
+            name,
+            failed_subtests(&output)
+        ),
+        Ok(true) => {}
+    }
+}
+
+macro_rules! blargg_tests {
+    ($($(#[$meta:meta])* $test:ident: $rom:literal, $frames:literal;)*) => {
+        $(
+            $(#[$meta])*
+            #[test]
+            fn $test() {
+                run_blargg($rom, $frames);
+            }
+        )*
+    };
+}
+
+blargg_tests! {
+    #[ignore = "ROM not in repository"]
+    cpu_instrs: "cpu_instrs.gb", 4000;
+    cpu_instrs_01_special: "01-special.gb", 600;
+    cpu_instrs_02_interrupts: "02-interrupts.gb", 300;
+    cpu_instrs_03_op_sp_hl: "03-op sp,hl.gb", 600;
+    cpu_instrs_04_op_r_imm: "04-op r,imm.gb", 600;
+    cpu_instrs_05_op_rp: "05-op rp.gb", 800;
+    cpu_instrs_06_ld_r_r: "06-ld r,r.gb", 300;
+    cpu_instrs_07_jr_jp_call_ret_rst: "07-jr,jp,call,ret,rst.gb", 300;
+    cpu_instrs_08_misc_instrs: "08-misc instrs.gb", 300;
+    cpu_instrs_09_op_r_r: "09-op r,r.gb", 1500;
+    cpu_instrs_10_bit_ops: "10-bit ops.gb", 2000;
+    cpu_instrs_11_op_a_hl: "11-op a,(hl).gb", 2500;
+    #[ignore = "ROM not in repository"]
+    instr_timing: "instr_timing.gb", 600;
+    // 访存发生在指令的最后一个M周期, 尚未按M周期模拟
+    #[ignore = "memory access timing is not cycle accurate yet"]
+    mem_timing: "mem_timing.gb", 600;
+    #[ignore = "ROM not in repository"]
+    halt_bug: "halt_bug.gb", 600;
+}
@@ -0,0 +1,41 @@
+//! 测试ROM工具函数, 各集成测试共用
+
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// 优先使用环境变量`var`指定的目录
+pub fn rom_dir(var: &str, default: &str) -> PathBuf {
+    env::var_os(var).map_or_else(|| PathBuf::from(default), PathBuf::from)
+}
+
+/// 按文件名递归查找ROM
+pub fn find_rom(dir: &Path, name: &str) -> Option<PathBuf> {
+    collect_roms(dir)
+        .into_iter()
+        .find(|p| p.file_name().is_some_and(|n| n == name))
+}
+
+/// 递归列出目录下全部`.gb`文件, 按路径排序
+pub fn collect_roms(dir: &Path) -> Vec<PathBuf> {
+    let mut roms = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return roms;
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            roms.extend(collect_roms(&path));
+        } else if path.extension().is_some_and(|ext| ext == "gb") {
+            roms.push(path);
+        }
+    }
+    roms.sort();
+    roms
+}
+
+pub fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
@@ -0,0 +1,110 @@
+//! Mooneye测试ROM, 结束时执行`LD B,B`(0x40), B/C/D/E/H/L为3/5/8/13/21/34表示通过
+//!
+//! 运行`../public/roms/mooneye`下的全部ROM, 可用环境变量`MOONEYE_ROM_DIR`指定其他目录. 只运行适用于DMG的ROM.
+//! ROM不随仓库提供, 默认忽略, 放入ROM后用`cargo test --test mooneye -- --ignored`运行, 没有ROM时失败
+
+mod common;
+
+use std::{fmt, fs, path::Path};
+
+use common::{collect_roms, rom_dir};
+use emulator::{
+    dump::CPUStateDump,
+    emulator::{HeadlessEmulator, CYCLES_PER_FRAME},
+};
+
+const DEFAULT_ROM_DIR: &str = "../public/roms/mooneye";
+const TIMEOUT_FRAMES: u32 = 1200;
+const LD_B_B: u8 = 0x40;
+const FIBONACCI: [u8; 6] = [3, 5, 8, 13, 21, 34];
+
+enum Outcome {
+    Passed,
+    Failed(CPUStateDump),
+    Timeout,
+    Error(String),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Passed => write!(f, "passed"),
+            Outcome::Failed(r) => write!(
+                f,
+                "failed (B={:02X} C={:02X} D={:02X} E={:02X} H={:02X} L={:02X})",
+                r.b, r.c, r.d, r.e, r.h, r.l
+            ),
+            Outcome::Timeout => write!(f, "no result within {TIMEOUT_FRAMES} frames"),
+            Outcome::Error(err) => write!(f, "emulator error: {err}"),
+        }
+    }
+}
+
+/// 文件名后缀标明适用机型, 如`-GS`, `-dmgABCmgb`, `-cgb`; 无后缀的ROM适用于所有机型
+fn runs_on_dmg(path: &Path) -> bool {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let Some((_, models)) = stem.rsplit_once('-') else {
+        return true;
+    };
+    if !models.is_empty() && models.chars().all(|c| "GSCA".contains(c)) {
+        models.contains('G')
+    } else if ["dmg", "mgb", "sgb", "cgb", "agb", "ags"]
+        .iter()
+        .any(|m| models.starts_with(m))
+    {
+        models.contains("dmgABC")
+    } else {
+        true
+    }
+}
+
+fn run_mooneye(path: &Path) -> Outcome {
+    let rom = fs::read(path).unwrap();
+    let mut emulator = HeadlessEmulator::default();
+    if let Err(err) = emulator.load_cart(rom.into_boxed_slice(), 0) {
+        return Outcome::Error(err.to_string());
+    }
+    let res = emulator.run_until(TIMEOUT_FRAMES * CYCLES_PER_FRAME, |emu| {
+        emu.peek(emu.core().cpu.pc()) == LD_B_B
+    });
+    match res {
+        Err(err) => Outcome::Error(err.to_string()),
+        Ok(false) => Outcome::Timeout,
+        Ok(true) => {
+            let regs = emulator.dump();
+            if [regs.b, regs.c, regs.d, regs.e, regs.h, regs.l] == FIBONACCI {
+                Outcome::Passed
+            } else {
+                Outcome::Failed(regs)
+            }
+        }
+    }
+}
+
+#[test]
+#[ignore = "put the mooneye test ROMs in ../public/roms/mooneye or set MOONEYE_ROM_DIR"]
+fn mooneye() {
+    let dir = rom_dir("MOONEYE_ROM_DIR", DEFAULT_ROM_DIR);
+    let roms: Vec<_> = collect_roms(&dir)
+        .into_iter()
+        .filter(|p| runs_on_dmg(p))
+        .collect();
+    assert!(!roms.is_empty(), "no mooneye ROMs in {}", dir.display());
+
+    let mut failed = Vec::new();
+    for path in &roms {
+        let name = path.strip_prefix(&dir).unwrap_or(path).display();
+        let outcome = run_mooneye(path);
+        println!("{name}: {outcome}");
+        if !matches!(outcome, Outcome::Passed) {
+            failed.push(name.to_string());
+        }
+    }
+    assert!(
+        failed.is_empty(),
+        "{} of {} mooneye ROMs failed: {:#?}",
+        failed.len(),
+        roms.len(),
+        failed
+    );
+}
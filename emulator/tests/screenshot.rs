@@ -0,0 +1,143 @@
+//! 截图回归测试, 运行固定帧数后与参考PNG逐像素比较
+//!
+//! 画面和参考图都映射为4级灰度后比较, 因此可以直接使用测试ROM附带的参考图.
+//! 参考图位于`tests/screenshots`, 应使用测试ROM作者发布的参考图; 没有官方参考图的ROM可以设置环境变量
+//! `UPDATE_SCREENSHOTS=1`用当前画面生成参考图. 比较失败时当前画面写入`target/screenshots`
+//!
+//! dmg-acid2的参考图为上游仓库的`img/reference-dmg.png`(https://github.com/mattcurrie/dmg-acid2),
+//! 放入`tests/screenshots`后用`cargo test -- --ignored`运行
+
+mod common;
+
+use std::{
+    env,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use common::{find_rom, rom_dir};
+use emulator::{
+    dev::ppu::graphic::{ScreenBitmap, PALETTE, SCREEN_HEIGHT, SCREEN_WIDTH},
+    emulator::HeadlessEmulator,
+};
+
+const DEFAULT_ROM_DIR: &str = "../public/roms";
+const REFERENCE_DIR: &str = "tests/screenshots";
+const OUTPUT_DIR: &str = "target/screenshots";
+/// 参考图使用的4级灰度, 从白到黑
+const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+type Shades = Vec<u8>;
+
+/// 调色板中的下标即灰度等级, 不在调色板中的像素(如关闭LCD时)视为白色
+fn screen_shades(bitmap: &ScreenBitmap) -> Shades {
+    bitmap
+        .iter()
+        .flatten()
+        .map(|pixel| PALETTE.iter().position(|c| c == pixel).unwrap_or(0) as u8)
+        .collect()
+}
+
+fn read_reference(path: &Path) -> Shades {
+    let mut decoder = png::Decoder::new(File::open(path).unwrap());
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    assert_eq!(
+        (info.width as usize, info.height as usize),
+        (SCREEN_WIDTH, SCREEN_HEIGHT),
+        "{}: unexpected size",
+        path.display()
+    );
+    let channels = info.color_type.samples();
+    buf[..info.buffer_size()]
+        .chunks(channels)
+        .map(|px| {
+            let luma = if channels >= 3 {
+                (px[0] as u32 * 299 + px[1] as u32 * 587 + px[2] as u32 * 114) / 1000
+            } else {
+                px[0] as u32
+            };
+            // 按亮度就近取灰度等级
+            ((255 - luma + 42) / 85).min(3) as u8
+        })
+        .collect()
+}
+
+fn write_shades(path: &Path, shades: &Shades) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let file = File::create(path).unwrap();
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        SCREEN_WIDTH as u32,
+        SCREEN_HEIGHT as u32,
+    );
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let data: Vec<u8> = shades.iter().map(|&s| SHADES[s as usize]).collect();
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&data).unwrap();
+}
+
+fn run_screenshot(rom: &str, frames: u32, reference: &str) {
+    let dir = rom_dir("SCREENSHOT_ROM_DIR", DEFAULT_ROM_DIR);
+    let Some(path) = find_rom(&dir, rom) else {
+        panic!("{rom}: not found in {}", dir.display());
+    };
+    let mut emulator = HeadlessEmulator::default();
+    emulator
+        .load_cart(fs::read(path).unwrap().into_boxed_slice(), 0)
+        .unwrap();
+    for _ in 0..frames {
+        emulator.run_frame().unwrap();
+    }
+    emulator.present();
+    let actual = screen_shades(emulator.screen_output().frame());
+
+    let reference_path = PathBuf::from(REFERENCE_DIR).join(reference);
+    if env::var_os("UPDATE_SCREENSHOTS").is_some() {
+        write_shades(&reference_path, &actual);
+        return;
+    }
+    assert!(
+        reference_path.exists(),
+        "{}: missing reference, use the image published with the test ROM",
+        reference_path.display()
+    );
+    let expected = read_reference(&reference_path);
+
+    let mismatches: Vec<_> = (0..actual.len())
+        .filter(|&i| actual[i] != expected[i])
+        .map(|i| (i % SCREEN_WIDTH, i / SCREEN_WIDTH))
+        .collect();
+    if let Some(&(x, y)) = mismatches.first() {
+        let output_path = PathBuf::from(OUTPUT_DIR).join(reference);
+        write_shades(&output_path, &actual);
+        panic!(
+            "{}: {} pixels differ from {}, first at ({x}, {y}); actual screen written to {}",
+            rom,
+            mismatches.len(),
+            reference_path.display(),
+            output_path.display()
+        );
+    }
+}
+
+macro_rules! screenshot_tests {
+    ($($(#[$meta:meta])* $test:ident: $rom:literal, $frames:literal, $reference:literal;)*) => {
+        $(
+            $(#[$meta])*
+            #[test]
+            fn $test() {
+                run_screenshot($rom, $frames, $reference);
+            }
+        )*
+    };
+}
+
+screenshot_tests! {
+    #[ignore = "put dmg-acid2's upstream img/reference-dmg.png in tests/screenshots"]
+    dmg_acid2: "dmg-acid2.gb", 60, "reference-dmg.png";
+}
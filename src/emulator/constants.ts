@@ -41,9 +41,9 @@ export const LOG_STR = ['Off', 'Error', 'Warn', 'Info', 'Debug'] as const satisf
 >
 
 export const BASE_FREQ_HZ = 4_194_304
-export const VISUAL_FREQ_HZ = 59.7
+export const CYCLES_PER_FRAME = 70224
+export const VISUAL_FREQ_HZ = BASE_FREQ_HZ / CYCLES_PER_FRAME
 export const MS_PER_FRAME = 1000 / VISUAL_FREQ_HZ
-export const CYCLES_PER_FRAME = BASE_FREQ_HZ / VISUAL_FREQ_HZ
 export const DEFAULT_VOLUME = 50
 
 export const Ok = Status.Ok
@@ -1,5 +1,10 @@
 import { shallowRef, type ShallowRef } from 'vue'
-import { GameboyLayoutButton, type GameboyLayoutButtons, type Callback } from './constants'
+import {
+  GameboyLayoutButton,
+  type GameboyLayoutButtons,
+  type Callback,
+  type TiltCallback
+} from './constants'
 import { Config } from '@/emulator/config'
 import { every } from '@/utils/timer'
 import logger from '@/emulator/logger'
@@ -50,7 +55,12 @@ export const DEFAULT_BUTTON_MAPPINGS = [
 
 export class PhysicalGamepad {
   private static readonly POLL_INTERVAL = 1000 / 60
+  // 马达关闭时会提前停止, 足够长即可
+  private static readonly RUMBLE_DURATION = 5000
+  // 摇杆变化超过该值才更新倾斜
+  private static readonly TILT_THRESHOLD = 0.02
   private buttons: GameboyLayoutButtons = 0
+  private tilt: [number, number] = [0, 0]
   private gamepad?: Gamepad
   public readonly mapping: ShallowRef<GamepadMapping>
   public readonly gamepadId = shallowRef('none')
@@ -86,10 +96,36 @@ export class PhysicalGamepad {
     return btns
   }
 
+  // 左摇杆作为MBC7卡带的倾斜, 推到底为1g
+  private newTilt(): [number, number] {
+    if (this.gamepad === undefined || !this.gamepad.connected || this.gamepad.axes.length < 2) {
+      return [0, 0]
+    }
+    const [x, y] = this.gamepad.axes
+    return [x, y]
+  }
+
   private hasChanged(newButtons: GameboyLayoutButtons): boolean {
     return (newButtons ^ this.buttons) !== 0
   }
 
+  // 卡带的震动马达, 开启时持续震动直到关闭
+  public rumble(on: boolean) {
+    const actuator = this.gamepad?.vibrationActuator
+    if (actuator === undefined || actuator === null) {
+      return
+    }
+    if (on) {
+      actuator.playEffect('dual-rumble', {
+        duration: PhysicalGamepad.RUMBLE_DURATION,
+        strongMagnitude: 1.0,
+        weakMagnitude: 1.0
+      })
+    } else {
+      actuator.reset()
+    }
+  }
+
   private poll() {
     const newButtons = this.newButtons()
     const hasChanged = this.hasChanged(newButtons)
@@ -97,11 +133,18 @@ export class PhysicalGamepad {
       this.buttons = newButtons
       this.callback(newButtons)
     }
+    const [x, y] = this.newTilt()
+    const threshold = PhysicalGamepad.TILT_THRESHOLD
+    if (Math.abs(x - this.tilt[0]) > threshold || Math.abs(y - this.tilt[1]) > threshold) {
+      this.tilt = [x, y]
+      this.tiltCallback(x, y)
+    }
   }
 
   constructor(
     config: Config,
-    private callback: Callback
+    private callback: Callback,
+    private tiltCallback: TiltCallback
   ) {
     window.addEventListener('gamepadconnected', (e) => this.connectListener(e))
     window.addEventListener('gamepaddisconnected', (e) => this.disconnectListener(e))
@@ -12,6 +12,8 @@ export const enum GameboyLayoutButton {
 
 export type GameboyLayoutButtons = number
 export type Callback = (buttons: GameboyLayoutButtons) => void
+// 倾斜, 单位为g, x向右为正, y向下为正
+export type TiltCallback = (x: number, y: number) => void
 export const enum GamepadMode {
   Virtual,
   Physical
@@ -2,7 +2,7 @@ import type { Config } from '../../config'
 import { PhysicalGamepad } from './PhysicalGamepad'
 import { VirtualGamepad } from './VirutalGamepad'
 import { shallowRef, type ShallowRef } from 'vue'
-import { type Callback, GameboyLayoutButton, GamepadMode } from './constants'
+import { type Callback, type TiltCallback, GameboyLayoutButton, GamepadMode } from './constants'
 
 export class EmuGamepad {
   public readonly mode: ShallowRef<GamepadMode>
@@ -10,16 +10,24 @@ export class EmuGamepad {
   public readonly physical: PhysicalGamepad
   public readonly activeA = shallowRef(false)
   public readonly activeB = shallowRef(false)
-  public constructor(config: Config, callback: Callback) {
+  public constructor(config: Config, callback: Callback, tiltCallback: TiltCallback) {
     const mode = config.gamepadMode
     this.mode = mode
-    this.physical = new PhysicalGamepad(config, (buttons) => {
-      if (mode.value === GamepadMode.Physical) {
-        callback(buttons)
-        this.activeA.value = (buttons & (1 << GameboyLayoutButton.A)) != 0b0000_0000
-        this.activeB.value = (buttons & (1 << GameboyLayoutButton.B)) != 0b0000_0000
+    this.physical = new PhysicalGamepad(
+      config,
+      (buttons) => {
+        if (mode.value === GamepadMode.Physical) {
+          callback(buttons)
+          this.activeA.value = (buttons & (1 << GameboyLayoutButton.A)) != 0b0000_0000
+          this.activeB.value = (buttons & (1 << GameboyLayoutButton.B)) != 0b0000_0000
+        }
+      },
+      (x, y) => {
+        if (mode.value === GamepadMode.Physical) {
+          tiltCallback(x, y)
+        }
       }
-    })
+    )
     this.virtual = new VirtualGamepad(config, (buttons) => {
       if (mode.value === GamepadMode.Virtual) {
         callback(buttons)
@@ -30,4 +38,5 @@ export class EmuGamepad {
   }
 }
 
-export const useGamepad = (config: Config, callback: Callback) => new EmuGamepad(config, callback)
+export const useGamepad = (config: Config, callback: Callback, tiltCallback: TiltCallback) =>
+  new EmuGamepad(config, callback, tiltCallback)
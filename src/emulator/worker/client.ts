@@ -57,7 +57,7 @@ export class Client {
     this.requester = new Requester(requestPort)
     this.listener = new Listener(listenPort)
     this.server = server
-    this.gamepad = useGamepad(config, (btns) => this.btnAction(btns))
+    this.gamepad = useGamepad(config, (btns) => this.btnAction(btns), (x, y) => this.setTilt(x, y))
     this.stat = useStat(config)
     this.audioCtx = audioCtx
     this.init()
@@ -72,6 +72,7 @@ export class Client {
     const { cpu, cycles, state, serialBytes: bytes, rom, actualFPS: fps } = this.stat
     const { freqScale } = this.config
     this.on('log', (logs) => log_batch(logs))
+    this.on('rumble', (on) => this.gamepad.physical.rumble(on))
     this.on(
       'update',
       ({ state: $state, cycles: $cycles, cpu: $cpu, byte: $byte, rom: $rom, fps: $fps }) => {
@@ -267,6 +268,10 @@ export class Client {
   public setVolume(volume: number) {
     this.request('set-volume', volume)
   }
+
+  public setTilt(x: number, y: number) {
+    this.request('set-tilt', { x, y })
+  }
 }
 
 // const createAudioWorklet = async (ctx: AudioContext, audioPort: MessagePort) => {
@@ -82,6 +82,14 @@ export type ClientSideEvent = {
     ret: undefined
     err: undefined
   }
+  'set-tilt': {
+    args: {
+      x: number
+      y: number
+    }
+    ret: undefined
+    err: undefined
+  }
 }
 
 export type ServerSideEvent = {
@@ -97,4 +105,5 @@ export type ServerSideEvent = {
     rom?: CartInfo | null
     fps?: number
   }
+  rumble: boolean
 }
@@ -116,12 +116,19 @@ export class Server {
 
   private update() {
     const now = Date.now()
-    const { err, cpu, cycles } = this.core.update({
+    const { err, stop, cpu, cycles, rumble } = this.core.update({
       ...this.updateInput,
       timestamp: now
     })
     const fps = this.updateFPSandCycles(cycles)
-    if (err === null) {
+    if (rumble !== null) {
+      this.emit('rumble', rumble)
+    }
+    if (stop !== null) {
+      this.state = State.Paused
+      this.emit('update', { state: State.Paused, cpu, cycles, fps })
+      this.log(LogLevel.Info, stop)
+    } else if (err === null) {
       this.emit('update', { cpu, cycles, fps })
     } else {
       this.state = State.Aborted
@@ -132,13 +139,16 @@ export class Server {
 
   private _step() {
     const now = Date.now()
-    const { err, cpu, cycles } = this.core.step({
+    const { err, stop, cpu, cycles } = this.core.step({
       ...this.updateInput,
       timestamp: now
     })
     this.lastCycles = cycles
     if (err === null) {
       this.emit('update', { cpu, cycles, fps: 0 })
+      if (stop !== null) {
+        this.log(LogLevel.Info, stop)
+      }
     } else {
       this.state = State.Aborted
       this.emit('update', { state: State.Aborted, cycles, cpu, fps: 0 })
@@ -160,7 +170,8 @@ export class Server {
       shutdown: this.handleShutdown(),
       save: this.handleSave(),
       load: this.handleLoad(),
-      'set-volume': this.handleSetVolume()
+      'set-volume': this.handleSetVolume(),
+      'set-tilt': this.handleSetTilt()
     }
   }
 
@@ -267,7 +278,7 @@ export class Server {
 
   private handleSave(): Handler<'save'> {
     return () => {
-      const data = this.core.save()
+      const data = this.core.save(Date.now())
       if (data !== undefined) {
         return Right(
           {
@@ -292,10 +303,8 @@ export class Server {
         })
         return Right(undefined)
       } else {
-        this.state = State.Aborted
-        this.emit('update', {
-          state: State.Aborted
-        })
+        // 存档不属于当前卡带时模拟器状态保持不变
+        this.log(LogLevel.Warn, 'failed to load save state! load the matching rom first!')
         return Throw(undefined)
       }
     }
@@ -307,4 +316,11 @@ export class Server {
       return NONE
     }
   }
+
+  private handleSetTilt(): Handler<'set-tilt'> {
+    return ({ x, y }) => {
+      this.core.setTilt(x, y)
+      return NONE
+    }
+  }
 }